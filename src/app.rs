@@ -1,6 +1,8 @@
-use crate::config::{AiEngine, AppConfig};
-use crate::lcu::{self, LcuState};
+use crate::config::{AiEngine, AppConfig, PromptTemplate};
+use crate::lcu::{self, LcuErrorKind, LcuState};
+use crate::live::{self, LiveGameState};
 use crate::opgg;
+use crate::tips;
 use crate::openai;
 use crate::types::*;
 use crate::win32;
@@ -29,26 +31,833 @@ fn save_favorites(fav: &HashSet<String>) {
     }
 }
 
+fn notes_path() -> std::path::PathBuf {
+    exe_dir_file("notes.json")
+}
+
+/// 对线笔记：`我方slug|敌方slug|位置` → 内容
+fn load_notes() -> HashMap<String, String> {
+    match std::fs::read_to_string(notes_path()) {
+        Ok(s) => serde_json::from_str(&s).unwrap_or_default(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+fn save_notes(notes: &HashMap<String, String>) {
+    if let Ok(json) = serde_json::to_string_pretty(notes) {
+        let _ = std::fs::write(notes_path(), json);
+    }
+}
+
+/// 收藏导出/导入文件（exe 同目录，方便分享给朋友）
+const FAVORITES_EXPORT_FILE: &str = "favorites_export.json";
+const FAVORITES_IMPORT_FILE: &str = "favorites_import.json";
+
+fn exe_dir_file(name: &str) -> std::path::PathBuf {
+    let exe = std::env::current_exe().unwrap_or_default();
+    exe.parent().unwrap_or(std::path::Path::new(".")).join(name)
+}
+
+/// 去掉 AI 输出开头的调试头（引擎/模型/Prompt）
+fn strip_debug_header(text: &str) -> &str {
+    text.rfind(openai::DEBUG_HEADER_END)
+        .map_or(text, |i| &text[i + openai::DEBUG_HEADER_END.len()..])
+}
+
+/// AI 输出文本：可选中复制、自动换行
+///
+/// 选区按控件 id 保存，流式追加内容时 id 不变，已选中的部分不会被重置。
+fn ai_text_label(ui: &mut egui::Ui, text: &str) {
+    ui.add(egui::Label::new(text).selectable(true).wrap());
+}
+
+/// 文件名中替换掉 Windows 不允许的字符
+fn sanitize_filename(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| if c.is_control() || "<>:\"/\\|?*".contains(c) || c.is_whitespace() { '_' } else { c })
+        .collect()
+}
+
+/// 已保存 AI 分析对应的对局信息
+#[derive(Clone)]
+struct AiAnalysisInfo {
+    my_slug: String,
+    my_name: String,
+    enemy_slug: String,
+    enemy_name: String,
+    /// OP.GG 位置名（可为空）
+    position: String,
+    /// 对位胜率（%），没有对位数据时为 None
+    win_rate: Option<f64>,
+    model: String,
+}
+
+/// 批量对位分析中的一项
+struct AiBatchItem {
+    info: AiAnalysisInfo,
+    text: String,
+    /// 已结束（完成、出错或命中缓存）
+    done: bool,
+    cached: bool,
+}
+
+/// 正在编辑的手动对位数据（克制表右键「自定义胜率」）
+struct OverrideEdit {
+    /// overrides.json 中的 key："slug:POS:对手slug"
+    key: String,
+    name: String,
+    win_rate: f64,
+    games: i64,
+}
+
+/// 敌方威胁一项：克制我方候选英雄的程度 + 该玩家段位高于本局平均的程度
+struct EnemyThreat {
+    champion_id: i64,
+    slug: String,
+    name: String,
+    /// 我方候选英雄中对它胜率最低的一个 (英雄名, 胜率)
+    worst: Option<(String, f64)>,
+    rank: Option<String>,
+    score: f64,
+}
+
+/// 我方候选英雄中对 enemy_slug 胜率最低的一个 (slug, 胜率)，场次少于 min_games 的对局不计入
+fn worst_matchup<'a>(
+    cache: &OpggCache,
+    overrides: &opgg::CounterOverrides,
+    picks: &'a [String],
+    enemy_slug: &str,
+    lcu_position: &str,
+    min_games: i64,
+) -> Option<(&'a str, f64)> {
+    picks
+        .iter()
+        .filter_map(|p| {
+            opgg::matchup_win_rate(cache, overrides, p, enemy_slug, lcu_position)
+                .filter(|(_, games)| *games >= min_games)
+                .map(|(wr, _)| (p.as_str(), wr))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// 敌方威胁的克制分：我方最低胜率低于 50% 的百分点
+fn counter_threat_points(worst_win_rate: Option<f64>) -> f64 {
+    worst_win_rate.map_or(0.0, |wr| (50.0 - wr).max(0.0))
+}
+
+/// 选人阶段中文名
+fn champ_select_phase_cn(phase: &str) -> &'static str {
+    match phase {
+        "PLANNING" => "预选",
+        "BAN_PICK" => "禁选",
+        "FINALIZATION" => "确认",
+        "GAME_STARTING" => "即将开始",
+        _ => "选人",
+    }
+}
+
+/// LCU 报告的选人 gameId 是否是新的一局（0 表示不在选人，不算新局）
+fn is_new_session(last_session_id: i64, session_id: i64) -> bool {
+    session_id != 0 && session_id != last_session_id
+}
+
+/// 本局选人数据：来自 LCU 的敌我阵容、意向，以及界面上的选中项
+#[derive(Default)]
+struct Draft {
+    enemies: Vec<EnemyInfo>,
+    /// 玩家信息（全部10人）
+    teammates: Vec<TeamMateInfo>,
+    lane_enemy_id: Option<i64>,
+    /// 大乱斗候选席英雄 id
+    bench: Vec<i64>,
+    /// 其他玩家未锁定的 ban / 选英雄意向
+    intents: Vec<ChampIntent>,
+    /// 我的英雄 slug（LCU 选人或调试设置）
+    my_champ_slug: String,
+    /// my_champ_slug 来自 LCU 选人（而不是调试面板手动选择），离开选人时随之清空
+    my_champ_from_lcu: bool,
+    selected_enemy_idx: Option<usize>,
+    selected_teammate_idx: Option<usize>,
+    /// 手动指定的位置：champion_id → LCU 位置（新一局选人时清空）
+    pos_overrides: HashMap<i64, String>,
+    last_session_id: i64,
+}
+
+/// Draft::apply_lcu 替换了哪些列表（App 据此重新查询克制数据、预取战绩）
+#[derive(Default)]
+struct DraftChange {
+    enemies: bool,
+    teammates: bool,
+}
+
+impl Draft {
+    /// 清空当前选人的敌方、队友及相关选中状态
+    fn clear(&mut self) {
+        self.enemies.clear();
+        self.teammates.clear();
+        self.bench.clear();
+        self.intents.clear();
+        self.selected_enemy_idx = None;
+        self.selected_teammate_idx = None;
+        self.lane_enemy_id = None;
+        self.my_champ_slug.clear();
+        self.my_champ_from_lcu = false;
+    }
+
+    /// 合并一次 LCU 状态，用到的字段从 state 中取走
+    ///
+    /// lock_data 时回到大厅不用空列表覆盖上一次的数据；新的一局则总是先清掉上一局的手动选位，
+    /// clear_on_new_session 时连同阵容和选中项一起清空。
+    fn apply_lcu(&mut self, state: &mut LcuState, lock_data: bool, clear_on_new_session: bool) -> DraftChange {
+        // 新的一局：先清掉上一局的残留，避免下面「新数据为空则保留旧数据」把它们留下
+        if is_new_session(self.last_session_id, state.session_id) {
+            self.last_session_id = state.session_id;
+            self.pos_overrides.clear();
+            if clear_on_new_session {
+                self.clear();
+            }
+        }
+        let mut change = DraftChange::default();
+        // 只在有新数据时更新 enemies，断线保留旧数据
+        let keep = lock_data && state.enemies.is_empty();
+        if !keep && (!state.enemies.is_empty() || (state.error.is_empty() && state.info.is_empty())) {
+            self.enemies = std::mem::take(&mut state.enemies);
+            change.enemies = true;
+        }
+        if !state.teammates.is_empty() {
+            self.teammates = std::mem::take(&mut state.teammates);
+            change.teammates = true;
+        }
+        if !state.my_champ_slug.is_empty() {
+            self.my_champ_slug = std::mem::take(&mut state.my_champ_slug);
+            self.my_champ_from_lcu = true;
+        } else if !keep && !state.info.is_empty() && self.my_champ_from_lcu {
+            // 已离开选人 / 游戏：上一局的英雄不再适用
+            self.my_champ_slug.clear();
+            self.my_champ_from_lcu = false;
+        }
+        if !keep {
+            self.lane_enemy_id = state.lane_enemy_id;
+            self.bench = std::mem::take(&mut state.bench);
+            self.intents = std::mem::take(&mut state.intents);
+        }
+        change
+    }
+}
+
+/// 用系统默认程序打开 config.toml；没有关联程序时改为打开所在目录
+fn open_config_file() -> Result<(), String> {
+    if crate::config::config_encrypted() {
+        return Err("配置已加密，请先在调试面板「解密配置」".into());
+    }
+    let path = crate::config::config_path();
+    let dir = path.parent().map(|d| d.to_path_buf()).unwrap_or_default();
+    if path.exists() && win32::open_url(&path.display().to_string()).is_ok() {
+        return Ok(());
+    }
+    win32::open_url(&dir.display().to_string())
+}
+
+/// 把 AI 分析保存为 analyses/<我方>-vs-<敌方>-<位置>.md（重名时追加序号），返回文件路径
+fn save_ai_analysis(info: &AiAnalysisInfo, text: &str) -> Result<std::path::PathBuf, String> {
+    let dir = exe_dir_file("analyses");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("创建目录失败: {e}"))?;
+    let pos_slug = crate::types::opgg_pos_slug(&info.position);
+    let mut base = format!("{}-vs-{}", sanitize_filename(&info.my_slug), sanitize_filename(&info.enemy_slug));
+    if !pos_slug.is_empty() {
+        base = format!("{base}-{pos_slug}");
+    }
+    let mut path = dir.join(format!("{base}.md"));
+    let mut n = 2;
+    while path.exists() {
+        path = dir.join(format!("{base}-{n}.md"));
+        n += 1;
+    }
+    let pos = pos_cn(&info.position);
+    let out = format!(
+        "# {} vs {}{}\n\n- 胜率：{}\n- 模型：{}\n- 时间：{}\n\n{}\n",
+        info.my_name,
+        info.enemy_name,
+        if pos.is_empty() { String::new() } else { format!("（{pos}）") },
+        info.win_rate.map_or("未知".into(), |wr| format!("{wr:.2}%")),
+        info.model,
+        chrono::Local::now().format("%Y-%m-%d %H:%M"),
+        text.trim(),
+    );
+    std::fs::write(&path, out).map_err(|e| format!("写入失败: {e}"))?;
+    Ok(path)
+}
+
+/// CSV 字段转义
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// 导出克制表到带时间戳的 CSV（带 UTF-8 BOM，Excel 可直接打开中文），返回文件路径
+fn export_counters_csv(
+    slug: &str,
+    rows: &[CounterDisplay],
+    favs: &HashSet<String>,
+) -> Result<std::path::PathBuf, String> {
+    let ts = chrono::Local::now().format("%Y%m%d_%H%M%S");
+    let path = exe_dir_file(&format!("counters_{slug}_{ts}.csv"));
+    let mut out = String::from("\u{feff}name,key,win_rate,games,delta,is_favorite\n");
+    for r in rows {
+        out += &format!(
+            "{},{},{:.2},{},{},{}\n",
+            csv_field(&r.name),
+            csv_field(&r.key),
+            r.win_rate,
+            r.games,
+            r.delta.map(|d| format!("{d:.2}")).unwrap_or_default(),
+            favs.contains(&r.key),
+        );
+    }
+    std::fs::write(&path, out).map_err(|e| format!("写入失败: {e}"))?;
+    Ok(path)
+}
+
+/// 导出收藏为 JSON，返回文件路径
+fn export_favorites(fav: &HashSet<String>) -> Result<std::path::PathBuf, String> {
+    let path = exe_dir_file(FAVORITES_EXPORT_FILE);
+    let mut list: Vec<&String> = fav.iter().collect();
+    list.sort();
+    let json = serde_json::to_string_pretty(&list).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("写入失败: {e}"))?;
+    Ok(path)
+}
+
+/// 从 favorites_import.json 读取收藏列表
+fn import_favorites() -> Result<HashSet<String>, String> {
+    let path = exe_dir_file(FAVORITES_IMPORT_FILE);
+    let s = std::fs::read_to_string(&path)
+        .map_err(|_| format!("未找到 {}", path.display()))?;
+    serde_json::from_str(&s).map_err(|e| format!("{FAVORITES_IMPORT_FILE} 格式错误: {e}"))
+}
+
+/// 敌方玩家 `name#tag` 列表（跳过没有 tag 的玩家）
+fn enemy_riot_ids(teammates: &[TeamMateInfo]) -> Vec<String> {
+    teammates
+        .iter()
+        .filter(|m| !m.is_ally && !m.summoner_name.is_empty() && !m.tag_line.is_empty())
+        .map(|m| format!("{}#{}", m.summoner_name, m.tag_line))
+        .collect()
+}
+
+/// 候选中文字体（按顺序取第一个存在的）
+const CJK_FONT_CANDIDATES: &[&str] = &[
+    r"C:\Windows\Fonts\msyh.ttc",
+    r"C:\Windows\Fonts\msyh.ttf",
+    r"C:\Windows\Fonts\msjh.ttc",
+    r"C:\Windows\Fonts\Deng.ttf",
+    r"C:\Windows\Fonts\simhei.ttf",
+    r"C:\Windows\Fonts\simsun.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+];
+
+/// 加载中文字体，返回使用的字体文件名（都找不到时返回空，中文会显示为方块）
+fn load_cjk_font(ctx: &egui::Context) -> String {
+    let mut fonts = egui::FontDefinitions::default();
+    let found = CJK_FONT_CANDIDATES
+        .iter()
+        .find_map(|p| std::fs::read(p).ok().map(|data| (*p, data)));
+    let Some((path, font_data)) = found else {
+        return String::new();
+    };
+    fonts.font_data.insert(
+        "cjk".to_owned(),
+        Arc::new(egui::FontData::from_owned(font_data)),
+    );
+    fonts
+        .families
+        .entry(egui::FontFamily::Proportional)
+        .or_default()
+        .insert(0, "cjk".to_owned());
+    fonts
+        .families
+        .entry(egui::FontFamily::Monospace)
+        .or_default()
+        .insert(0, "cjk".to_owned());
+    ctx.set_fonts(fonts);
+    std::path::Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// OP.GG 支持的地区（手动查询下拉框）
+const OPGG_REGIONS: &[&str] = &[
+    "kr", "jp", "na", "euw", "eune", "oce", "br", "las", "lan", "ru", "tr", "sg", "ph", "tw", "vn", "th", "me",
+];
+
+/// 对局记录查询目标
+#[derive(Debug, Clone, Default)]
+struct HistoryQuery {
+    game_name: String,
+    tag_line: String,
+    display_name: String,
+    region: String,
+    /// 已知段位（来自 LCU），手动查询时为空，由 OP.GG 结果补全
+    rank: Option<SoloRank>,
+}
+
+impl HistoryQuery {
+    fn cache_key(&self) -> String {
+        history_cache_key(&self.region, &self.game_name, &self.tag_line)
+    }
+}
+
+/// 战绩 / 段位缓存的 key：同名同标签的玩家在不同服务器是不同的人，需带上地区
+fn history_cache_key(region: &str, game_name: &str, tag_line: &str) -> String {
+    format!("{region}:{game_name}-{tag_line}")
+}
+
+/// 批量侦察最多查询的玩家数
+const SCOUT_MAX_PLAYERS: usize = 10;
+/// 批量侦察同时进行的 OP.GG 请求数
+const SCOUT_CONCURRENCY: usize = 3;
+
+/// OP.GG 战绩查询结果：(对局列表, 单双排段位)
+type HistoryResult = Result<(Vec<MatchEntry>, Option<SoloRank>), String>;
+
+/// 批量侦察中的一名玩家
+struct ScoutEntry {
+    riot_id: String,
+    /// 去掉首尾空白后的 (名字, 标签)，格式不对时为 None；查询和写入战绩缓存都用它
+    name_tag: Option<(String, String)>,
+    /// 查询时的地区（写入战绩缓存用）
+    region: String,
+    /// None = 加载中
+    result: Option<HistoryResult>,
+}
+
+/// 解析 `名字#标签`，名字和标签去掉首尾空白后都不能为空
+fn parse_riot_id(riot_id: &str) -> Option<(String, String)> {
+    let (name, tag) = riot_id.split_once('#')?;
+    let (name, tag) = (name.trim(), tag.trim());
+    (!name.is_empty() && !tag.is_empty()).then(|| (name.to_string(), tag.to_string()))
+}
+
+/// 近期战绩摘要：(胜场, 总场次, 常用英雄 [(英雄名, 场次)]，最多 3 个)
+fn history_summary(entries: &[MatchEntry]) -> (usize, usize, Vec<(String, usize)>) {
+    let wins = entries.iter().filter(|e| e.win).count();
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for e in entries {
+        match counts.iter_mut().find(|(n, _)| *n == e.champion_name) {
+            Some((_, c)) => *c += 1,
+            None => counts.push((e.champion_name.clone(), 1)),
+        }
+    }
+    // 稳定排序：同场次按首次出现（最近）顺序
+    counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+    counts.truncate(3);
+    (wins, entries.len(), counts)
+}
+
+/// 查找 LoL 窗口（吸附）的默认间隔（毫秒），可在界面调整
+const WINDOW_CHECK_INTERVAL_MS: u64 = 500;
+
+/// 平滑吸附每帧向目标位置移动的比例，距离小于 DOCK_SNAP_DISTANCE（逻辑像素）时直接到位
+const DOCK_LERP: f32 = 0.3;
+const DOCK_SNAP_DISTANCE: f32 = 2.0;
+
+/// 对线敌人持续这么久不变才自动选中（敌方锁定后才出现在列表中，防抖避免换位/交换时来回切换）
+const LANE_ENEMY_SETTLE: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// 下路组合胜率查询失败后，过这么久再重试
+const DUO_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// 英雄熟练度查询失败后，过这么久再重试（客户端刚启动时接口可能还没就绪）
+const MASTERY_RETRY_AFTER: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// 界面偏好在 eframe 存储中的键
+const UI_PREFS_KEY: &str = "ui_prefs";
+
+/// 吸附到客户端窗口的哪一侧
+#[derive(Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+enum DockSide {
+    Left,
+    Right,
+    /// 哪侧屏幕空间大就吸附到哪侧
+    Auto,
+}
+
+impl DockSide {
+    fn label(self) -> &'static str {
+        match self {
+            DockSide::Left => "左侧",
+            DockSide::Right => "右侧",
+            DockSide::Auto => "自动",
+        }
+    }
+}
+
+/// 界面偏好（随 eframe persistence 保存）
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+struct UiPrefs {
+    /// 克制表最少场次，低于此值的行不显示
+    min_games: i64,
+    /// 克制表只显示收藏的英雄
+    favorites_only: bool,
+    /// 胜率着色使用色盲友好配色（蓝/橙）
+    colorblind_palette: bool,
+    /// 紧凑模式：隐藏战绩面板和 AI 调试头，克制表行数减少
+    compact: bool,
+    /// 隐私模式：隐藏玩家名和战绩，并将窗口从录屏中排除（直播用）
+    privacy: bool,
+    /// 窗口置顶（关闭后重启也保持普通窗口层级）
+    topmost: bool,
+    /// 上次选择的 AI 引擎 / 模型（按名称保存，config 调整顺序后仍有效）
+    ai_engine: String,
+    ai_model: String,
+    /// 上次选择的提示词模板名（空为内置默认）
+    ai_template: String,
+    /// ai_template 是否保存过；没保存过且配置了 prompt_template 时默认用「自定义」
+    ai_template_saved: bool,
+    /// 克制表可见行数（敌方/队友列表高度按比例缩放）
+    table_rows: usize,
+    /// 吸附方向与间距（逻辑像素）
+    dock_side: DockSide,
+    dock_gap: f32,
+    /// 查找客户端窗口的间隔（毫秒），越小跟随越及时
+    dock_interval_ms: u64,
+    /// 平滑移动到吸附位置（而不是直接跳过去）
+    dock_smooth: bool,
+    /// 游戏内按固定时间表提醒地图资源刷新
+    objective_reminders: bool,
+    reminder_objectives: Vec<live::Objective>,
+    /// 提前多少秒提醒
+    reminder_lead_secs: u32,
+}
+
+impl Default for UiPrefs {
+    fn default() -> Self {
+        Self {
+            min_games: 500,
+            favorites_only: false,
+            colorblind_palette: false,
+            compact: false,
+            privacy: false,
+            topmost: true,
+            ai_engine: String::new(),
+            ai_model: String::new(),
+            ai_template: String::new(),
+            ai_template_saved: false,
+            table_rows: 10,
+            dock_side: DockSide::Right,
+            dock_gap: 6.0,
+            dock_interval_ms: WINDOW_CHECK_INTERVAL_MS,
+            dock_smooth: false,
+            objective_reminders: false,
+            reminder_objectives: live::Objective::ALL.to_vec(),
+            reminder_lead_secs: 30,
+        }
+    }
+}
+
+impl UiPrefs {
+    /// 当前布局的窗口高度
+    fn window_height(&self) -> f32 {
+        if self.compact { 500.0 } else { 900.0 }
+    }
+
+    /// 克制表行数（紧凑模式最多 5 行）
+    fn counter_rows(&self) -> f32 {
+        let rows = self.table_rows.clamp(3, 30);
+        if self.compact { rows.min(5) as f32 } else { rows as f32 }
+    }
+
+    /// 查找客户端窗口的间隔（100ms–2s）
+    fn dock_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.dock_interval_ms.clamp(100, 2000))
+    }
+
+    /// 敌方/队友列表行数：默认 5 行，随表格行数按比例缩放
+    fn list_rows(&self) -> f32 {
+        (self.counter_rows() / 2.0).clamp(3.0, 10.0)
+    }
+}
+
+/// 右键菜单：手动指定位置。返回 Some(Some(pos)) 设置、Some(None) 恢复自动
+fn pos_override_menu(ui: &mut egui::Ui, current: Option<&String>) -> Option<Option<String>> {
+    let mut action = None;
+    ui.label("指定位置：");
+    for pos in ["TOP", "JUNGLE", "MIDDLE", "BOTTOM", "UTILITY"] {
+        if ui.selectable_label(current.map(String::as_str) == Some(pos), pos_cn(pos)).clicked() {
+            action = Some(Some(pos.to_string()));
+        }
+    }
+    if ui.button("恢复自动").clicked() {
+        action = Some(None);
+    }
+    if action.is_some() {
+        ui.close_menu();
+    }
+    action
+}
+
+/// 克制表胜率着色：>53 优势，48–53 均势，<48 劣势
+fn win_rate_color(wr: f64, colorblind: bool) -> egui::Color32 {
+    match (wr, colorblind) {
+        (w, false) if w > 53.0 => egui::Color32::from_rgb(60, 180, 80),
+        (w, false) if w < 48.0 => egui::Color32::from_rgb(220, 60, 60),
+        (_, false) => egui::Color32::from_rgb(210, 180, 60),
+        // Okabe-Ito 配色
+        (w, true) if w > 53.0 => egui::Color32::from_rgb(86, 180, 233),
+        (w, true) if w < 48.0 => egui::Color32::from_rgb(230, 159, 0),
+        (_, true) => egui::Color32::from_rgb(200, 200, 200),
+    }
+}
+
+/// 胜率 95% 置信区间半宽（百分点），按二项分布正态近似；无场次时为无穷大
+fn win_rate_margin(wr: f64, games: i64) -> f64 {
+    if games <= 0 {
+        return f64::INFINITY;
+    }
+    let p = (wr / 100.0).clamp(0.0, 1.0);
+    1.96 * (p * (1.0 - p) / games as f64).sqrt() * 100.0
+}
+
+/// 样本少的胜率单元格：半宽 > 5 个百分点（约 400 场以下）淡化并加 *，3–5 之间轻微淡化
+fn confidence_shade(color: egui::Color32, margin: f64) -> (egui::Color32, &'static str) {
+    if margin > 5.0 {
+        (color.gamma_multiply(0.45), "*")
+    } else if margin > 3.0 {
+        (color.gamma_multiply(0.75), "")
+    } else {
+        (color, "")
+    }
+}
+
+/// 相对优势着色：正值（真正的克制）绿色，负值红色，±0.5 以内视为无差别
+fn delta_color(delta: f64, colorblind: bool) -> egui::Color32 {
+    match (delta, colorblind) {
+        (d, false) if d > 0.5 => egui::Color32::from_rgb(60, 180, 80),
+        (d, false) if d < -0.5 => egui::Color32::from_rgb(220, 60, 60),
+        (d, true) if d > 0.5 => egui::Color32::from_rgb(86, 180, 233),
+        (d, true) if d < -0.5 => egui::Color32::from_rgb(230, 159, 0),
+        _ => egui::Color32::GRAY,
+    }
+}
+
+/// 出现次数最多的值及次数（并列时取先出现的）
+fn most_common<'a>(values: impl Iterator<Item = &'a str>) -> Option<(&'a str, usize)> {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for v in values {
+        match counts.iter_mut().find(|(n, _)| *n == v) {
+            Some((_, c)) => *c += 1,
+            None => counts.push((v, 1)),
+        }
+    }
+    counts.into_iter().fold(None, |best, cur| match best {
+        Some(b) if b.1 >= cur.1 => Some(b),
+        _ => Some(cur),
+    })
+}
+
+/// 对局历史概况：平均时长、最常玩的队列和位置，如「均 28:15 · 多为单双排 (12) · 常玩打野 (9/15)」
+///
+/// 时长为 0 或队列未知的对局不计入；位置优先取列表数据，缺失时用已加载的对局详情。
+fn history_profile_text(
+    entries: &[MatchEntry],
+    details: &HashMap<String, Result<MatchDetail, String>>,
+) -> Option<String> {
+    let mut parts = Vec::new();
+    let durations: Vec<i64> = entries.iter().map(|e| e.game_duration_secs).filter(|&d| d > 0).collect();
+    if !durations.is_empty() {
+        let avg = durations.iter().sum::<i64>() / durations.len() as i64;
+        parts.push(format!("均 {}:{:02}", avg / 60, avg % 60));
+    }
+    if let Some((queue, n)) = most_common(entries.iter().filter(|e| e.queue_id > 0).map(|e| queue_name(e.queue_id))) {
+        parts.push(format!("多为{queue} ({n})"));
+    }
+    let positions: Vec<&str> = entries
+        .iter()
+        .map(|e| match details.get(&e.game_id) {
+            Some(Ok(d)) if e.position.is_empty() => d.position.as_str(),
+            _ => e.position.as_str(),
+        })
+        .filter(|p| !p.is_empty())
+        .collect();
+    if let Some((pos, n)) = most_common(positions.iter().copied()) {
+        parts.push(format!("常玩{} ({n}/{})", pos_cn(pos), positions.len()));
+    }
+    (!parts.is_empty()).then(|| parts.join(" · "))
+}
+
+/// 按时间衰减加权的胜率（%）：每过 half_life_hours 小时权重减半，时间未知的对局不计入
+///
+/// half_life_hours 不大于 0 或没有可用对局时返回 None。
+fn weighted_win_rate<'a>(entries: impl IntoIterator<Item = &'a MatchEntry>, half_life_hours: f64) -> Option<f64> {
+    if half_life_hours <= 0.0 {
+        return None;
+    }
+    let now_ms = chrono::Utc::now().timestamp_millis();
+    let (mut won, mut total) = (0.0, 0.0);
+    for e in entries.into_iter().filter(|e| e.timestamp_ms > 0) {
+        let age_hours = (now_ms - e.timestamp_ms).max(0) as f64 / 3_600_000.0;
+        let w = 0.5f64.powf(age_hours / half_life_hours);
+        total += w;
+        if e.win {
+            won += w;
+        }
+    }
+    (total > 0.0).then(|| won / total * 100.0)
+}
+
+/// 热力图一行：(我方英雄 slug, 对每个敌方的胜率/场次, 平均胜率)
+type HeatmapRow = (String, Vec<Option<(f64, i64)>>, f64);
+
+/// 热力图各行：每个收藏英雄对各敌方的胜率（我方视角），按有数据格子的平均胜率降序
+fn heatmap_rows(
+    cache: &OpggCache,
+    overrides: &opgg::CounterOverrides,
+    picks: &HashSet<String>,
+    enemy_slugs: &[&str],
+    lcu_position: &str,
+) -> Vec<HeatmapRow> {
+    let mut rows: Vec<HeatmapRow> = picks
+        .iter()
+        .map(|pick| {
+            let cells: Vec<Option<(f64, i64)>> = enemy_slugs
+                .iter()
+                .map(|e| opgg::matchup_win_rate(cache, overrides, pick, e, lcu_position))
+                .collect();
+            let known: Vec<f64> = cells.iter().flatten().map(|(wr, _)| *wr).collect();
+            let avg = if known.is_empty() { f64::NEG_INFINITY } else { known.iter().sum::<f64>() / known.len() as f64 };
+            (pick.clone(), cells, avg)
+        })
+        .collect();
+    rows.sort_by(|a, b| b.2.total_cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+    rows
+}
+
+/// 走势图最多显示的场次
+const SPARKLINE_GAMES: usize = 20;
+
+/// 对局历史顶部的走势图：胜负柱（胜向上、败向下）+ KDA 折线，从左到右由旧到新
+///
+/// `entries` 按时间倒序（最新在前），不足 3 场时不绘制。
+fn history_sparklines(ui: &mut egui::Ui, entries: &[&MatchEntry], colorblind: bool) {
+    if entries.len() < 3 {
+        return;
+    }
+    let games: Vec<&MatchEntry> = entries.iter().take(SPARKLINE_GAMES).rev().copied().collect();
+    let (win_color, loss_color) = if colorblind {
+        (egui::Color32::from_rgb(86, 180, 233), egui::Color32::from_rgb(230, 159, 0))
+    } else {
+        (egui::Color32::from_rgb(60, 180, 80), egui::Color32::from_rgb(220, 60, 60))
+    };
+    let width = (SPARKLINE_GAMES as f32 * 8.0).min(ui.available_width());
+    let step = width / SPARKLINE_GAMES as f32;
+
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("胜负").small().weak());
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, 16.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        let mid = rect.center().y;
+        painter.hline(rect.x_range(), mid, egui::Stroke::new(1.0, egui::Color32::from_gray(80)));
+        for (i, g) in games.iter().enumerate() {
+            let x = rect.left() + step * (i as f32 + 0.5);
+            let (y, color) = if g.win { (rect.top() + 1.0, win_color) } else { (rect.bottom() - 1.0, loss_color) };
+            painter.line_segment([egui::pos2(x, mid), egui::pos2(x, y)], egui::Stroke::new(step * 0.6, color));
+        }
+    });
+
+    // KDA 比值，无死亡按 K+A 计，上限 10 避免个别场次压扁整条线
+    let kdas: Vec<f32> = games
+        .iter()
+        .map(|g| ((g.kills + g.assists) as f32 / g.deaths.max(1) as f32).min(10.0))
+        .collect();
+    let max = kdas.iter().copied().fold(1.0_f32, f32::max);
+    let avg = kdas.iter().sum::<f32>() / kdas.len() as f32;
+    ui.horizontal(|ui| {
+        ui.label(egui::RichText::new("KDA").small().weak());
+        let (rect, resp) = ui.allocate_exact_size(egui::vec2(width, 20.0), egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        let y_of = |v: f32| rect.bottom() - 2.0 - (v / max) * (rect.height() - 4.0);
+        painter.hline(
+            rect.x_range(),
+            y_of(avg),
+            egui::Stroke::new(1.0, egui::Color32::from_gray(80)),
+        );
+        let points: Vec<egui::Pos2> = kdas
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| egui::pos2(rect.left() + step * (i as f32 + 0.5), y_of(v)))
+            .collect();
+        painter.add(egui::Shape::line(points.clone(), egui::Stroke::new(1.5, egui::Color32::LIGHT_GRAY)));
+        for (p, g) in points.iter().zip(&games) {
+            painter.circle_filled(*p, 2.0, if g.win { win_color } else { loss_color });
+        }
+        resp.on_hover_text(format!("近{}场平均 KDA {avg:.1}", kdas.len()));
+    });
+}
+
 /// 后台任务消息
 enum BgMsg {
-    /// LCU 状态更新
-    Lcu(LcuState),
+    /// LCU 状态更新（装箱，避免整个枚举随 LcuState 变大）
+    Lcu(Box<LcuState>),
+    /// 游戏内实时数据（Live Client Data API）
+    Live(LiveGameState),
+    /// 本地缓存的英雄图标
+    CachedIcons(lcu::ChampionIconData),
+    /// 没有本地缓存且从 Data Dragon 获取英雄数据失败
+    IconsFailed(String),
+    /// 下路组合胜率（OP.GG）
+    Duo { key: String, result: Result<Option<(f64, i64)>, String> },
+    /// 英雄熟练度（LCU）：key 为请求时的 puuid（空 = 当前玩家），结果带实际 puuid
+    Mastery { key: String, result: Result<(String, Vec<ChampionMastery>), String> },
+    /// 全局快捷键切换窗口显示失败
+    ToggleWindowFailed(String),
+    /// 推荐符文应用结果（成功时为符文页说明）
+    RunesApplied(Result<String, String>),
+    /// 批量侦察单个玩家结果（generation 用于丢弃旧批次）
+    Scout {
+        generation: u64,
+        idx: usize,
+        result: HistoryResult,
+    },
+    /// 单局详情（OP.GG）
+    MatchDetail { game_id: String, detail: Result<MatchDetail, String> },
+    /// 装备图标（Data Dragon），获取失败为 None
+    ItemIcon { id: i64, icon: Option<(Vec<u8>, u32, u32)> },
     /// 全量更新进度
     UpdateProgress(usize, usize, String),
     /// 全量更新完成
     UpdateDone(Result<OpggCache, String>),
-    /// AI 流式片段
-    AiChunk(String),
+    /// AI 流式片段（generation 用于丢弃已被新请求取代的流）
+    AiChunk { generation: u64, text: String },
     /// AI 流式结束（完整文本用于缓存）
-    AiDone { cache_key: String, full_text: String },
+    AiDone { generation: u64, cache_key: String, full_text: String, usage: Option<openai::TokenUsage> },
     /// AI 错误
-    AiError(String),
+    AiError { generation: u64, err: String },
+    /// AI 引擎连接测试结果：成功时为耗时
+    AiTest {
+        engine: String,
+        model: String,
+        result: Result<std::time::Duration, String>,
+    },
+    /// 从接口 /models 获取的模型列表
+    AiModels {
+        engine_idx: usize,
+        result: Result<Vec<String>, String>,
+    },
     /// 对局历史（OP.GG）
     MatchHistory {
         cache_key: String,
         name: String,
         url: String,
-        entries: Result<Vec<MatchEntry>, String>,
+        entries: HistoryResult,
     },
 }
 
@@ -61,23 +870,65 @@ pub struct App {
     // LCU 状态
     connected: bool,
     error: String,
-    enemies: Vec<EnemyInfo>,
+    /// LCU 连接错误的类别（找不到 / 读不了 lockfile 时显示配置引导）
+    lcu_error_kind: Option<LcuErrorKind>,
+    /// 用户点过 ✕ 的错误文本，相同内容不再显示
+    dismissed_error: String,
+    /// LCU 正常状态提示（如不在选人界面），灰色显示
+    lcu_info: String,
+    /// 本局选人数据（敌我阵容、意向、选中项）
+    draft: Draft,
     my_pos: String,
-    lane_enemy_id: Option<i64>,
+    /// 选人阶段倒计时：(LCU 阶段名, 阶段结束时刻)，不在选人或无计时数据时为 None
+    phase_deadline: Option<(String, std::time::Instant)>,
+    /// 首次运行生成的配置文件路径（提示框关闭后清空）
+    first_run_config: Option<std::path::PathBuf>,
+    // 对线笔记
+    notes: HashMap<String, String>,
+    note_key: String,
+    note_edit: String,
+    /// 最近一次 AI 分析的对局 (我方slug, 敌方slug, 位置)，笔记跟随它
+    note_matchup: Option<(String, String, String)>,
+    /// 自动选中对线敌人的防抖：(champion_id, 首次出现时间)
+    lane_enemy_pending: Option<(i64, std::time::Instant)>,
+    /// 已自动选中过的 (session_id, champion_id)，同一局同一英雄不重复触发
+    lane_enemy_auto_done: Option<(i64, i64)>,
+    /// 大乱斗模式：不分位置，克制数据合并全部位置
+    is_aram: bool,
+    /// 当前队列 id（选人 / 游戏中由 gameflow 提供，未知为 0），用于切换特殊模式的数据
+    queue_id: i64,
     champion_lang: String,
+    /// 客户端游戏版本（LCU）
+    game_version: String,
     last_update_time: String,
 
     // 选项
-    topmost: bool,
+    /// 已发送给窗口的置顶状态（变化时才发送 WindowLevel）
+    applied_topmost: Option<bool>,
+    /// 上次查找 LoL 窗口的时间（按 dock_interval 查找，不每帧枚举窗口）
+    last_window_check: Option<std::time::Instant>,
+    /// 吸附目标位置（逻辑坐标），每帧移向它
+    dock_target: Option<egui::Pos2>,
+    /// 快捷键注册状态（调试显示）
+    hotkey_status: String,
+    /// 启动时获取英雄图标失败的原因（调试显示，收到图标后清空）
+    icon_status: String,
+    /// 加密 / 解密配置的结果提示
+    config_crypt_status: String,
+    /// 通知 LCU 轮询线程重新加载英雄数据
+    lcu_reload: Arc<std::sync::atomic::AtomicBool>,
     autodock: bool,
+    /// 已手动重置窗口：下一次保存时清掉 eframe 记录的窗口几何，下次启动用默认位置
+    window_reset: bool,
+    /// 锁定数据：回到大厅后不用空列表覆盖上一次的敌方/队友（秒退后复盘用）
+    lock_data: bool,
     show_debug: bool,
-
-    // 敌方选中
-    selected_enemy_idx: Option<usize>,
+    prefs: UiPrefs,
 
     // 调试
     debug_slug: String,
     debug_hero_options: Vec<String>,
+    /// 添加敌方时的位置（空 = 按英雄常用位置自动分配）
     debug_pos: String,
     debug_pos_options: Vec<String>,
 
@@ -90,82 +941,205 @@ pub struct App {
     counter_champ_slug: String,
     counter_sort_desc: bool,
     counter_sort_col: String,
+    /// 键盘选中的克制表行（可见行中的序号，跟随当前排序），↑↓ 移动、Enter 分析
+    counter_selected_row: Option<usize>,
+    /// 反查模式：显示克制对方的英雄（而非对方英雄自己的对位数据）
+    counter_reverse: bool,
     counter_error: String,
     counter_favorites: HashSet<String>,
+    /// 手动覆盖的对位数据（overrides.json），优先于 OP.GG 数据
+    counter_overrides: opgg::CounterOverrides,
+    /// overrides.json 读取失败的原因；此时禁止保存，避免用空数据覆盖用户的文件
+    overrides_error: Option<String>,
+    override_edit: Option<OverrideEdit>,
+    /// AI 分析使用的我方英雄（slug），为空时跟随选人中的英雄
+    ai_pick: String,
+    ai_pick_search: String,
+    /// 导出/导入结果提示
+    export_status: String,
 
     // 全量更新
     updating: bool,
     update_progress_text: String,
+    stale_banner_dismissed: bool,
 
     // AI 分析
     ai_title: String,
     ai_text: String,
     ai_cache: HashMap<String, String>,
     ai_loading: bool,
+    /// 当前 AI 请求的序号，每次新请求加一，旧请求的流式消息按此丢弃
+    ai_generation: u64,
+    /// 最近一次请求的 token 用量
+    ai_usage: Option<openai::TokenUsage>,
     ai_engines: Vec<AiEngine>,
     ai_engine_idx: usize,
     ai_model_idx: usize,
+    /// 提示词模板，ai_template_idx 为 0 表示内置默认，其余对应 prompt_templates[idx - 1]
+    prompt_templates: Vec<PromptTemplate>,
+    ai_template_idx: usize,
     ai_cache_key: String,
     ai_chat_input: String,
     ai_chat_visible: bool,
+    /// 当前 AI 分析的对局（自由对话时为 None，不可保存）
+    ai_analysis_info: Option<AiAnalysisInfo>,
+    /// 批量分析我的英雄对每个敌方英雄，逐个串行请求
+    ai_batch: Vec<AiBatchItem>,
+    /// 正在流式输出的批量项下标
+    ai_batch_running: Option<usize>,
+    /// AI 面板显示在独立窗口中
+    ai_detached: bool,
+    ai_save_status: String,
+    /// 连接测试 / 获取模型列表进行中
+    ai_probing: bool,
+    ai_probe_status: String,
 
     // LCU poller 是否已启动
     lcu_started: bool,
     // 调试：找到的窗口信息
     debug_lol_win: String,
+    // 调试：使用的中文字体
+    cjk_font: String,
 
     // 英雄图标
     icon_textures: HashMap<i64, egui::TextureHandle>,
+    /// 图标原始数据 champion_id → (rgba, w, h)，用于重建纹理
+    icon_rgba: HashMap<i64, (Vec<u8>, u32, u32)>,
     slug_to_id: HashMap<String, i64>,
     name_to_id: HashMap<String, i64>,
     champ_names: HashMap<i64, String>,
-
-    // 玩家信息（全部10人）
-    teammates: Vec<TeamMateInfo>,
-    selected_teammate_idx: Option<usize>,
+    slug_to_alias: HashMap<String, String>,
+    /// slug → 定位标签（LCU roles），用于内置对线提示
+    slug_to_roles: HashMap<String, Vec<String>>,
+    item_names: HashMap<i64, String>,
+    spell_names: HashMap<i64, String>,
 
     // 手动选位
     selected_enemy_pos: String,
 
+    // 游戏内实时数据
+    live: LiveGameState,
+
+    // 推荐符文
+    rune_applying: bool,
+    rune_status: Option<Result<String, String>>,
+
     // 对局历史
     lcu_auth: Option<LcuAuth>,
     match_history: Vec<MatchEntry>,
     match_history_cache: HashMap<String, Vec<MatchEntry>>,
     match_history_loading: bool,
     match_history_name: String,
+    /// 当前面板对应的玩家
+    match_history_query: HistoryQuery,
+    match_history_rank: Option<SoloRank>,
+    /// OP.GG 查到的段位：cache_key → 段位
+    opgg_rank_cache: HashMap<String, SoloRank>,
+    // 下路组合胜率："adc+support" → 结果
+    /// 下路组合查询失败：key → (错误, 失败时间)，DUO_RETRY_AFTER 后重试（成功结果存在 opgg_cache）
+    duo_errors: HashMap<String, (String, std::time::Instant)>,
+    duo_loading: HashSet<String>,
+    // 英雄熟练度：puuid → 按点数降序的列表
+    mastery_cache: HashMap<String, Vec<ChampionMastery>>,
+    /// 熟练度查询失败：puuid → (错误, 失败时间)，MASTERY_RETRY_AFTER 后重试（当前玩家 key 为空串）
+    mastery_errors: HashMap<String, (String, std::time::Instant)>,
+    mastery_loading: HashSet<String>,
+    /// 当前登录玩家的 puuid（首次查询熟练度时获得）
+    my_puuid: String,
+    // 单局详情：game_id → 详情（失败结果保留用于显示，重新展开时重试）
+    match_detail_cache: HashMap<String, Result<MatchDetail, String>>,
+    match_detail_loading: HashSet<String>,
+    /// 装备图标纹理：item_id → 纹理
+    item_textures: HashMap<i64, egui::TextureHandle>,
+    /// 已请求过的装备图标（含获取失败的，失败项显示装备名，不再重复请求）
+    item_icon_requested: HashSet<i64>,
+    expanded_match: Option<String>,
+    /// 对局历史按英雄筛选（英雄名），None = 全部
+    history_champ_filter: Option<String>,
+    // 队友战绩预取（避战提醒）
+    history_prefetched: HashSet<String>,
+    last_prefetch: Option<std::time::Instant>,
+    /// 批量预取战绩的后台任务（离开选人时中止）
+    prefetch_tasks: Vec<tokio::task::JoinHandle<()>>,
+    // 手动查询玩家
+    search_input: String,
+    search_region: String,
+    search_error: String,
+    manual_search_active: bool,
+
+    // 批量侦察
+    scout_open: bool,
+    scout_input: String,
+    scout_entries: Vec<ScoutEntry>,
+    scout_generation: u64,
     history_panel_open: bool,
 }
 
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>, config: AppConfig, rt: Arc<tokio::runtime::Runtime>) -> Self {
-        // 加载中文字体
-        let mut fonts = egui::FontDefinitions::default();
-        let font_path = std::path::Path::new(r"C:\Windows\Fonts\msyh.ttc");
-        if let Ok(font_data) = std::fs::read(font_path) {
-            fonts.font_data.insert(
-                "msyh".to_owned(),
-                Arc::new(egui::FontData::from_owned(font_data)),
-            );
-            fonts
-                .families
-                .entry(egui::FontFamily::Proportional)
-                .or_default()
-                .insert(0, "msyh".to_owned());
-            fonts
-                .families
-                .entry(egui::FontFamily::Monospace)
-                .or_default()
-                .insert(0, "msyh".to_owned());
-        }
-        cc.egui_ctx.set_fonts(fonts);
+    /// `created_config`：本次启动新生成的 config.toml 路径（首次运行提示用）
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        config: AppConfig,
+        rt: Arc<tokio::runtime::Runtime>,
+        created_config: Option<std::path::PathBuf>,
+    ) -> Self {
+        let cjk_font = load_cjk_font(&cc.egui_ctx);
 
         let (tx, rx) = mpsc::unbounded_channel();
 
+        // 后台读取本地图标缓存；没有缓存时从 Data Dragon 获取一次（客户端未启动也能显示英雄名和图标）
+        {
+            let tx = tx.clone();
+            let ctx = cc.egui_ctx.clone();
+            let proxy_url = config.proxy_url.clone();
+            let locale = lcu::ddragon_locale(&config.opgg_locale, config.client_variant);
+            rt.spawn(async move {
+                let cached = tokio::task::spawn_blocking(lcu::load_cached_icons).await.ok().flatten();
+                let result = match cached {
+                    Some(data) => Ok(data),
+                    None => match crate::config::http_client(&proxy_url, std::time::Duration::from_secs(20)) {
+                        Ok(client) => lcu::fetch_ddragon_icons(&client, locale).await,
+                        Err(e) => Err(e),
+                    },
+                };
+                let _ = tx.send(match result {
+                    Ok(data) => BgMsg::CachedIcons(data),
+                    Err(e) => BgMsg::IconsFailed(e),
+                });
+                ctx.request_repaint();
+            });
+        }
+
         // 加载本地缓存
         let opgg_cache = opgg::load_local_data();
         let counter_favorites = load_favorites();
+        let (counter_overrides, overrides_error) = match opgg::load_overrides() {
+            Ok(o) => (o, None),
+            Err(e) => (opgg::CounterOverrides::new(), Some(e)),
+        };
         let ai_engines = config.get_engines();
+        let prefs: UiPrefs = cc.storage
+            .and_then(|s| eframe::get_value(s, UI_PREFS_KEY))
+            .unwrap_or_default();
+
+        // 恢复上次的引擎 / 模型，已从配置中删除则回到第一个
+        let ai_engine_idx = ai_engines.iter()
+            .position(|e| e.name == prefs.ai_engine)
+            .unwrap_or(0);
+        let ai_model_idx = ai_engines.get(ai_engine_idx)
+            .and_then(|e| e.get_models().iter().position(|m| *m == prefs.ai_model))
+            .unwrap_or(0);
+        let prompt_templates = config.get_prompt_templates();
+        let ai_template_idx = if !prefs.ai_template_saved && !config.prompt_template.trim().is_empty() {
+            // get_prompt_templates 把顶层 prompt_template 作为第一个模板「自定义」
+            1
+        } else {
+            prompt_templates.iter()
+                .position(|t| t.name == prefs.ai_template)
+                .map_or(0, |i| i + 1)
+        };
 
+        let search_region = config.region.clone();
         Self {
             config,
             rt,
@@ -173,15 +1147,36 @@ impl App {
             rx,
             connected: false,
             error: String::new(),
-            enemies: vec![],
+            lcu_error_kind: None,
+            dismissed_error: String::new(),
+            lcu_info: String::new(),
+            draft: Draft::default(),
             my_pos: String::new(),
-            lane_enemy_id: None,
+            phase_deadline: None,
+            first_run_config: created_config,
+            notes: load_notes(),
+            note_key: String::new(),
+            note_edit: String::new(),
+            note_matchup: None,
+            lane_enemy_pending: None,
+            lane_enemy_auto_done: None,
+            is_aram: false,
+            queue_id: 0,
             champion_lang: "unknown".to_string(),
+            game_version: String::new(),
             last_update_time: "N/A".to_string(),
-            topmost: true,
+            applied_topmost: None,
+            last_window_check: None,
+            dock_target: None,
+            hotkey_status: String::new(),
+            icon_status: String::new(),
+            config_crypt_status: String::new(),
+            lcu_reload: Arc::new(std::sync::atomic::AtomicBool::new(false)),
             autodock: true,
+            window_reset: false,
+            lock_data: false,
             show_debug: false,
-            selected_enemy_idx: None,
+            prefs,
             debug_slug: "ahri".to_string(),
             debug_hero_options: vec![
                 "ahri", "yasuo", "zed", "lux", "jinx", "thresh", "leona",
@@ -191,6 +1186,7 @@ impl App {
             ].into_iter().map(String::from).collect(),
             debug_pos: "MIDDLE".to_string(),
             debug_pos_options: vec![
+                String::new(),
                 "TOP".into(),
                 "JUNGLE".into(),
                 "MIDDLE".into(),
@@ -203,34 +1199,87 @@ impl App {
             counter_champ_slug: String::new(),
             counter_sort_desc: true,
             counter_sort_col: "win_rate".to_string(),
+            counter_selected_row: None,
+            counter_reverse: false,
             counter_error: String::new(),
+            counter_overrides,
+            overrides_error: overrides_error.clone(),
+            override_edit: None,
             counter_favorites,
+            ai_pick: String::new(),
+            ai_pick_search: String::new(),
+            export_status: overrides_error.unwrap_or_default(),
             updating: false,
             update_progress_text: String::new(),
+            stale_banner_dismissed: false,
             ai_title: "AI 对线分析".into(),
             ai_text: String::new(),
             ai_cache: HashMap::new(),
             ai_loading: false,
+            ai_generation: 0,
+            ai_usage: None,
             ai_engines,
-            ai_engine_idx: 0,
-            ai_model_idx: 0,
+            ai_engine_idx,
+            ai_model_idx,
+            prompt_templates,
+            ai_template_idx,
             ai_cache_key: String::new(),
             ai_chat_input: String::new(),
             ai_chat_visible: false,
+            ai_analysis_info: None,
+            ai_batch: Vec::new(),
+            ai_batch_running: None,
+            ai_detached: false,
+            ai_save_status: String::new(),
+            ai_probing: false,
+            ai_probe_status: String::new(),
             lcu_started: false,
             debug_lol_win: String::new(),
+            cjk_font,
             icon_textures: HashMap::new(),
+            icon_rgba: HashMap::new(),
             slug_to_id: HashMap::new(),
             name_to_id: HashMap::new(),
             champ_names: HashMap::new(),
-            teammates: vec![],
-            selected_teammate_idx: None,
+            slug_to_alias: HashMap::new(),
+            slug_to_roles: HashMap::new(),
+            item_names: HashMap::new(),
+            spell_names: HashMap::new(),
             selected_enemy_pos: String::new(),
+            live: LiveGameState::default(),
+            rune_applying: false,
+            rune_status: None,
             lcu_auth: None,
             match_history: vec![],
             match_history_cache: HashMap::new(),
             match_history_loading: false,
             match_history_name: String::new(),
+            match_history_query: HistoryQuery::default(),
+            match_history_rank: None,
+            opgg_rank_cache: HashMap::new(),
+            duo_errors: HashMap::new(),
+            duo_loading: HashSet::new(),
+            mastery_cache: HashMap::new(),
+            mastery_errors: HashMap::new(),
+            mastery_loading: HashSet::new(),
+            my_puuid: String::new(),
+            match_detail_cache: HashMap::new(),
+            match_detail_loading: HashSet::new(),
+            item_textures: HashMap::new(),
+            item_icon_requested: HashSet::new(),
+            expanded_match: None,
+            history_champ_filter: None,
+            history_prefetched: HashSet::new(),
+            last_prefetch: None,
+            prefetch_tasks: Vec::new(),
+            search_input: String::new(),
+            search_region,
+            search_error: String::new(),
+            manual_search_active: false,
+            scout_open: false,
+            scout_input: String::new(),
+            scout_entries: vec![],
+            scout_generation: 0,
             history_panel_open: false,
         }
     }
@@ -239,24 +1288,45 @@ impl App {
         while let Ok(msg) = self.rx.try_recv() {
             match msg {
                 BgMsg::Lcu(state) => {
+                    let mut state = *state;
                     self.connected = state.connected;
-                    // 只在有新数据时更新 enemies，断线保留旧数据
-                    if !state.enemies.is_empty() || state.error.is_empty() {
-                        self.enemies = state.enemies;
-                    }
-                    if !state.teammates.is_empty() {
-                        self.teammates = state.teammates;
+                    let change = self.draft.apply_lcu(&mut state, self.lock_data, self.config.clear_on_new_session);
+                    if change.enemies {
+                        self.refresh_selected_enemy();
                     }
-                    if !state.error.is_empty() {
-                        self.error = state.error;
-                    } else {
-                        self.error.clear();
+                    if change.teammates {
+                        self.start_bulk_prefetch(ctx);
+                    } else if state.connected {
+                        // 已离开选人（例如秒退），未完成的预取不再需要
+                        self.cancel_bulk_prefetch();
                     }
+                    self.error = state.error;
+                    self.lcu_error_kind = state.error_kind;
+                    self.lcu_info = state.info;
+                    // 收到时换算成截止时刻，两次轮询之间界面也能逐秒倒数
+                    self.phase_deadline = state.timer.map(|t| {
+                        (t.phase, std::time::Instant::now() + std::time::Duration::from_millis(t.left_ms as u64))
+                    });
                     if !state.my_pos.is_empty() {
                         self.my_pos = state.my_pos;
                     }
-                    self.lane_enemy_id = state.lane_enemy_id;
+                    if state.is_aram != self.is_aram || state.queue_id != self.queue_id {
+                        self.is_aram = state.is_aram;
+                        self.queue_id = state.queue_id;
+                        // 切换模式后按新的位置规则重新查询
+                        if !self.counter_champ_slug.is_empty() {
+                            let (slug, name, pos) = (
+                                self.counter_champ_slug.clone(),
+                                self.counter_champ_name.clone(),
+                                self.selected_enemy_pos.clone(),
+                            );
+                            self.load_counter_data(&slug, &name, &pos);
+                        }
+                    }
                     self.champion_lang = state.champion_lang;
+                    if !state.game_version.is_empty() {
+                        self.game_version = state.game_version;
+                    }
                     if state.auth.is_some() {
                         self.lcu_auth = state.auth;
                     }
@@ -265,38 +1335,127 @@ impl App {
 
                     // 加载英雄图标纹理（一次性）
                     if let Some(data) = state.champion_data {
-                        self.slug_to_id = data.slug_to_id;
-                        self.name_to_id = data.name_to_id;
-                        self.champ_names = data.id_to_name;
-                        for (id, (rgba, w, h)) in data.icons {
-                            let image = ColorImage::from_rgba_unmultiplied(
-                                [w as usize, h as usize],
-                                &rgba,
-                            );
-                            self.icon_textures.entry(id).or_insert_with(|| {
-                                ctx.load_texture(
-                                    format!("champ_{id}"),
-                                    image,
-                                    egui::TextureOptions::LINEAR,
-                                )
-                            });
+                        self.apply_champion_data(ctx, data);
+                    }
+                    self.auto_select_lane_enemy(ctx);
+                }
+                BgMsg::CachedIcons(data) => {
+                    // LCU 数据已到达时不覆盖
+                    if self.slug_to_id.is_empty() {
+                        self.apply_champion_data(ctx, data);
+                    }
+                }
+                BgMsg::Live(state) => {
+                    self.live = state;
+                }
+                BgMsg::Duo { key, result } => {
+                    self.duo_loading.remove(&key);
+                    match result {
+                        Ok(found) => {
+                            self.duo_errors.remove(&key);
+                            let fetched_at = chrono::Local::now().timestamp() as f64;
+                            let (win_rate, games) = match found {
+                                Some((wr, games)) => (Some(wr), games),
+                                None => (None, 0),
+                            };
+                            self.opgg_cache.duo_synergies.insert(key, DuoSynergy { win_rate, games, fetched_at });
+                            opgg::save_local_data(&self.opgg_cache);
+                        }
+                        Err(e) => {
+                            self.duo_errors.insert(key, (e, std::time::Instant::now()));
+                        }
+                    }
+                }
+                BgMsg::Mastery { key, result } => {
+                    self.mastery_loading.remove(&key);
+                    match result {
+                        Ok((puuid, list)) => {
+                            if key.is_empty() {
+                                self.my_puuid = puuid.clone();
+                            }
+                            self.mastery_errors.remove(&key);
+                            self.mastery_cache.insert(puuid, list);
+                        }
+                        Err(e) => {
+                            self.mastery_errors.insert(key, (e, std::time::Instant::now()));
+                            ctx.request_repaint_after(MASTERY_RETRY_AFTER);
+                        }
+                    }
+                }
+                BgMsg::ToggleWindowFailed(e) => {
+                    self.hotkey_status = e;
+                }
+                BgMsg::IconsFailed(e) => {
+                    // 期间已连上客户端拿到了英雄数据时不再提示
+                    if self.slug_to_id.is_empty() {
+                        self.icon_status = format!("获取英雄图标失败（连上客户端后会自动加载）：{e}");
+                    }
+                }
+                BgMsg::RunesApplied(result) => {
+                    self.rune_applying = false;
+                    self.rune_status = Some(result);
+                }
+                BgMsg::Scout { generation, idx, result } => {
+                    if generation != self.scout_generation {
+                        continue;
+                    }
+                    if let Some(entry) = self.scout_entries.get_mut(idx) {
+                        // 顺便写入战绩缓存，点开详情时不必重新请求
+                        if let (Ok((data, rank)), Some((name, tag))) = (&result, &entry.name_tag) {
+                            let key = history_cache_key(&entry.region, name, tag);
+                            if !data.is_empty() {
+                                self.match_history_cache.insert(key.clone(), data.clone());
+                            }
+                            if let Some(rank) = rank {
+                                self.opgg_rank_cache.insert(key, rank.clone());
+                            }
                         }
+                        entry.result = Some(result);
+                    }
+                }
+                BgMsg::MatchDetail { game_id, detail } => {
+                    self.match_detail_loading.remove(&game_id);
+                    if let Ok(d) = &detail {
+                        let items = d.items.clone();
+                        self.start_fetch_item_icons(&items, ctx);
+                    }
+                    self.match_detail_cache.insert(game_id, detail);
+                }
+                BgMsg::ItemIcon { id, icon } => {
+                    if let Some((rgba, w, h)) = icon {
+                        let image = ColorImage::from_rgba_unmultiplied([w as usize, h as usize], &rgba);
+                        let tex = ctx.load_texture(format!("item_{id}"), image, egui::TextureOptions::LINEAR);
+                        self.item_textures.insert(id, tex);
                     }
                 }
                 BgMsg::MatchHistory { cache_key, name, url, entries } => {
+                    if let Ok((_, Some(rank))) = &entries {
+                        self.opgg_rank_cache.insert(cache_key.clone(), rank.clone());
+                    }
+                    if cache_key != self.match_history_query.cache_key() {
+                        // 已切换到其他玩家，只更新缓存
+                        if let Ok((data, _)) = entries {
+                            if !data.is_empty() {
+                                self.match_history_cache.insert(cache_key, data);
+                            }
+                        }
+                        continue;
+                    }
                     self.match_history_loading = false;
                     self.match_history_name = name;
-                    match entries {
+                    if self.match_history_rank.is_none() {
+                        self.match_history_rank = self.opgg_rank_cache.get(&cache_key).cloned();
+                    }
+                    match entries.map(|(data, _)| data) {
                         Ok(data) if !data.is_empty() => {
                             self.match_history_cache.insert(cache_key, data.clone());
                             self.match_history = data;
                         }
+                        // 刷新失败时保留旧列表
                         Ok(_) => {
-                            self.match_history = vec![];
                             self.match_history_name += &format!("\n暂无数据\n{url}");
                         }
                         Err(e) => {
-                            self.match_history = vec![];
                             self.match_history_name += &format!("\n错误: {e}\n{url}");
                         }
                     }
@@ -307,17 +1466,23 @@ impl App {
                 BgMsg::UpdateDone(result) => {
                     self.updating = false;
                     match result {
-                        Ok(cache) => {
+                        Ok(mut cache) => {
+                            // OP.GG 页面未标明版本时记为更新时的客户端版本
+                            if cache.patch.is_empty() && !self.game_version.is_empty() {
+                                cache.patch = self.game_version.clone();
+                                opgg::save_local_data(&cache);
+                            }
                             self.opgg_cache = cache;
                             self.update_progress_text.clear();
                             // 刷新当前克制数据
                             if !self.counter_champ_slug.is_empty() {
-                                self.counter_data = opgg::get_counters_for_champion(
-                                    &self.opgg_cache,
+                                self.counter_data = self.query_counters(
                                     &self.counter_champ_slug,
                                     &self.my_pos,
                                 );
-                                self.counter_error = if self.counter_data.is_empty() {
+                                self.counter_error = if let Some(reason) = self.counters_unavailable() {
+                                    reason.into()
+                                } else if self.counter_data.is_empty() {
                                     "未找到克制数据".into()
                                 } else {
                                     String::new()
@@ -329,29 +1494,190 @@ impl App {
                         }
                     }
                 }
-                BgMsg::AiChunk(chunk) => {
-                    self.ai_text.push_str(&chunk);
+                BgMsg::AiChunk { generation, text } => {
+                    if generation == self.ai_generation {
+                        self.ai_text.push_str(&text);
+                    }
                 }
-                BgMsg::AiDone { cache_key, full_text } => {
+                BgMsg::AiDone { generation, cache_key, full_text, usage } => {
+                    // 被取代的请求仍然缓存结果（token 已经花了），但不影响当前显示
+                    if !cache_key.is_empty() {
+                        self.ai_cache.insert(cache_key, full_text);
+                    }
+                    if generation != self.ai_generation {
+                        continue;
+                    }
                     self.ai_loading = false;
-                    self.ai_cache.insert(cache_key, full_text);
+                    self.ai_usage = usage;
+                    self.finish_ai_batch_item(ctx);
                 }
-                BgMsg::AiError(err) => {
+                BgMsg::AiError { generation, err } => {
+                    if generation != self.ai_generation {
+                        continue;
+                    }
                     self.ai_loading = false;
                     self.ai_text.push_str(&format!("\n\n错误：{err}"));
+                    self.finish_ai_batch_item(ctx);
+                }
+                BgMsg::AiTest { engine, model, result } => {
+                    self.ai_probing = false;
+                    self.ai_probe_status = match result {
+                        Ok(elapsed) => format!("✔ {engine} / {model} 连接正常（{} ms）", elapsed.as_millis()),
+                        Err(e) => format!("✘ {engine} / {model}：{e}"),
+                    };
+                }
+                BgMsg::AiModels { engine_idx, result } => {
+                    self.ai_probing = false;
+                    match (result, self.ai_engines.get_mut(engine_idx)) {
+                        (Ok(models), Some(engine)) => {
+                            // 保持当前选择的模型；不在列表中说明模型名可能写错
+                            let current = engine.get_models().get(self.ai_model_idx).cloned().unwrap_or_default();
+                            let pos = models.iter().position(|m| *m == current);
+                            self.ai_probe_status = match pos {
+                                Some(_) => format!("已获取 {} 个模型", models.len()),
+                                None => format!("已获取 {} 个模型，当前模型 {current} 不在列表中", models.len()),
+                            };
+                            if engine_idx == self.ai_engine_idx {
+                                self.ai_model_idx = pos.unwrap_or(0);
+                            }
+                            engine.models = models;
+                        }
+                        (Err(e), _) => self.ai_probe_status = format!("获取模型列表失败：{e}"),
+                        (Ok(_), None) => {}
+                    }
                 }
             }
         }
     }
 
-    fn data_time_text(&self) -> String {
-        if self.opgg_cache.updated_at > 0.0 {
-            let ts = self.opgg_cache.updated_at as i64;
-            let dt = chrono::DateTime::from_timestamp(ts, 0)
+    /// 载入英雄名称映射和图标纹理
+    fn apply_champion_data(&mut self, ctx: &egui::Context, data: lcu::ChampionIconData) {
+        self.slug_to_id = data.slug_to_id;
+        self.name_to_id = data.name_to_id;
+        self.champ_names = data.id_to_name;
+        if !data.slug_to_alias.is_empty() {
+            self.slug_to_alias = data.slug_to_alias;
+        }
+        if !data.slug_to_roles.is_empty() {
+            self.slug_to_roles = data.slug_to_roles;
+        }
+        if !data.item_names.is_empty() {
+            self.item_names = data.item_names;
+        }
+        if !data.spell_names.is_empty() {
+            self.spell_names = data.spell_names;
+        }
+        for (id, icon) in data.icons {
+            self.icon_rgba.entry(id).or_insert(icon);
+        }
+        self.upload_icon_textures(ctx);
+    }
+
+    /// 为尚无纹理的图标上传纹理（原始 RGBA 常驻内存，图标显示异常时可用「重载图标」重建）
+    fn upload_icon_textures(&mut self, ctx: &egui::Context) {
+        for (&id, (rgba, w, h)) in &self.icon_rgba {
+            self.icon_textures.entry(id).or_insert_with(|| {
+                let image = ColorImage::from_rgba_unmultiplied([*w as usize, *h as usize], rgba);
+                ctx.load_texture(format!("champ_{id}"), image, egui::TextureOptions::LINEAR)
+            });
+        }
+    }
+
+    /// 丢弃全部图标纹理并从内存中的 RGBA 重建；内存中没有时重新读取本地缓存
+    fn reload_icon_textures(&mut self, ctx: &egui::Context) {
+        self.icon_textures.clear();
+        if !self.icon_rgba.is_empty() {
+            self.upload_icon_textures(ctx);
+            return;
+        }
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn_blocking(move || {
+            if let Some(data) = lcu::load_cached_icons() {
+                let _ = tx.send(BgMsg::CachedIcons(data));
+                ctx.request_repaint();
+            }
+        });
+    }
+
+    /// 本地数据是否缺失或过期
+    fn data_is_stale(&self) -> bool {
+        if self.opgg_cache.updated_at <= 0.0 || self.opgg_cache.counters.is_empty() {
+            return true;
+        }
+        let days = self.config.auto_update_days;
+        if days == 0 {
+            return false;
+        }
+        let now = chrono::Local::now().timestamp() as f64;
+        now - self.opgg_cache.updated_at > days as f64 * 86400.0
+    }
+
+    /// 当前克制数据的采集时间提示，超过 auto_update_days 着色提醒
+    fn ui_counter_age(&self, ui: &mut egui::Ui) {
+        let ts = if self.counter_reverse {
+            Some(self.opgg_cache.updated_at).filter(|t| *t > 0.0)
+        } else {
+            opgg::counter_updated_at(&self.opgg_cache, &self.counter_champ_slug, &self.selected_enemy_pos)
+        };
+        let Some(ts) = ts else { return };
+        let age = (chrono::Local::now().timestamp() as f64 - ts).max(0.0);
+        let text = if age < 3600.0 {
+            "刚刚".to_string()
+        } else if age < 86400.0 {
+            format!("{}小时前", (age / 3600.0) as i64)
+        } else {
+            format!("{}天前", (age / 86400.0) as i64)
+        };
+        let days = self.config.auto_update_days;
+        if days > 0 && age > days as f64 * 86400.0 {
+            ui.colored_label(egui::Color32::from_rgb(220, 150, 40), text)
+                .on_hover_text("该英雄数据较旧，建议更新");
+        } else {
+            ui.weak(text);
+        }
+    }
+
+    /// 选择文件导入 OP.GG 数据，结果显示在导出状态栏
+    fn import_opgg_data(&mut self, merge: bool) {
+        let Some(path) = win32::pick_json_file("选择要导入的 opgg_data.json") else {
+            self.export_status = "未选择文件".into();
+            return;
+        };
+        match opgg::import_local_data(&path, &self.opgg_cache, merge) {
+            Ok(cache) => {
+                let ts = chrono::DateTime::from_timestamp(cache.updated_at as i64, 0)
+                    .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "未知".into());
+                self.export_status = format!(
+                    "已{}：{} 条克制数据，数据时间 {ts}",
+                    if merge { "合并导入" } else { "导入" },
+                    cache.counters.len(),
+                );
+                self.opgg_cache = cache;
+                if !self.counter_champ_slug.is_empty() {
+                    let (slug, name, pos) =
+                        (self.counter_champ_slug.clone(), self.counter_champ_name.clone(), self.selected_enemy_pos.clone());
+                    self.load_counter_data(&slug, &name, &pos);
+                }
+            }
+            Err(e) => self.export_status = format!("导入失败：{e}"),
+        }
+    }
+
+    fn data_time_text(&self) -> String {
+        if self.opgg_cache.updated_at > 0.0 {
+            let ts = self.opgg_cache.updated_at as i64;
+            let dt = chrono::DateTime::from_timestamp(ts, 0)
                 .map(|d| d.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
                 .unwrap_or_default();
+            let patch = if self.opgg_cache.patch.is_empty() {
+                String::new()
+            } else {
+                format!(" | 版本 {}", self.opgg_cache.patch)
+            };
             format!(
-                "数据：{}/{} | 更新：{dt}",
+                "数据：{}/{} | 更新：{dt}{patch}",
                 self.opgg_cache.counters.len(),
                 self.opgg_cache.total_entries
             )
@@ -360,18 +1686,73 @@ impl App {
         }
     }
 
+    /// 敌方列表刷新后校正选中项：该位置换了英雄（交换英雄）时重新加载克制数据
+    fn refresh_selected_enemy(&mut self) {
+        let Some(idx) = self.draft.selected_enemy_idx else { return };
+        match self.draft.enemies.get(idx) {
+            Some(enemy) if enemy.slug != self.counter_champ_slug => {
+                let enemy = enemy.clone();
+                let pos = self.selected_enemy_pos.clone();
+                self.load_counter_data(&enemy.slug, &enemy.name, &pos);
+            }
+            Some(_) => {}
+            None => self.draft.selected_enemy_idx = None,
+        }
+    }
+
+    /// 当前队列对应的 OP.GG 特殊模式（大乱斗 / 无限火力 / 斗魂竞技场），排位等为 None
+    fn queue_mode(&self) -> Option<&'static str> {
+        queue_mode_slug(self.queue_id)
+    }
+
+    /// 当前模式不适用克制数据时的说明（斗魂竞技场没有对线）
+    fn counters_unavailable(&self) -> Option<&'static str> {
+        (self.queue_mode() == Some("arena")).then_some("斗魂竞技场没有对线，克制数据不适用")
+    }
+
+    /// 按当前模式查询克制数据
+    fn query_counters(&self, slug: &str, pos: &str) -> Vec<CounterDisplay> {
+        if self.counters_unavailable().is_some() {
+            return Vec::new();
+        }
+        // 大乱斗没有位置，OP.GG 也无大乱斗对位数据，按全部位置合并
+        let pos = if self.is_aram { ALL_POSITIONS } else { pos };
+        if self.counter_reverse {
+            opgg::get_good_picks_against(&self.opgg_cache, &self.counter_overrides, slug, pos)
+        } else {
+            opgg::get_counters_for_champion(&self.opgg_cache, &self.counter_overrides, slug, pos)
+        }
+    }
+
     fn load_counter_data(&mut self, slug: &str, name: &str, pos: &str) {
         self.counter_champ_slug = slug.to_string();
         self.counter_champ_name = name.to_string();
-        self.counter_data =
-            opgg::get_counters_for_champion(&self.opgg_cache, slug, pos);
-        self.counter_error = if self.counter_data.is_empty() {
+        self.counter_data = self.query_counters(slug, pos);
+        self.counter_selected_row = None;
+        self.override_edit = None;
+        self.counter_error = if let Some(reason) = self.counters_unavailable() {
+            reason.into()
+        } else if self.counter_data.is_empty() {
             "本地无数据，请先点击「全量更新」".into()
         } else {
             String::new()
         };
     }
 
+    /// 当前克制表可手动覆盖时的 key 前缀（反查、大乱斗或合并全部位置时不可覆盖）
+    fn override_base_key(&self) -> Option<String> {
+        if self.counter_reverse || self.is_aram {
+            return None;
+        }
+        opgg::override_base_key(&self.opgg_cache, &self.counter_champ_slug, &self.selected_enemy_pos)
+    }
+
+    /// 克制表是否显示该行（最少场次 + 只看收藏）
+    fn counter_row_visible(&self, row: &CounterDisplay) -> bool {
+        row.games >= self.prefs.min_games
+            && (!self.prefs.favorites_only || self.counter_favorites.contains(&row.key))
+    }
+
     fn sort_counter_data(&mut self) {
         let desc = self.counter_sort_desc;
         let favs = &self.counter_favorites;
@@ -389,6 +1770,18 @@ impl App {
                         .then_with(|| if desc { b.games.cmp(&a.games) } else { a.games.cmp(&b.games) })
                 });
             }
+            "delta" => {
+                // 无整体胜率数据的行排在最后
+                self.counter_data.sort_by(|a, b| {
+                    favs.contains(&b.key).cmp(&favs.contains(&a.key))
+                        .then_with(|| b.delta.is_some().cmp(&a.delta.is_some()))
+                        .then_with(|| {
+                            let (x, y) = (a.delta.unwrap_or(0.0), b.delta.unwrap_or(0.0));
+                            if desc { y.partial_cmp(&x) } else { x.partial_cmp(&y) }
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                        })
+                });
+            }
             "fav" => {
                 self.counter_data.sort_by(|a, b| {
                     let fa = favs.contains(&a.key);
@@ -405,273 +1798,1839 @@ impl App {
                             a.win_rate.partial_cmp(&b.win_rate).unwrap_or(std::cmp::Ordering::Equal)
                         })
                 });
-            }
+            }
+        }
+    }
+
+    fn start_update(&mut self, ctx: &egui::Context) {
+        if self.updating {
+            return;
+        }
+        self.updating = true;
+        self.update_progress_text = "正在获取英雄列表…".into();
+
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        let proxy_url = self.config.proxy_url.clone();
+        self.rt.spawn(async move {
+            let progress_tx = tx.clone();
+            let progress_ctx = ctx.clone();
+            let progress = Arc::new(move |done: usize, total: usize, name: &str| {
+                let _ = progress_tx.send(BgMsg::UpdateProgress(done, total, name.to_string()));
+                progress_ctx.request_repaint();
+            });
+            let result = opgg::fetch_all_counters(&proxy_url, Some(progress)).await;
+            let _ = tx.send(BgMsg::UpdateDone(result));
+            ctx.request_repaint();
+        });
+    }
+
+    /// 只补采上次全量更新失败的条目，结果同样经 UpdateDone 替换缓存
+    fn start_retry_missing(&mut self, ctx: &egui::Context) {
+        if self.updating {
+            return;
+        }
+        self.updating = true;
+        self.update_progress_text = "正在补全失败项…".into();
+
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        let proxy_url = self.config.proxy_url.clone();
+        let cache = self.opgg_cache.clone();
+        self.rt.spawn(async move {
+            let progress_tx = tx.clone();
+            let progress_ctx = ctx.clone();
+            let progress = Arc::new(move |done: usize, total: usize, name: &str| {
+                let _ = progress_tx.send(BgMsg::UpdateProgress(done, total, name.to_string()));
+                progress_ctx.request_repaint();
+            });
+            let result = opgg::fetch_missing_counters(&proxy_url, cache, Some(progress)).await;
+            let _ = tx.send(BgMsg::UpdateDone(result));
+            ctx.request_repaint();
+        });
+    }
+
+    /// 当前选择的提示词模板，None 为内置默认
+    fn current_prompt_template(&self) -> Option<&PromptTemplate> {
+        self.ai_template_idx.checked_sub(1).and_then(|i| self.prompt_templates.get(i))
+    }
+
+    fn start_ai_analysis(
+        &mut self,
+        counter_name: &str,
+        enemy_name: &str,
+        position: &str,
+        win_rate: Option<f64>,
+        ctx: &egui::Context,
+    ) {
+        let engine = match self.ai_engines.get(self.ai_engine_idx) {
+            Some(e) => e.clone(),
+            None => {
+                self.ai_text = "错误：未配置 AI 引擎，请在 config.toml 中设置。".into();
+                return;
+            }
+        };
+        let models = engine.get_models();
+        let model = models.get(self.ai_model_idx).or(models.first())
+            .cloned().unwrap_or_default();
+        self.ai_save_status.clear();
+        if let Some(info) = &mut self.ai_analysis_info {
+            info.model = model.clone();
+        }
+        let template = self.current_prompt_template().cloned();
+        let template_name = template.as_ref().map(|t| t.name.as_str()).unwrap_or_default();
+        let cache_key = format!("{counter_name}|{enemy_name}|{position}|{}|{model}|{template_name}", engine.name);
+        // 新请求（包括命中缓存）取代仍在进行的流式输出
+        self.ai_generation += 1;
+        let generation = self.ai_generation;
+        if let Some(cached) = self.ai_cache.get(&cache_key) {
+            self.ai_loading = false;
+            self.ai_title = "AI 分析（缓存）".into();
+            self.ai_text = cached.clone();
+            self.ai_usage = None;
+            return;
+        }
+
+        self.ai_loading = true;
+        self.ai_title = "AI 分析".into();
+        self.ai_text.clear();
+        self.ai_usage = None;
+        self.ai_cache_key = cache_key.clone();
+
+        let tx = self.tx.clone();
+        let ctx2 = ctx.clone();
+        let req = Arc::new(openai::AiRequest {
+            my_champ: counter_name.to_string(),
+            enemy_champ: enemy_name.to_string(),
+            position: position.to_string(),
+            win_rate,
+            template,
+            system_prompt: self.config.system_prompt.clone(),
+        });
+        let ck = cache_key;
+
+        // 主模型 + 备用模型（去重）
+        let mut candidates = vec![model.clone()];
+        for m in &engine.fallback_models {
+            if !m.is_empty() && !candidates.contains(m) {
+                candidates.push(m.clone());
+            }
+        }
+
+        self.rt.spawn(async move {
+            let engine = Arc::new(engine);
+            for (attempt, model) in candidates.iter().enumerate() {
+                let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel();
+
+                // 启动流式请求
+                let stream_ctx = ctx2.clone();
+                let (engine, model_c, req) = (engine.clone(), model.clone(), req.clone());
+                let stream_handle = tokio::spawn(async move {
+                    openai::call_ai_stream(&engine, &model_c, &req, chunk_tx, stream_ctx).await;
+                });
+
+                // 备用模型应答时标注实际模型
+                let prefix = if attempt > 0 { format!("【实际应答模型】{model}\n") } else { String::new() };
+                if !prefix.is_empty() {
+                    let _ = tx.send(BgMsg::AiChunk { generation, text: prefix.clone() });
+                }
+
+                // 转发流式消息到主 channel
+                let mut retry = false;
+                while let Some(msg) = chunk_rx.recv().await {
+                    match msg {
+                        openai::AiStreamMsg::Chunk(text) => {
+                            let _ = tx.send(BgMsg::AiChunk { generation, text });
+                        }
+                        openai::AiStreamMsg::Done(full_text, usage) => {
+                            let full_text = format!("{prefix}{full_text}");
+                            let _ = tx.send(BgMsg::AiDone { generation, cache_key: ck.clone(), full_text, usage });
+                            ctx2.request_repaint();
+                            break;
+                        }
+                        openai::AiStreamMsg::Retryable(err) if attempt + 1 < candidates.len() => {
+                            let next = &candidates[attempt + 1];
+                            let _ = tx.send(BgMsg::AiChunk { generation, text: format!("{err}\n改用备用模型 {next} 重试…\n\n") });
+                            retry = true;
+                            break;
+                        }
+                        openai::AiStreamMsg::Error(err) | openai::AiStreamMsg::Retryable(err) => {
+                            let _ = tx.send(BgMsg::AiError { generation, err });
+                            ctx2.request_repaint();
+                            break;
+                        }
+                    }
+                    ctx2.request_repaint();
+                }
+
+                let _ = stream_handle.await;
+                if !retry {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// 测试当前引擎和模型能否正常应答
+    fn start_ai_test(&mut self, ctx: &egui::Context) {
+        let Some(engine) = self.ai_engines.get(self.ai_engine_idx).cloned() else {
+            return;
+        };
+        let models = engine.get_models();
+        let model = models.get(self.ai_model_idx).or(models.first()).cloned().unwrap_or_default();
+        self.ai_probing = true;
+        self.ai_probe_status = format!("正在测试 {} / {model}…", engine.name);
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let result = openai::test_connection(&engine, &model).await;
+            let _ = tx.send(BgMsg::AiTest { engine: engine.name, model, result });
+            ctx.request_repaint();
+        });
+    }
+
+    /// 从接口获取当前引擎的可用模型，填充模型下拉框
+    fn start_fetch_models(&mut self, ctx: &egui::Context) {
+        let Some(engine) = self.ai_engines.get(self.ai_engine_idx).cloned() else {
+            return;
+        };
+        let engine_idx = self.ai_engine_idx;
+        self.ai_probing = true;
+        self.ai_probe_status = format!("正在获取 {} 的模型列表…", engine.name);
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let result = openai::list_models(&engine).await;
+            let _ = tx.send(BgMsg::AiModels { engine_idx, result });
+            ctx.request_repaint();
+        });
+    }
+
+    /// 批量分析：我的英雄对每个敌方英雄各分析一次（按我的位置），依次串行请求
+    fn start_ai_batch(&mut self, ctx: &egui::Context) {
+        let my_slug = self.ai_pick_slug().to_string();
+        if self.ai_loading || my_slug.is_empty() || self.draft.enemies.is_empty() {
+            return;
+        }
+        if self.ai_engines.is_empty() {
+            self.ai_text = "错误：未配置 AI 引擎，请在 config.toml 中设置。".into();
+            return;
+        }
+        let my_pos = self.effective_my_pos();
+        let position = lcu_pos_to_opgg(&my_pos).to_string();
+        let my_name = self.champ_display_name(&my_slug);
+        // 未选英雄的敌方（slug 为空）没有可分析的对位，跳过
+        self.ai_batch = self
+            .draft.enemies
+            .iter()
+            .filter(|enemy| !enemy.slug.is_empty())
+            .map(|enemy| AiBatchItem {
+                info: AiAnalysisInfo {
+                    my_slug: my_slug.clone(),
+                    my_name: my_name.clone(),
+                    enemy_slug: enemy.slug.clone(),
+                    enemy_name: enemy.name.clone(),
+                    position: position.clone(),
+                    win_rate: opgg::matchup_win_rate(&self.opgg_cache, &self.counter_overrides, &my_slug, &enemy.slug, &my_pos)
+                        .map(|(wr, _)| wr),
+                    model: String::new(),
+                },
+                text: String::new(),
+                done: false,
+                cached: false,
+            })
+            .collect();
+        if self.ai_batch.is_empty() {
+            self.ai_text = "敌方还没有选出英雄".into();
+            return;
+        }
+        self.advance_ai_batch(ctx);
+    }
+
+    /// 启动下一项批量分析；命中缓存的项直接完成，不重复请求
+    fn advance_ai_batch(&mut self, ctx: &egui::Context) {
+        while let Some(idx) = self.ai_batch.iter().position(|item| !item.done) {
+            let info = self.ai_batch[idx].info.clone();
+            self.ai_analysis_info = Some(info.clone());
+            self.ai_batch_running = Some(idx);
+            self.start_ai_analysis(&info.my_name, &info.enemy_name, &info.position, info.win_rate, ctx);
+            if self.ai_loading {
+                return;
+            }
+            let item = &mut self.ai_batch[idx];
+            item.text = self.ai_text.clone();
+            item.done = true;
+            item.cached = true;
+        }
+        self.ai_batch_running = None;
+    }
+
+    /// 当前批量项流式输出结束，记录结果并继续下一项
+    fn finish_ai_batch_item(&mut self, ctx: &egui::Context) {
+        let Some(idx) = self.ai_batch_running.take() else {
+            return;
+        };
+        if let Some(item) = self.ai_batch.get_mut(idx) {
+            item.text = self.ai_text.clone();
+            item.done = true;
+        }
+        self.advance_ai_batch(ctx);
+    }
+
+    /// 结束批量模式（单独分析 / 对话时调用）
+    fn clear_ai_batch(&mut self) {
+        self.ai_batch.clear();
+        self.ai_batch_running = None;
+    }
+
+    fn start_ai_chat(&mut self, user_prompt: &str, ctx: &egui::Context) {
+        let engine = match self.ai_engines.get(self.ai_engine_idx) {
+            Some(e) => e.clone(),
+            None => {
+                self.ai_text = "错误：未配置 AI 引擎，请在 config.toml 中设置。".into();
+                return;
+            }
+        };
+        let models = engine.get_models();
+        let model = models.get(self.ai_model_idx).or(models.first())
+            .cloned().unwrap_or_default();
+
+        self.ai_generation += 1;
+        let generation = self.ai_generation;
+        self.ai_loading = true;
+        self.ai_title = "AI 对话".into();
+        self.ai_analysis_info = None;
+        self.ai_save_status.clear();
+        self.ai_text.clear();
+        self.ai_usage = None;
+
+        let tx = self.tx.clone();
+        let ctx2 = ctx.clone();
+        let prompt = user_prompt.to_string();
+
+        self.rt.spawn(async move {
+            let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel();
+
+            let stream_ctx = ctx2.clone();
+            let stream_handle = tokio::spawn(async move {
+                openai::call_ai_raw(&engine, &model, "你是一个有用的助手。用简洁中文回答。", &prompt, chunk_tx, stream_ctx).await;
+            });
+
+            while let Some(msg) = chunk_rx.recv().await {
+                match msg {
+                    openai::AiStreamMsg::Chunk(text) => {
+                        let _ = tx.send(BgMsg::AiChunk { generation, text });
+                    }
+                    openai::AiStreamMsg::Done(_, usage) => {
+                        let _ = tx.send(BgMsg::AiDone { generation, cache_key: String::new(), full_text: String::new(), usage });
+                        ctx2.request_repaint();
+                        break;
+                    }
+                    openai::AiStreamMsg::Error(err) | openai::AiStreamMsg::Retryable(err) => {
+                        let _ = tx.send(BgMsg::AiError { generation, err });
+                        ctx2.request_repaint();
+                        break;
+                    }
+                }
+                ctx2.request_repaint();
+            }
+
+            let _ = stream_handle.await;
+        });
+    }
+
+    /// 获取玩家对局记录；`force` 为 true 时跳过缓存重新获取，并保留旧列表直到新数据到达
+    fn start_fetch_match_history(&mut self, query: HistoryQuery, force: bool, ctx: &egui::Context) {
+        let cache_key = query.cache_key();
+        if cache_key != self.match_history_query.cache_key() {
+            self.history_champ_filter = None;
+        }
+        self.match_history_query = query.clone();
+        self.match_history_rank = query.rank.clone()
+            .or_else(|| self.opgg_rank_cache.get(&cache_key).cloned());
+        // 检查缓存
+        if !force {
+            if let Some(cached) = self.match_history_cache.get(&cache_key) {
+                self.match_history = cached.clone();
+                self.match_history_name = query.display_name;
+                self.match_history_loading = false;
+                return;
+            }
+        }
+        if query.game_name.is_empty() || query.tag_line.is_empty() { return; }
+        self.match_history_loading = true;
+        if !force {
+            self.match_history_name = format!("{} (加载中…)", query.display_name);
+            self.match_history = vec![];
+        }
+
+        self.spawn_history_fetch(query, ctx);
+    }
+
+    /// 解析输入的 名字#标签 列表并并发查询（最多 SCOUT_CONCURRENCY 个请求同时进行）
+    fn start_scout(&mut self, ctx: &egui::Context) {
+        let ids: Vec<String> = self
+            .scout_input
+            .split(['\n', ',', '，'])
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .take(SCOUT_MAX_PLAYERS)
+            .collect();
+        self.scout_generation += 1;
+        let generation = self.scout_generation;
+        let region = self.search_region.clone();
+
+        let mut jobs = Vec::new();
+        self.scout_entries = ids
+            .into_iter()
+            .enumerate()
+            .map(|(idx, riot_id)| {
+                let name_tag = parse_riot_id(&riot_id);
+                let result = match &name_tag {
+                    Some((name, tag)) => {
+                        let key = history_cache_key(&region, name, tag);
+                        match self.match_history_cache.get(&key) {
+                            Some(cached) => {
+                                let rank = self.opgg_rank_cache.get(&key).cloned();
+                                Some(Ok((cached.clone(), rank)))
+                            }
+                            None => {
+                                jobs.push((idx, name.clone(), tag.clone()));
+                                None
+                            }
+                        }
+                    }
+                    None => Some(Err("格式应为 名字#标签".to_string())),
+                };
+                ScoutEntry { riot_id, name_tag, region: region.clone(), result }
+            })
+            .collect();
+        if jobs.is_empty() {
+            return;
+        }
+
+        let proxy_url = self.config.proxy_url.clone();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let client = match crate::config::http_client(&proxy_url, std::time::Duration::from_secs(15)) {
+                Ok(c) => c,
+                Err(e) => {
+                    for (idx, ..) in jobs {
+                        let _ = tx.send(BgMsg::Scout { generation, idx, result: Err(e.clone()) });
+                    }
+                    ctx.request_repaint();
+                    return;
+                }
+            };
+            let sem = Arc::new(tokio::sync::Semaphore::new(SCOUT_CONCURRENCY));
+            for (idx, name, tag) in jobs {
+                let (client, sem, tx, ctx, region) = (client.clone(), sem.clone(), tx.clone(), ctx.clone(), region.clone());
+                tokio::spawn(async move {
+                    let _permit = sem.acquire().await;
+                    let result = opgg::fetch_match_history(&client, &region, &name, &tag).await;
+                    let _ = tx.send(BgMsg::Scout { generation, idx, result });
+                    ctx.request_repaint();
+                });
+            }
+        });
+    }
+
+    fn ui_scout(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.label(format!("每行一个 名字#标签（最多 {SCOUT_MAX_PLAYERS} 个），地区：{}", self.search_region));
+        ui.add(
+            egui::TextEdit::multiline(&mut self.scout_input)
+                .hint_text("Faker#KR1\nShowMaker#KR1")
+                .desired_rows(4)
+                .desired_width(f32::INFINITY),
+        );
+        let loading = self.scout_entries.iter().any(|e| e.result.is_none());
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!loading, egui::Button::new("开始查询")).clicked() {
+                self.start_scout(ctx);
+            }
+            if loading {
+                ui.spinner();
+            }
+        });
+        if self.scout_entries.is_empty() {
+            return;
+        }
+        ui.separator();
+        egui::ScrollArea::vertical().id_salt("scout_scroll").show(ui, |ui| {
+            egui::Grid::new("scout_grid").striped(true).show(ui, |ui| {
+                ui.strong("玩家");
+                ui.strong("段位");
+                ui.strong("近期");
+                ui.strong("常用英雄");
+                ui.end_row();
+                for entry in &self.scout_entries {
+                    ui.label(&entry.riot_id);
+                    match &entry.result {
+                        None => {
+                            ui.spinner();
+                            ui.label("");
+                            ui.label("");
+                        }
+                        Some(Err(e)) => {
+                            ui.colored_label(egui::Color32::from_rgb(220, 60, 60), "失败").on_hover_text(e);
+                            ui.label("");
+                            ui.label("");
+                        }
+                        Some(Ok((data, rank))) => {
+                            ui.label(rank.as_ref().map(|r| r.text()).unwrap_or_else(|| "未定级".into()));
+                            let (wins, total, top) = history_summary(data);
+                            if total == 0 {
+                                ui.weak("无记录");
+                            } else {
+                                let wr = wins as f64 / total as f64 * 100.0;
+                                let text = match weighted_win_rate(data, self.config.form_half_life_hours) {
+                                    Some(w) => format!("{wins}/{total} {wr:.0}% · 加权{w:.0}%"),
+                                    None => format!("{wins}/{total} {wr:.0}%"),
+                                };
+                                ui.colored_label(win_rate_color(wr, self.prefs.colorblind_palette), text);
+                            }
+                            let top: Vec<String> = top.iter().map(|(n, c)| format!("{n}×{c}")).collect();
+                            ui.label(top.join(" "));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    }
+
+    /// 后台请求对局记录，结果经 BgMsg::MatchHistory 返回（非当前玩家时只写入缓存）
+    fn spawn_history_fetch(&self, query: HistoryQuery, ctx: &egui::Context) {
+        let cache_key = query.cache_key();
+        let HistoryQuery { game_name, tag_line, display_name: name, region, .. } = query;
+        let proxy_url = self.config.proxy_url.clone();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let url = opgg::match_history_url(&region, &game_name, &tag_line);
+            let result = match crate::config::http_client(&proxy_url, std::time::Duration::from_secs(15)) {
+                Ok(client) => opgg::fetch_match_history(&client, &region, &game_name, &tag_line)
+                    .await
+                    .map_err(|e| if proxy_url.is_empty() { e } else { format!("{e}（当前代理：{proxy_url}）") }),
+                Err(e) => Err(e),
+            };
+            let _ = tx.send(BgMsg::MatchHistory { cache_key, name, url, entries: result });
+            ctx.request_repaint();
+        });
+    }
+}
+
+impl App {
+    /// 当前笔记对应的对局：最近一次 AI 分析的对局，否则为我的英雄 vs 克制表英雄
+    fn note_matchup(&self) -> Option<(String, String, String)> {
+        if let Some(m) = &self.note_matchup {
+            if m.1 == self.counter_champ_slug {
+                return Some(m.clone());
+            }
+        }
+        if self.draft.my_champ_slug.is_empty() || self.counter_champ_slug.is_empty() {
+            return None;
+        }
+        Some((self.draft.my_champ_slug.clone(), self.counter_champ_slug.clone(), self.selected_enemy_pos.clone()))
+    }
+
+    fn ui_matchup_note(&mut self, ui: &mut egui::Ui) {
+        let Some((my, enemy, pos)) = self.note_matchup() else { return };
+        let key = format!("{my}|{enemy}|{pos}");
+        if key != self.note_key {
+            self.note_edit = self.notes.get(&key).cloned().unwrap_or_default();
+            self.note_key = key.clone();
+        }
+        let name = |slug: &str| self.opgg_cache.champions.get(slug).cloned().unwrap_or_else(|| slug.to_string());
+        let title = format!("📝 我的笔记：{} vs {} {}", name(&my), name(&enemy), pos_cn(&pos));
+        egui::Frame::group(ui.style())
+            .fill(egui::Color32::from_rgb(48, 44, 30))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(egui::RichText::new(title).color(egui::Color32::from_rgb(230, 200, 120)));
+                    let saved = self.notes.get(&key).cloned().unwrap_or_default();
+                    let dirty = self.note_edit.trim() != saved;
+                    if ui.add_enabled(dirty, egui::Button::new("保存").small()).clicked() {
+                        if self.note_edit.trim().is_empty() {
+                            self.notes.remove(&key);
+                        } else {
+                            self.notes.insert(key.clone(), self.note_edit.trim().to_string());
+                        }
+                        save_notes(&self.notes);
+                    }
+                    if ui.add_enabled(!saved.is_empty(), egui::Button::new("删除").small()).clicked() {
+                        self.notes.remove(&key);
+                        self.note_edit.clear();
+                        save_notes(&self.notes);
+                    }
+                });
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.note_edit)
+                        .hint_text("记录这个对局的心得…")
+                        .desired_rows(2)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+    }
+
+    /// 未有 AI 输出时按双方定位显示一句内置对线提示（我的英雄 vs 克制表中的敌方）
+    fn ui_builtin_tip(&self, ui: &mut egui::Ui) {
+        let (my, enemy) = (self.ai_pick_slug(), self.counter_champ_slug.as_str());
+        if my.is_empty() || enemy.is_empty() || my == enemy {
+            return;
+        }
+        let roles = |slug: &str| self.slug_to_roles.get(slug).map(Vec::as_slice).unwrap_or_default();
+        let Some(tip) = tips::matchup_tip(roles(my), roles(enemy)) else { return };
+        ui.label(format!("提示（{} vs {}）：{tip}", self.champ_display_name(my), self.counter_champ_name))
+            .on_hover_text("按英雄定位推断的通用提示，仅供参考；需要客户端提供英雄定位数据");
+    }
+
+    /// 选中敌方列表中的一个英雄并加载其克制数据（位置：指定/分配的位置 → 我的位置 → 英雄常用位置）
+    fn select_enemy(&mut self, idx: usize) {
+        let Some(enemy) = self.draft.enemies.get(idx).cloned() else { return };
+        self.draft.selected_enemy_idx = Some(idx);
+        let enemy_pos = self.effective_pos(enemy.champion_id, &enemy.pos);
+        let my_pos = self.effective_my_pos();
+        self.selected_enemy_pos = if !enemy_pos.is_empty() {
+            enemy_pos
+        } else if !my_pos.is_empty() {
+            my_pos
+        } else {
+            // 位置未分配（如匹配模式）时按英雄常用位置推测
+            default_role(&enemy.slug).to_string()
+        };
+        self.load_counter_data(&enemy.slug, &enemy.name, &self.selected_enemy_pos.clone());
+    }
+
+    /// 用「我的英雄」对克制表当前的敌方英雄做 AI 分析
+    fn analyze_pick_vs_counter_champ(&mut self, ctx: &egui::Context) {
+        let pick = self.ai_pick_slug().to_string();
+        let name = self.champ_display_name(&pick);
+        self.analyze_vs_counter_champ(name, pick, ctx);
+    }
+
+    /// 以 my_slug 为我方、克制表当前的敌方英雄为对手做 AI 分析，并把笔记关联到该对位
+    ///
+    /// 两种入口（我的英雄 / Shift+点击的行）都用 matchup_win_rate 取我方对敌方的胜率，方向一致。
+    fn analyze_vs_counter_champ(&mut self, my_name: String, my_slug: String, ctx: &egui::Context) {
+        let win_rate = opgg::matchup_win_rate(&self.opgg_cache, &self.counter_overrides, &my_slug, &self.counter_champ_slug, &self.my_pos)
+            .map(|(wr, _)| wr);
+        let enemy_name = self.counter_champ_name.clone();
+        let opgg_pos = lcu_pos_to_opgg(&self.my_pos).to_string();
+        self.ai_analysis_info = Some(AiAnalysisInfo {
+            my_slug: my_slug.clone(),
+            my_name: my_name.clone(),
+            enemy_slug: self.counter_champ_slug.clone(),
+            enemy_name: enemy_name.clone(),
+            position: opgg_pos.clone(),
+            win_rate,
+            model: String::new(),
+        });
+        self.note_matchup = Some((
+            my_slug,
+            self.counter_champ_slug.clone(),
+            self.selected_enemy_pos.clone(),
+        ));
+        self.start_ai_analysis(&my_name, &enemy_name, &opgg_pos, win_rate, ctx);
+    }
+
+    /// auto_select_lane_enemy：对线敌人确定且 LANE_ENEMY_SETTLE 内不再变化后自动选中（随 LCU 轮询检查，每局每个英雄一次），
+    /// 开启 auto_analyze_lane_enemy 时再用我的英雄对其做 AI 分析
+    fn auto_select_lane_enemy(&mut self, ctx: &egui::Context) {
+        if !self.config.auto_select_lane_enemy {
+            return;
+        }
+        let Some(lane_id) = self.effective_lane_enemy().filter(|&id| id > 0) else {
+            self.lane_enemy_pending = None;
+            return;
+        };
+        if self.lane_enemy_auto_done == Some((self.draft.last_session_id, lane_id)) {
+            return;
+        }
+        let now = std::time::Instant::now();
+        match self.lane_enemy_pending {
+            Some((id, since)) if id == lane_id => {
+                if now.duration_since(since) < LANE_ENEMY_SETTLE {
+                    return;
+                }
+            }
+            _ => {
+                self.lane_enemy_pending = Some((lane_id, now));
+                return;
+            }
+        }
+        self.lane_enemy_pending = None;
+        self.lane_enemy_auto_done = Some((self.draft.last_session_id, lane_id));
+        let Some(idx) = self.draft.enemies.iter().position(|e| e.champion_id == lane_id) else { return };
+        self.select_enemy(idx);
+        let pick = self.ai_pick_slug();
+        if self.config.auto_analyze_lane_enemy
+            && !self.ai_loading
+            && !pick.is_empty()
+            && pick != self.counter_champ_slug
+        {
+            self.clear_ai_batch();
+            self.analyze_pick_vs_counter_champ(ctx);
+        }
+    }
+
+    /// 敌方英雄悬停提示：OP.GG 有数据的位置及各位置的最强克制（方便预判灵活位英雄去哪路）
+    fn ui_position_breakdown(&self, ui: &mut egui::Ui, slug: &str, show_pos_hint: bool) {
+        let rows = opgg::position_breakdown(&self.opgg_cache, slug, self.prefs.min_games);
+        if rows.is_empty() {
+            ui.weak("本地没有该英雄的克制数据");
+        } else {
+            egui::Grid::new("position_breakdown").striped(true).show(ui, |ui| {
+                ui.strong("位置");
+                ui.strong("胜率");
+                ui.strong("最强克制");
+                ui.end_row();
+                for row in &rows {
+                    ui.label(pos_cn(row.pos));
+                    match row.baseline {
+                        Some(wr) => ui.label(format!("{wr:.1}%")),
+                        None => ui.weak("-"),
+                    };
+                    match &row.top_counter {
+                        Some((name, wr, games)) => ui.label(format!("{name} {wr:.1}%（{games}场）")),
+                        None => ui.weak(format!("{} 条，样本不足", row.entries)),
+                    };
+                    ui.end_row();
+                }
+            });
+        }
+        if show_pos_hint {
+            ui.weak("右键指定位置");
+        }
+    }
+
+    /// 敌方列表为空时的引导：未连接提示启动客户端，找不到 lockfile 时可打开配置；返回是否点击了「打开配置」
+    fn ui_players_empty(&self, ui: &mut egui::Ui) -> bool {
+        if self.connected {
+            ui.weak("进入选人阶段后自动显示");
+            return false;
+        }
+        ui.colored_label(egui::Color32::from_rgb(220, 150, 40), "请启动英雄联盟客户端");
+        if !matches!(self.lcu_error_kind, Some(LcuErrorKind::LockfileNotFound | LcuErrorKind::LockfileInvalid)) {
+            ui.weak("正在连接…");
+            return false;
+        }
+        ui.weak("已启动仍找不到时，在 config.toml 设置 lockfile_dir");
+        ui.small_button("打开配置文件").clicked()
+    }
+
+    /// 连接状态指示：绿 = 已连接，黄 = 正在查找 / 连接客户端，红 = 找不到或读不了 lockfile
+    fn connection_status(&self) -> (egui::Color32, &'static str) {
+        if self.connected {
+            (egui::Color32::from_rgb(80, 200, 120), "已连接客户端")
+        } else if self.lcu_error_kind == Some(LcuErrorKind::LockfileNotFound) {
+            (egui::Color32::from_rgb(220, 70, 70), "找不到客户端 lockfile：客户端未启动，或需在 config.toml 设置 lockfile_dir")
+        } else if self.lcu_error_kind == Some(LcuErrorKind::LockfileInvalid) {
+            (egui::Color32::from_rgb(220, 70, 70), "客户端 lockfile 无法读取：请检查 config.toml 的 lockfile_dir 是否指向客户端目录")
+        } else {
+            (egui::Color32::from_rgb(230, 190, 60), "正在连接客户端…")
+        }
+    }
+
+    /// 按隐私模式设置窗口是否可被录屏捕获
+    fn apply_privacy(&mut self) {
+        if let Err(e) = win32::set_capture_excluded(self.prefs.privacy) {
+            self.error = e;
+        }
+    }
+
+    /// 英雄的实际位置：手动指定优先，否则用 LCU 分配的位置
+    fn effective_pos(&self, champion_id: i64, pos: &str) -> String {
+        self.draft.pos_overrides.get(&champion_id).cloned().unwrap_or_else(|| pos.to_string())
+    }
+
+    /// 我的实际位置（可按我的英雄手动指定）
+    fn effective_my_pos(&self) -> String {
+        match self.slug_to_id.get(&self.draft.my_champ_slug) {
+            Some(id) => self.effective_pos(*id, &self.my_pos),
+            None => self.my_pos.clone(),
+        }
+    }
+
+    /// 对线敌人：有手动指定位置时按实际位置匹配，否则用 LCU 结果
+    fn effective_lane_enemy(&self) -> Option<i64> {
+        if self.draft.pos_overrides.is_empty() {
+            return self.draft.lane_enemy_id;
+        }
+        let my_pos = self.effective_my_pos();
+        if my_pos.is_empty() {
+            return self.draft.lane_enemy_id;
+        }
+        self.draft.enemies
+            .iter()
+            .find(|e| e.champion_id > 0 && self.effective_pos(e.champion_id, &e.pos) == my_pos)
+            .map(|e| e.champion_id)
+    }
+
+    /// 预取队友战绩（dodge_warning 开启时），每次只发一个请求并间隔 2 秒
+    fn prefetch_ally_histories(&mut self, ctx: &egui::Context) {
+        if !self.config.dodge_warning {
+            return;
+        }
+        if self.last_prefetch.is_some_and(|t| t.elapsed() < std::time::Duration::from_secs(2)) {
+            return;
+        }
+        let next = self.draft.teammates.iter().find(|m| {
+            m.is_ally
+                && !m.tag_line.is_empty()
+                && !self.history_prefetched.contains(&history_cache_key(&self.config.region, &m.summoner_name, &m.tag_line))
+        });
+        let Some(mate) = next.cloned() else { return };
+        let query = HistoryQuery {
+            game_name: mate.summoner_name.clone(),
+            tag_line: mate.tag_line,
+            display_name: mate.summoner_name,
+            region: self.config.region.clone(),
+            rank: None,
+        };
+        self.history_prefetched.insert(query.cache_key());
+        self.last_prefetch = Some(std::time::Instant::now());
+        if !self.match_history_cache.contains_key(&query.cache_key()) {
+            self.spawn_history_fetch(query, ctx);
+        }
+    }
+
+    /// 批量预取选人中玩家的战绩（prefetch_histories 开启时），最多 SCOUT_CONCURRENCY 个请求同时进行
+    fn start_bulk_prefetch(&mut self, ctx: &egui::Context) {
+        if !self.config.prefetch_histories {
+            return;
+        }
+        self.prefetch_tasks.retain(|t| !t.is_finished());
+        let queries: Vec<HistoryQuery> = self
+            .draft.teammates
+            .iter()
+            .filter(|m| m.is_ally || self.config.prefetch_enemies)
+            .filter(|m| !m.summoner_name.is_empty() && !m.tag_line.is_empty())
+            .map(|m| HistoryQuery {
+                game_name: m.summoner_name.clone(),
+                tag_line: m.tag_line.clone(),
+                display_name: m.summoner_name.clone(),
+                region: self.config.region.clone(),
+                rank: None,
+            })
+            .filter(|q| {
+                let key = q.cache_key();
+                !self.history_prefetched.contains(&key) && !self.match_history_cache.contains_key(&key)
+            })
+            .collect();
+        if queries.is_empty() {
+            return;
+        }
+        for q in &queries {
+            self.history_prefetched.insert(q.cache_key());
+        }
+
+        let proxy_url = self.config.proxy_url.clone();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        let task = self.rt.spawn(async move {
+            let client = match crate::config::http_client(&proxy_url, std::time::Duration::from_secs(15)) {
+                Ok(c) => c,
+                Err(_) => return,
+            };
+            let sem = Arc::new(tokio::sync::Semaphore::new(SCOUT_CONCURRENCY));
+            // JoinSet 随任务中止一起丢弃，未完成的请求会被取消
+            let mut set = tokio::task::JoinSet::new();
+            for query in queries {
+                let (client, sem, tx, ctx) = (client.clone(), sem.clone(), tx.clone(), ctx.clone());
+                set.spawn(async move {
+                    let _permit = sem.acquire().await;
+                    let cache_key = query.cache_key();
+                    let HistoryQuery { game_name, tag_line, display_name: name, region, .. } = query;
+                    let url = opgg::match_history_url(&region, &game_name, &tag_line);
+                    let entries = opgg::fetch_match_history(&client, &region, &game_name, &tag_line).await;
+                    let _ = tx.send(BgMsg::MatchHistory { cache_key, name, url, entries });
+                    ctx.request_repaint();
+                });
+            }
+            while set.join_next().await.is_some() {}
+        });
+        self.prefetch_tasks.push(task);
+    }
+
+    /// 中止批量预取；未取到的玩家下次进入选人时重新预取
+    fn cancel_bulk_prefetch(&mut self) {
+        if self.prefetch_tasks.is_empty() {
+            return;
+        }
+        for task in self.prefetch_tasks.drain(..) {
+            task.abort();
+        }
+        let cache = &self.match_history_cache;
+        self.history_prefetched.retain(|k| cache.contains_key(k));
+    }
+
+    /// 队友近期战绩过差时返回 (胜率, 加权胜率, 场次)；开启近期加权时按加权胜率判断
+    fn dodge_warning_for(&self, mate: &TeamMateInfo) -> Option<(f64, Option<f64>, usize)> {
+        if !self.config.dodge_warning || !mate.is_ally {
+            return None;
+        }
+        let history = self
+            .match_history_cache
+            .get(&history_cache_key(&self.config.region, &mate.summoner_name, &mate.tag_line))?;
+        let recent = &history[..history.len().min(self.config.dodge_games.max(1))];
+        if recent.len() < self.config.dodge_games.max(1) {
+            return None;
+        }
+        let wins = recent.iter().filter(|e| e.win).count();
+        let wr = wins as f64 / recent.len() as f64 * 100.0;
+        let weighted = weighted_win_rate(recent, self.config.form_half_life_hours);
+        (weighted.unwrap_or(wr) < self.config.dodge_win_rate).then_some((wr, weighted, recent.len()))
+    }
+
+    /// 双方下路组合 (是否友方, adc slug, 辅助 slug)
+    fn bot_lane_pairs(&self) -> Vec<(bool, String, String)> {
+        let slug_of = |id: i64| {
+            self.slug_to_id.iter().find(|(_, v)| **v == id).map(|(k, _)| k.clone())
+        };
+        let mut pairs = Vec::new();
+        for ally in [true, false] {
+            let find = |pos: &str| {
+                self.draft.teammates
+                    .iter()
+                    .find(|m| m.is_ally == ally && m.champion_id > 0 && self.effective_pos(m.champion_id, &m.position) == pos)
+                    .and_then(|m| slug_of(m.champion_id))
+            };
+            let (mut adc, mut sup) = (find("BOTTOM"), find("UTILITY"));
+            // 选人阶段对方信息只在 enemies 里
+            if !ally {
+                let from_enemies = |pos: &str| {
+                    self.draft.enemies.iter()
+                        .find(|e| !e.slug.is_empty() && self.effective_pos(e.champion_id, &e.pos) == pos)
+                        .map(|e| e.slug.clone())
+                };
+                adc = adc.or_else(|| from_enemies("BOTTOM"));
+                sup = sup.or_else(|| from_enemies("UTILITY"));
+            }
+            if let (Some(adc), Some(sup)) = (adc, sup) {
+                pairs.push((ally, adc, sup));
+            }
+        }
+        pairs
+    }
+
+    /// 已缓存且在上次全量更新之后获取的下路组合胜率
+    fn cached_duo(&self, key: &str) -> Option<&DuoSynergy> {
+        self.opgg_cache.duo_synergies.get(key).filter(|d| d.fetched_at >= self.opgg_cache.updated_at)
+    }
+
+    /// 获取下路组合胜率（成功结果随 opgg_data.json 保存，失败的过一段时间后重试）
+    fn start_fetch_duo(&mut self, adc: &str, sup: &str, ctx: &egui::Context) {
+        let key = format!("{adc}+{sup}");
+        let recently_failed = self.duo_errors.get(&key).is_some_and(|(_, t)| t.elapsed() < DUO_RETRY_AFTER);
+        if self.cached_duo(&key).is_some() || recently_failed || !self.duo_loading.insert(key.clone()) {
+            return;
+        }
+        let (adc, sup) = (adc.to_string(), sup.to_string());
+        let proxy_url = self.config.proxy_url.clone();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let result = match crate::config::http_client(&proxy_url, std::time::Duration::from_secs(10)) {
+                Ok(client) => opgg::fetch_duo_synergy(&client, &adc, &sup).await,
+                Err(e) => Err(e),
+            };
+            let _ = tx.send(BgMsg::Duo { key, result });
+            ctx.request_repaint();
+        });
+    }
+
+    /// 从 LCU 获取玩家英雄熟练度（按 puuid 缓存，空 puuid 为当前玩家）
+    fn start_fetch_mastery(&mut self, puuid: &str, ctx: &egui::Context) {
+        let Some(auth) = self.lcu_auth.clone() else { return };
+        let recently_failed = self.mastery_errors.get(puuid).is_some_and(|(_, t)| t.elapsed() < MASTERY_RETRY_AFTER);
+        if self.mastery_cache.contains_key(puuid) || recently_failed || !self.mastery_loading.insert(puuid.to_string()) {
+            return;
+        }
+        let key = puuid.to_string();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let result = lcu::fetch_champion_mastery(&auth, &key).await;
+            let _ = tx.send(BgMsg::Mastery { key, result });
+            ctx.request_repaint();
+        });
+    }
+
+    /// 常用英雄一行：熟练度最高的 5 个英雄图标 + 点数（未缓存时触发查询，失败的隔一段时间重试）
+    fn ui_mastery_row(&mut self, ui: &mut egui::Ui, label: &str, puuid: &str, ctx: &egui::Context) {
+        if !self.mastery_cache.contains_key(puuid) {
+            self.start_fetch_mastery(puuid, ctx);
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label(label);
+            match (self.mastery_cache.get(puuid), self.mastery_errors.get(puuid)) {
+                (None, Some((e, _))) => { ui.weak("熟练度不可用").on_hover_text(e); }
+                (None, None) => { ui.spinner(); }
+                (Some(list), _) if list.is_empty() => { ui.weak("暂无熟练度数据"); }
+                (Some(list), _) => {
+                    for m in list.iter().take(5) {
+                        let name = self.champ_names.get(&m.champion_id).cloned().unwrap_or_else(|| m.champion_id.to_string());
+                        let points = if m.points >= 10_000 {
+                            format!("{:.1}万", m.points as f64 / 10_000.0)
+                        } else {
+                            m.points.to_string()
+                        };
+                        let hover = format!("{name}：熟练度 {} 级 · {} 点", m.level, m.points);
+                        match self.icon_textures.get(&m.champion_id) {
+                            Some(tex) => { ui.image((tex.id(), egui::vec2(20.0, 20.0))).on_hover_text(&hover); }
+                            None => { ui.label(&name).on_hover_text(&hover); }
+                        }
+                        ui.weak(points);
+                    }
+                }
+            }
+        });
+    }
+
+    /// 查找 LoL 客户端窗口并吸附到其右侧
+    fn update_window_dock(&mut self, ctx: &egui::Context) {
+        let Some(lol_win) = win32::find_lol_client_window() else {
+            self.debug_lol_win = "未找到 LOL 窗口".into();
+            // 客户端关闭后不再朝旧位置移动
+            self.dock_target = None;
+            return;
+        };
+        let (scr_x, scr_y, scr_w, scr_h) = win32::virtual_screen_rect();
+        self.debug_lol_win = format!(
+            "找到窗口: L={} T={} R={} B={} min={} | 屏幕: x={} y={} w={} h={}",
+            lol_win.left, lol_win.top, lol_win.right, lol_win.bottom,
+            lol_win.minimized, scr_x, scr_y, scr_w, scr_h,
+        );
+
+        if self.autodock {
+            let scale = ctx.pixels_per_point();
+            let side = match self.prefs.dock_side {
+                // 比较客户端窗口两侧到虚拟屏幕边缘的距离
+                DockSide::Auto if lol_win.left - scr_x > scr_x + scr_w - lol_win.right => DockSide::Left,
+                DockSide::Auto => DockSide::Right,
+                side => side,
+            };
+            let gap = self.prefs.dock_gap;
+            // 物理像素 → 逻辑坐标
+            let x = match side {
+                DockSide::Left => {
+                    let width = ctx.input(|i| i.viewport().outer_rect).map_or(365.0, |r| r.width());
+                    lol_win.left as f32 / scale - gap - width
+                }
+                _ => lol_win.right as f32 / scale + gap,
+            };
+            self.dock_target = Some(egui::pos2(x, lol_win.top as f32 / scale));
+        }
+    }
+
+    /// 把窗口移向吸附目标：平滑模式下每帧移动一部分距离，否则直接到位
+    fn apply_dock_motion(&mut self, ctx: &egui::Context) {
+        if !self.autodock {
+            self.dock_target = None;
+            return;
+        }
+        let Some(target) = self.dock_target else { return };
+        let next = match ctx.input(|i| i.viewport().outer_rect).map(|r| r.min) {
+            // 已在目标位置时不重复发送
+            Some(current) if current.distance(target) <= 1.0 => return,
+            Some(current) if self.prefs.dock_smooth && current.distance(target) > DOCK_SNAP_DISTANCE => {
+                ctx.request_repaint();
+                current.lerp(target, DOCK_LERP)
+            }
+            _ => target,
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(next));
+    }
+
+    /// 恢复默认窗口大小并移到主显示器中央（多显示器变化后窗口跑到屏幕外时使用）
+    fn reset_window(&mut self, ctx: &egui::Context) {
+        // 关闭吸附，否则下一帧又会被移回客户端旁边
+        self.autodock = false;
+        self.window_reset = true;
+        let size = egui::vec2(365.0, self.prefs.window_height());
+        ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size));
+
+        // 主显示器左上角为 (0, 0)，再限制在虚拟屏幕范围内
+        let scale = ctx.pixels_per_point();
+        let (px, py) = win32::primary_screen_size();
+        let (vx, vy, vw, vh) = win32::virtual_screen_rect();
+        let min = egui::pos2(vx as f32 / scale, vy as f32 / scale);
+        let max = egui::pos2(
+            ((vx + vw) as f32 / scale - size.x).max(min.x),
+            ((vy + vh) as f32 / scale - size.y).max(min.y),
+        );
+        let center = egui::pos2(
+            (px as f32 / scale - size.x) / 2.0,
+            (py as f32 / scale - size.y) / 2.0,
+        );
+        ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(center.clamp(min, max)));
+    }
+
+    /// 抓取我的英雄的 OP.GG 推荐符文并写入客户端符文页
+    fn start_apply_runes(&mut self, ctx: &egui::Context) {
+        let Some(auth) = self.lcu_auth.clone() else {
+            return;
+        };
+        if self.rune_applying || self.draft.my_champ_slug.is_empty() {
+            return;
+        }
+        self.rune_applying = true;
+        self.rune_status = None;
+        let slug = self.draft.my_champ_slug.clone();
+        let pos = self.effective_my_pos();
+        let mode = self.queue_mode();
+        let name = self.opgg_cache.champions.get(&slug).cloned().unwrap_or_else(|| slug.clone());
+        let proxy_url = self.config.proxy_url.clone();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let result = async {
+                let client = crate::config::http_client(&proxy_url, std::time::Duration::from_secs(10))?;
+                let page = opgg::fetch_recommended_runes(&client, &slug, &pos, mode)
+                    .await?
+                    .ok_or("OP.GG 暂无该英雄的符文数据")?;
+                lcu::apply_rune_page(&auth, &page, &name).await?;
+                Ok(format!(
+                    "已应用 {name} 符文：{} + {}",
+                    crate::types::perk_style_name(page.primary_style),
+                    crate::types::perk_style_name(page.sub_style)
+                ))
+            }
+            .await;
+            let _ = tx.send(BgMsg::RunesApplied(result));
+            ctx.request_repaint();
+        });
+    }
+
+    fn ui_runes(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        if self.draft.my_champ_slug.is_empty() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            // 斗魂竞技场用强化符文，没有符文页
+            let arena = self.queue_mode() == Some("arena");
+            let enabled = self.connected && self.lcu_auth.is_some() && !self.rune_applying && !arena;
+            let hover = match self.queue_mode() {
+                Some(_) => format!("从 OP.GG 获取当前英雄的{}推荐符文，写入名为 lol-helper 的符文页", queue_name(self.queue_id)),
+                None => "从 OP.GG 获取当前英雄/位置的推荐符文，写入名为 lol-helper 的符文页".to_string(),
+            };
+            if ui
+                .add_enabled(enabled, egui::Button::new("应用推荐符文"))
+                .on_hover_text(hover)
+                .on_disabled_hover_text(if arena { "斗魂竞技场没有符文页" } else { "需连接客户端并处于选人阶段" })
+                .clicked()
+            {
+                self.start_apply_runes(ctx);
+            }
+            if self.rune_applying {
+                ui.spinner();
+            }
+            match &self.rune_status {
+                Some(Ok(msg)) => { ui.colored_label(egui::Color32::from_rgb(60, 180, 80), msg); }
+                Some(Err(e)) => { ui.colored_label(egui::Color32::from_rgb(220, 60, 60), format!("符文应用失败：{e}")); }
+                None => {}
+            }
+        });
+    }
+
+    /// 双方平均段位一览，差距超过半个大段时提示
+    fn ui_rank_summary(&self, ui: &mut egui::Ui) {
+        if self.prefs.privacy {
+            return;
+        }
+        let avg = |ally: bool| -> Option<(i32, usize)> {
+            let scores: Vec<i32> = self
+                .draft.teammates
+                .iter()
+                .filter(|m| m.is_ally == ally)
+                .filter_map(|m| crate::types::rank_to_score(&m.rank_tier, &m.rank_division, m.rank_lp))
+                .collect();
+            (!scores.is_empty()).then(|| (scores.iter().sum::<i32>() / scores.len() as i32, scores.len()))
+        };
+        let (ally, enemy) = (avg(true), avg(false));
+        if ally.is_none() && enemy.is_none() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            for (label, team, color) in [
+                ("友", ally, egui::Color32::from_rgb(60, 140, 220)),
+                ("敌", enemy, egui::Color32::from_rgb(220, 70, 70)),
+            ] {
+                let text = match team {
+                    Some((score, _)) => format!("{label} {}", crate::types::score_to_rank_text(score)),
+                    None => format!("{label} -"),
+                };
+                let resp = ui.colored_label(color, egui::RichText::new(text).small());
+                if let Some((_, n)) = team {
+                    resp.on_hover_text(format!("{n} 名已定级玩家的平均段位"));
+                }
+            }
+            if let (Some((a, _)), Some((e, _))) = (ally, enemy) {
+                // 200 分 = 半个大段
+                if e - a >= 200 {
+                    ui.colored_label(egui::Color32::from_rgb(220, 150, 40), egui::RichText::new("⚠ 敌方段位更高").small());
+                } else if a - e >= 200 {
+                    ui.colored_label(egui::Color32::from_rgb(60, 180, 80), egui::RichText::new("我方段位更高").small());
+                }
+            }
+        });
+    }
+
+    /// AI 分析使用的我方英雄：手动选择优先，否则为选人中的英雄（可能为空）
+    fn ai_pick_slug(&self) -> &str {
+        if self.ai_pick.is_empty() { &self.draft.my_champ_slug } else { &self.ai_pick }
+    }
+
+    fn champ_display_name(&self, slug: &str) -> String {
+        self.opgg_cache.champions.get(slug).cloned().unwrap_or_else(|| slug.to_string())
+    }
+
+    /// 手动对位数据编辑：保存后写入 overrides.json 并刷新克制表
+    fn ui_override_editor(&mut self, ui: &mut egui::Ui) {
+        let Some(edit) = &mut self.override_edit else { return };
+        if let Some(err) = &self.overrides_error {
+            let mut close = false;
+            ui.horizontal_wrapped(|ui| {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), format!("{err}，修正或删除该文件后重启才能保存自定义胜率"));
+                close = ui.small_button("关闭").clicked();
+            });
+            if close {
+                self.override_edit = None;
+            }
+            return;
+        }
+        let exists = self.counter_overrides.contains_key(&edit.key);
+        let (mut action, mut cancel) = (None, false);
+        ui.horizontal_wrapped(|ui| {
+            ui.label(format!("自定义 {}：", edit.name));
+            ui.add(egui::DragValue::new(&mut edit.win_rate).range(0.0..=100.0).speed(0.1).suffix("%"));
+            ui.add(egui::DragValue::new(&mut edit.games).range(0..=1_000_000).suffix(" 场"))
+                .on_hover_text("场次低于「最少场次」的行仍会被隐藏");
+            if ui.small_button("保存").clicked() {
+                action = Some(true);
+            }
+            if exists && ui.small_button("删除").on_hover_text("恢复使用 OP.GG 数据").clicked() {
+                action = Some(false);
+            }
+            cancel = ui.small_button("取消").clicked();
+        });
+        if cancel {
+            self.override_edit = None;
+        }
+        let Some(save) = action else { return };
+        let Some(edit) = self.override_edit.take() else { return };
+        if save {
+            self.counter_overrides.insert(edit.key, CounterOverride { win_rate: edit.win_rate, games: edit.games });
+        } else {
+            self.counter_overrides.remove(&edit.key);
+        }
+        if let Err(e) = opgg::save_overrides(&self.counter_overrides) {
+            self.export_status = e;
+        }
+        let (slug, name, pos) =
+            (self.counter_champ_slug.clone(), self.counter_champ_name.clone(), self.selected_enemy_pos.clone());
+        self.load_counter_data(&slug, &name, &pos);
+    }
+
+    /// “我的英雄”选择：点击克制行时 AI 用该英雄对当前敌方分析
+    fn ui_ai_pick(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("我的英雄：");
+            let follow_text = if self.draft.my_champ_slug.is_empty() {
+                "跟随选人".to_string()
+            } else {
+                format!("跟随选人（{}）", self.champ_display_name(&self.draft.my_champ_slug))
+            };
+            let selected = if self.ai_pick.is_empty() { follow_text.clone() } else { self.champ_display_name(&self.ai_pick) };
+            let mut pick = self.ai_pick.clone();
+            egui::ComboBox::from_id_salt("ai_pick")
+                .selected_text(selected)
+                .width(110.0)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut pick, String::new(), follow_text);
+                    let mut favs: Vec<&String> = self.counter_favorites.iter().collect();
+                    favs.sort();
+                    for slug in favs {
+                        ui.selectable_value(&mut pick, slug.clone(), format!("★ {}", self.champ_display_name(slug)));
+                    }
+                    ui.separator();
+                    ui.add(egui::TextEdit::singleline(&mut self.ai_pick_search).hint_text("搜索英雄…").desired_width(100.0));
+                    let query = self.ai_pick_search.trim().to_lowercase();
+                    if !query.is_empty() {
+                        let mut matches: Vec<(&String, &String)> = self
+                            .opgg_cache
+                            .champions
+                            .iter()
+                            .filter(|(slug, name)| slug.contains(&query) || name.contains(&query))
+                            .collect();
+                        matches.sort();
+                        for (slug, name) in matches.into_iter().take(20) {
+                            ui.selectable_value(&mut pick, slug.clone(), name);
+                        }
+                    }
+                });
+            if pick != self.ai_pick {
+                self.ai_pick = pick;
+                self.ai_pick_search.clear();
+            }
+            ui.weak("ⓘ").on_hover_text("点击克制英雄：用我的英雄对当前敌方做 AI 分析\nShift+点击：以点击的英雄为我方分析");
+        });
+    }
+
+    /// 队友正在悬停的 ban、队友预选和敌方悬停的英雄（ban 位参考，避免和队友 ban 重复或 ban 掉队友想玩的）
+    fn ui_intents(&self, ui: &mut egui::Ui) {
+        let in_select = self.draft.teammates.iter().any(|m| m.is_ally);
+        if self.is_aram || (!in_select && self.draft.intents.is_empty()) {
+            return;
+        }
+        egui::CollapsingHeader::new("ban / 预选意向")
+            .id_salt("champ_intents")
+            .default_open(true)
+            .show(ui, |ui| {
+                if self.draft.intents.is_empty() {
+                    ui.weak("暂无意向数据（自选等队列没有 ban 阶段，敌方悬停一般不可见）");
+                    return;
+                }
+                let groups = [
+                    ("队友想 ban", true, true),
+                    ("队友预选（别 ban）", true, false),
+                    ("敌方悬停 ban", false, true),
+                    ("敌方悬停选择", false, false),
+                ];
+                for (label, is_ally, is_ban) in groups {
+                    let list: Vec<&ChampIntent> = self.draft.intents.iter()
+                        .filter(|i| i.is_ally == is_ally && i.is_ban == is_ban)
+                        .collect();
+                    if list.is_empty() {
+                        continue;
+                    }
+                    ui.horizontal_wrapped(|ui| {
+                        ui.weak(format!("{label}："));
+                        for intent in list {
+                            if let Some(tex) = self.icon_textures.get(&intent.champion_id) {
+                                ui.image((tex.id(), egui::vec2(18.0, 18.0)));
+                            }
+                            let resp = ui.label(&intent.name);
+                            let pos = pos_cn(&intent.position);
+                            if !pos.is_empty() {
+                                resp.on_hover_text(format!("{pos}玩家"));
+                            }
+                        }
+                    });
+                }
+            });
+    }
+
+    /// 汇总克制收藏英雄池的敌方英雄（ban 位参考）
+    fn ui_pool_threats(&self, ui: &mut egui::Ui) {
+        if self.counter_favorites.is_empty() {
+            return;
+        }
+        egui::CollapsingHeader::new("英雄池威胁（避免ban/注意）")
+            .id_salt("pool_threats")
+            .default_open(false)
+            .show(ui, |ui| {
+                let mut pool: Vec<String> = self.counter_favorites.iter().cloned().collect();
+                pool.sort();
+                let threats = opgg::pool_threats(&self.opgg_cache, &pool, self.prefs.min_games, 48.0);
+                if threats.is_empty() {
+                    ui.weak("收藏英雄暂无胜率低于 48% 的对局");
+                    return;
+                }
+                egui::ScrollArea::vertical().id_salt("pool_threat_scroll").max_height(120.0).show(ui, |ui| {
+                    for t in threats.iter().take(10) {
+                        ui.horizontal(|ui| {
+                            if let Some(tex) = self.slug_to_id.get(&t.key).and_then(|id| self.icon_textures.get(id)) {
+                                ui.image((tex.id(), egui::vec2(18.0, 18.0)));
+                            }
+                            ui.label(&t.name)
+                                .on_hover_text(format!("克制：{}\n样本：{} 场", t.victims.join("、"), t.games));
+                            ui.weak(format!("克制 {}/{} 个", t.victims.len(), pool.len()));
+                            ui.colored_label(
+                                win_rate_color(t.avg_win_rate, self.prefs.colorblind_palette),
+                                format!("{:.1}%", t.avg_win_rate),
+                            );
+                        });
+                    }
+                });
+            });
+    }
+
+    /// 评估每个敌方英雄的威胁：我方候选英雄（我的英雄 + 收藏）对它的最低胜率，低于 50% 每个百分点计 1 分；
+    /// 玩家段位高于本局已定级玩家平均时每个小段（100 分）再计 1 分。按总分降序。
+    fn enemy_threats(&self) -> Vec<EnemyThreat> {
+        let mut picks: Vec<String> = self.counter_favorites.iter().cloned().collect();
+        let my_pick = self.ai_pick_slug().to_string();
+        if !my_pick.is_empty() && !picks.contains(&my_pick) {
+            picks.push(my_pick);
         }
+        let my_pos = self.effective_my_pos();
+        let rank_score = |m: &TeamMateInfo| crate::types::rank_to_score(&m.rank_tier, &m.rank_division, m.rank_lp);
+        let ranked: Vec<i32> = self.draft.teammates.iter().filter_map(rank_score).collect();
+        let lobby_avg = (!ranked.is_empty()).then(|| ranked.iter().sum::<i32>() as f64 / ranked.len() as f64);
+
+        let mut threats: Vec<EnemyThreat> = self
+            .draft.enemies
+            .iter()
+            .map(|enemy| {
+                let worst = worst_matchup(&self.opgg_cache, &self.counter_overrides, &picks, &enemy.slug, &my_pos, self.prefs.min_games)
+                    .map(|(p, wr)| (self.champ_display_name(p), wr));
+                let player = self
+                    .draft.teammates
+                    .iter()
+                    .find(|m| !m.is_ally && m.champion_id > 0 && m.champion_id == enemy.champion_id);
+                let rank_pts = match (player.and_then(rank_score), lobby_avg) {
+                    (Some(score), Some(avg)) => ((score as f64 - avg) / 100.0).max(0.0),
+                    _ => 0.0,
+                };
+                let counter_pts = counter_threat_points(worst.as_ref().map(|(_, wr)| *wr));
+                EnemyThreat {
+                    champion_id: enemy.champion_id,
+                    slug: enemy.slug.clone(),
+                    name: enemy.name.clone(),
+                    worst,
+                    rank: player
+                        .filter(|m| !m.rank_tier.is_empty())
+                        .map(|m| rank_text(&m.rank_tier, &m.rank_division, m.rank_lp)),
+                    score: counter_pts + rank_pts,
+                }
+            })
+            .collect();
+        threats.sort_by(|a, b| b.score.total_cmp(&a.score));
+        threats
     }
 
-    fn start_update(&mut self, ctx: &egui::Context) {
-        if self.updating {
+    /// 敌方威胁一览（选人阶段）：克制我方候选英雄且段位高的敌人排在前面
+    fn ui_enemy_threats(&self, ui: &mut egui::Ui) {
+        if self.draft.enemies.is_empty() || (self.counter_favorites.is_empty() && self.ai_pick_slug().is_empty()) {
             return;
         }
-        self.updating = true;
-        self.update_progress_text = "正在获取英雄列表…".into();
-
-        let tx = self.tx.clone();
-        let ctx = ctx.clone();
-        self.rt.spawn(async move {
-            let progress_tx = tx.clone();
-            let progress_ctx = ctx.clone();
-            let progress = Arc::new(move |done: usize, total: usize, name: &str| {
-                let _ = progress_tx.send(BgMsg::UpdateProgress(done, total, name.to_string()));
-                progress_ctx.request_repaint();
+        egui::CollapsingHeader::new("敌方威胁")
+            .id_salt("enemy_threats")
+            .default_open(true)
+            .show(ui, |ui| {
+                let threats = self.enemy_threats();
+                if threats.iter().all(|t| t.score <= 0.0) {
+                    ui.weak("暂无明显威胁（我方候选英雄对敌方胜率都不低于 50%）");
+                    return;
+                }
+                for t in threats.iter().filter(|t| t.score > 0.0) {
+                    ui.horizontal(|ui| {
+                        let tex = self.icon_textures.get(&t.champion_id)
+                            .or_else(|| self.slug_to_id.get(&t.slug).and_then(|id| self.icon_textures.get(id)));
+                        if let Some(tex) = tex {
+                            ui.image((tex.id(), egui::vec2(18.0, 18.0)));
+                        }
+                        ui.label(&t.name);
+                        if let Some((pick, wr)) = &t.worst {
+                            ui.weak(format!("克 {pick}"));
+                            ui.colored_label(win_rate_color(*wr, self.prefs.colorblind_palette), format!("{wr:.1}%"));
+                        }
+                        if let Some(rank) = t.rank.as_ref().filter(|_| !self.prefs.privacy) {
+                            ui.weak(rank);
+                        }
+                        ui.weak(format!("威胁 {:.0}", t.score))
+                            .on_hover_text("我方最低胜率低于 50% 的百分点 + 段位高于本局平均的小段数");
+                    });
+                }
             });
-            let result = opgg::fetch_all_counters(Some(progress)).await;
-            let _ = tx.send(BgMsg::UpdateDone(result));
-            ctx.request_repaint();
-        });
     }
 
-    fn start_ai_analysis(
-        &mut self,
-        counter_name: &str,
-        enemy_name: &str,
-        position: &str,
-        win_rate: f64,
-        ctx: &egui::Context,
-    ) {
-        let engine = match self.ai_engines.get(self.ai_engine_idx) {
-            Some(e) => e.clone(),
-            None => {
-                self.ai_text = "错误：未配置 AI 引擎，请在 config.toml 中设置。".into();
-                return;
-            }
-        };
-        let models = engine.get_models();
-        let model = models.get(self.ai_model_idx).or(models.first())
-            .cloned().unwrap_or_default();
-        let cache_key = format!("{counter_name}|{enemy_name}|{position}|{}|{model}", engine.name);
-        if let Some(cached) = self.ai_cache.get(&cache_key) {
-            self.ai_title = "AI 分析（缓存）".into();
-            self.ai_text = cached.clone();
+    /// 收藏英雄 × 敌方英雄的对位胜率热力图：行按有数据格子的平均胜率降序，无数据为灰色
+    fn ui_pool_heatmap(&self, ui: &mut egui::Ui) {
+        let enemies: Vec<&EnemyInfo> = self.draft.enemies.iter().filter(|e| !e.slug.is_empty()).collect();
+        if enemies.is_empty() || self.counter_favorites.is_empty() {
             return;
         }
+        egui::CollapsingHeader::new("英雄池对位热力图")
+            .id_salt("pool_heatmap")
+            .default_open(false)
+            .show(ui, |ui| {
+                let enemy_slugs: Vec<&str> = enemies.iter().map(|e| e.slug.as_str()).collect();
+                let rows = heatmap_rows(&self.opgg_cache, &self.counter_overrides, &self.counter_favorites, &enemy_slugs, &self.effective_my_pos());
 
-        self.ai_loading = true;
-        self.ai_title = "AI 分析".into();
-        self.ai_text.clear();
-        self.ai_cache_key = cache_key.clone();
-
-        let tx = self.tx.clone();
-        let ctx2 = ctx.clone();
-        let my_champ = counter_name.to_string();
-        let enemy_champ = enemy_name.to_string();
-        let pos = position.to_string();
-        let ck = cache_key;
-
-        self.rt.spawn(async move {
-            let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel();
+                let colorblind = self.prefs.colorblind_palette;
+                let cell_size = egui::vec2(42.0, 18.0);
+                egui::ScrollArea::horizontal().id_salt("pool_heatmap_scroll").show(ui, |ui| {
+                    egui::Grid::new("pool_heatmap_grid").spacing([2.0, 2.0]).show(ui, |ui| {
+                        ui.label("");
+                        for e in &enemies {
+                            match self.icon_textures.get(&e.champion_id) {
+                                Some(tex) => { ui.image((tex.id(), egui::vec2(18.0, 18.0))).on_hover_text(&e.name); }
+                                None => { ui.weak(&e.name); }
+                            }
+                        }
+                        ui.end_row();
 
-            // 启动流式请求
-            let stream_ctx = ctx2.clone();
-            let stream_handle = tokio::spawn(async move {
-                openai::call_ai_stream(&engine, &model, &my_champ, &enemy_champ, &pos, win_rate, chunk_tx, stream_ctx).await;
+                        for (pick, cells, _) in &rows {
+                            let name = self.champ_display_name(pick);
+                            match self.slug_to_id.get(pick).and_then(|id| self.icon_textures.get(id)) {
+                                Some(tex) => { ui.image((tex.id(), egui::vec2(18.0, 18.0))).on_hover_text(&name); }
+                                None => { ui.label(&name); }
+                            }
+                            for (cell, enemy) in cells.iter().zip(&enemies) {
+                                let (rect, resp) = ui.allocate_exact_size(cell_size, egui::Sense::hover());
+                                let (fill, text) = match cell {
+                                    Some((wr, games)) => {
+                                        let (color, _) = confidence_shade(win_rate_color(*wr, colorblind), win_rate_margin(*wr, *games));
+                                        (color, format!("{wr:.1}"))
+                                    }
+                                    None => (egui::Color32::from_gray(90), "-".to_string()),
+                                };
+                                ui.painter().rect_filled(rect, 2.0, fill);
+                                ui.painter().text(
+                                    rect.center(),
+                                    egui::Align2::CENTER_CENTER,
+                                    text,
+                                    egui::FontId::proportional(11.0),
+                                    egui::Color32::BLACK,
+                                );
+                                resp.on_hover_text(match cell {
+                                    Some((wr, games)) => format!("{name} 对 {}：{wr:.2}%（{games} 场）", enemy.name),
+                                    None => format!("{name} 对 {}：无数据", enemy.name),
+                                });
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
             });
+    }
 
-            // 转发流式消息到主 channel
-            while let Some(msg) = chunk_rx.recv().await {
-                match msg {
-                    openai::AiStreamMsg::Chunk(text) => {
-                        let _ = tx.send(BgMsg::AiChunk(text));
-                    }
-                    openai::AiStreamMsg::Done(full_text) => {
-                        let _ = tx.send(BgMsg::AiDone { cache_key: ck, full_text });
-                        ctx2.request_repaint();
-                        break;
-                    }
-                    openai::AiStreamMsg::Error(err) => {
-                        let _ = tx.send(BgMsg::AiError(err));
-                        ctx2.request_repaint();
-                        break;
-                    }
+    /// 大乱斗候选席：可交换的英雄
+    fn ui_bench(&self, ui: &mut egui::Ui) {
+        if self.draft.bench.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label("候选席：");
+            for id in &self.draft.bench {
+                let name = self.champ_names.get(id).cloned().unwrap_or_else(|| format!("#{id}"));
+                match self.icon_textures.get(id) {
+                    Some(tex) => { ui.image((tex.id(), egui::vec2(24.0, 24.0))).on_hover_text(name); }
+                    None => { ui.label(name); }
                 }
-                ctx2.request_repaint();
             }
-
-            let _ = stream_handle.await;
         });
     }
-    fn start_ai_chat(&mut self, user_prompt: &str, ctx: &egui::Context) {
-        let engine = match self.ai_engines.get(self.ai_engine_idx) {
-            Some(e) => e.clone(),
-            None => {
-                self.ai_text = "错误：未配置 AI 引擎，请在 config.toml 中设置。".into();
-                return;
-            }
-        };
-        let models = engine.get_models();
-        let model = models.get(self.ai_model_idx).or(models.first())
-            .cloned().unwrap_or_default();
-
-        self.ai_loading = true;
-        self.ai_title = "AI 对话".into();
-        self.ai_text.clear();
-
-        let tx = self.tx.clone();
-        let ctx2 = ctx.clone();
-        let prompt = user_prompt.to_string();
-
-        self.rt.spawn(async move {
-            let (chunk_tx, mut chunk_rx) = mpsc::unbounded_channel();
-
-            let stream_ctx = ctx2.clone();
-            let stream_handle = tokio::spawn(async move {
-                openai::call_ai_raw(&engine, &model, "你是一个有用的助手。用简洁中文回答。", &prompt, chunk_tx, stream_ctx).await;
-            });
 
-            while let Some(msg) = chunk_rx.recv().await {
-                match msg {
-                    openai::AiStreamMsg::Chunk(text) => {
-                        let _ = tx.send(BgMsg::AiChunk(text));
-                    }
-                    openai::AiStreamMsg::Done(_) => {
-                        let _ = tx.send(BgMsg::AiDone { cache_key: String::new(), full_text: String::new() });
-                        ctx2.request_repaint();
-                        break;
+    fn ui_duo_synergy(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        let pairs = self.bot_lane_pairs();
+        if pairs.is_empty() {
+            return;
+        }
+        ui.horizontal_wrapped(|ui| {
+            ui.label("下路组合：");
+            for (ally, adc, sup) in &pairs {
+                self.start_fetch_duo(adc, sup, ctx);
+                let name = |slug: &str| self.opgg_cache.champions.get(slug).cloned().unwrap_or_else(|| slug.to_string());
+                let side = if *ally { "友方" } else { "敌方" };
+                ui.label(format!("{side} {}+{}", name(adc), name(sup)));
+                let key = format!("{adc}+{sup}");
+                match (self.cached_duo(&key), self.duo_errors.get(&key)) {
+                    (Some(DuoSynergy { win_rate: Some(wr), games, .. }), _) => {
+                        let color = if *wr > 52.0 {
+                            egui::Color32::from_rgb(60, 180, 80)
+                        } else if *wr < 48.0 {
+                            egui::Color32::from_rgb(220, 60, 60)
+                        } else {
+                            egui::Color32::GRAY
+                        };
+                        ui.colored_label(color, format!("{wr:.1}%"))
+                            .on_hover_text(format!("组合胜率（{games}场）"));
                     }
-                    openai::AiStreamMsg::Error(err) => {
-                        let _ = tx.send(BgMsg::AiError(err));
-                        ctx2.request_repaint();
-                        break;
+                    (Some(_), _) => { ui.weak("无数据"); }
+                    (None, Some((e, _))) if !self.duo_loading.contains(&key) => {
+                        ui.weak("查询失败").on_hover_text(format!("{e}（稍后自动重试）"));
                     }
+                    (None, _) => { ui.spinner(); }
                 }
-                ctx2.request_repaint();
+                ui.add_space(8.0);
             }
-
-            let _ = stream_handle.await;
         });
     }
 
-    fn start_fetch_match_history(&mut self, game_name: &str, tag_line: &str, display_name: &str, ctx: &egui::Context) {
-        let cache_key = format!("{game_name}-{tag_line}");
-        // 检查缓存
-        if let Some(cached) = self.match_history_cache.get(&cache_key) {
-            self.match_history = cached.clone();
-            self.match_history_name = display_name.to_string();
-            self.match_history_loading = false;
+    /// 获取单局详情（按 game_id 缓存，之前失败的会重新请求）
+    fn start_fetch_match_detail(&mut self, entry: &MatchEntry, ctx: &egui::Context) {
+        let game_id = entry.game_id.clone();
+        if game_id.is_empty()
+            || self.match_detail_cache.get(&game_id).is_some_and(|d| d.is_ok())
+            || !self.match_detail_loading.insert(game_id.clone())
+        {
             return;
         }
-        if game_name.is_empty() || tag_line.is_empty() { return; }
-        self.match_history_loading = true;
-        self.match_history_name = format!("{display_name} (加载中…)");
-        self.match_history = vec![];
+        self.match_detail_cache.remove(&game_id);
+        let region = self.match_history_query.region.clone();
+        let game_name = self.match_history_query.game_name.clone();
+        let created_at = entry.created_at.clone();
+        let proxy_url = self.config.proxy_url.clone();
+        let tx = self.tx.clone();
+        let ctx = ctx.clone();
+        self.rt.spawn(async move {
+            let detail = match crate::config::http_client(&proxy_url, std::time::Duration::from_secs(15)) {
+                Ok(client) => opgg::fetch_match_detail(&client, &region, &game_id, &created_at, &game_name).await,
+                Err(e) => Err(e),
+            };
+            let _ = tx.send(BgMsg::MatchDetail { game_id, detail });
+            ctx.request_repaint();
+        });
+    }
 
-        let region = self.config.region.clone();
-        let game_name = game_name.to_string();
-        let tag_line = tag_line.to_string();
-        let name = display_name.to_string();
+    /// 后台获取尚未请求过的装备图标
+    fn start_fetch_item_icons(&mut self, items: &[i64], ctx: &egui::Context) {
+        let ids: Vec<i64> = items.iter().copied().filter(|id| self.item_icon_requested.insert(*id)).collect();
+        if ids.is_empty() {
+            return;
+        }
+        let proxy_url = self.config.proxy_url.clone();
         let tx = self.tx.clone();
         let ctx = ctx.clone();
         self.rt.spawn(async move {
-            let client = reqwest::Client::builder()
-                .timeout(std::time::Duration::from_secs(15))
-                .build()
-                .unwrap();
-            let url = opgg::match_history_url(&region, &game_name, &tag_line);
-            let result = opgg::fetch_match_history(&client, &region, &game_name, &tag_line).await;
-            let ck = format!("{game_name}-{tag_line}");
-            let _ = tx.send(BgMsg::MatchHistory { cache_key: ck, name, url, entries: result });
+            let client = crate::config::http_client(&proxy_url, std::time::Duration::from_secs(10)).ok();
+            for id in ids {
+                let icon = match &client {
+                    Some(client) => lcu::fetch_item_icon(client, id).await.ok(),
+                    None => None,
+                };
+                let _ = tx.send(BgMsg::ItemIcon { id, icon });
+            }
             ctx.request_repaint();
         });
     }
 }
 
 impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        if let Some(engine) = self.ai_engines.get(self.ai_engine_idx) {
+            self.prefs.ai_engine = engine.name.clone();
+            self.prefs.ai_model = engine.get_models().get(self.ai_model_idx).cloned().unwrap_or_default();
+        }
+        self.prefs.ai_template = self.current_prompt_template().map(|t| t.name.clone()).unwrap_or_default();
+        self.prefs.ai_template_saved = true;
+        eframe::set_value(storage, UI_PREFS_KEY, &self.prefs);
+        if self.window_reset {
+            // eframe 先写窗口几何再调用 save，这里覆盖为空值，下次启动读取失败即使用默认
+            storage.set_string("window", String::new());
+            // 只清一次：之后再移动窗口的位置照常保存
+            self.window_reset = false;
+        }
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // 启动 LCU poller（需要 ctx）
         if !self.lcu_started {
             self.lcu_started = true;
             // 强制重置窗口大小（覆盖 persistence 缓存的旧尺寸）
-            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(365.0, 900.0)));
+            ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(365.0, self.prefs.window_height())));
 
             let (lcu_tx, mut lcu_rx) = mpsc::unbounded_channel();
             let lockfile_dir = self.config.lockfile_dir.clone();
-            lcu::spawn_lcu_poller(self.rt.clone(), lockfile_dir, lcu_tx, ctx.clone());
+            lcu::spawn_lcu_poller(self.rt.clone(), lockfile_dir, self.lcu_reload.clone(), lcu_tx, ctx.clone());
+
+            if self.prefs.privacy {
+                self.apply_privacy();
+            }
+
+            if self.config.auto_update && self.data_is_stale() {
+                self.start_update(ctx);
+            }
 
             // 转发 LCU 消息到主 channel
             let tx = self.tx.clone();
             let ctx2 = ctx.clone();
             self.rt.spawn(async move {
                 while let Some(state) = lcu_rx.recv().await {
-                    let _ = tx.send(BgMsg::Lcu(state));
+                    let _ = tx.send(BgMsg::Lcu(Box::new(state)));
+                    ctx2.request_repaint();
+                }
+            });
+
+            // 全局快捷键显示/隐藏窗口
+            let hotkey = self.config.hotkey.trim().to_string();
+            if !hotkey.is_empty() {
+                let tx = self.tx.clone();
+                let ctx2 = ctx.clone();
+                // 在快捷键线程里直接切换：窗口隐藏时界面线程可能不再运行，无法处理消息
+                self.hotkey_status = match win32::spawn_hotkey_listener(&hotkey, move || {
+                    if let Err(e) = win32::toggle_own_windows() {
+                        let _ = tx.send(BgMsg::ToggleWindowFailed(e));
+                    }
+                    ctx2.request_repaint();
+                }) {
+                    Ok(()) => hotkey,
+                    Err(e) => e,
+                };
+            }
+
+            // 游戏内实时数据
+            let (live_tx, mut live_rx) = mpsc::unbounded_channel();
+            live::spawn_live_poller(self.rt.clone(), live_tx, ctx.clone());
+            let tx = self.tx.clone();
+            let ctx2 = ctx.clone();
+            self.rt.spawn(async move {
+                while let Some(state) = live_rx.recv().await {
+                    let _ = tx.send(BgMsg::Live(state));
                     ctx2.request_repaint();
                 }
             });
         }
 
         self.process_messages(ctx);
+        self.prefetch_ally_histories(ctx);
 
-        // 窗口吸附和最小化跟随
-        let found_win = win32::find_lol_client_window();
-        if let Some(ref lol_win) = found_win {
-            let (scr_x, scr_y, scr_w, scr_h) = win32::virtual_screen_rect();
-            self.debug_lol_win = format!(
-                "找到窗口: L={} T={} R={} B={} min={} | 屏幕: x={} y={} w={} h={}",
-                lol_win.left, lol_win.top, lol_win.right, lol_win.bottom,
-                lol_win.minimized, scr_x, scr_y, scr_w, scr_h,
-            );
-
-            if self.autodock {
-                let scale = ctx.pixels_per_point();
-                // 物理像素 → 逻辑坐标
-                let x = (lol_win.right as f32 + 6.0) / scale;
-                let y = lol_win.top as f32 / scale;
-                ctx.send_viewport_cmd(egui::ViewportCommand::OuterPosition(
-                    egui::pos2(x, y),
-                ));
-            }
-        } else {
-            self.debug_lol_win = "未找到 LOL 窗口".into();
+        // 窗口吸附和最小化跟随（每 dock_interval 查找一次窗口，移动每帧进行）
+        let now = std::time::Instant::now();
+        let interval = self.prefs.dock_interval();
+        let window_due = self
+            .last_window_check
+            .is_none_or(|t| now.duration_since(t) >= interval);
+        if window_due {
+            self.last_window_check = Some(now);
+            self.update_window_dock(ctx);
+        }
+        self.apply_dock_motion(ctx);
+        if self.autodock {
+            // 空闲时也按间隔醒来跟随客户端窗口
+            ctx.request_repaint_after(interval);
         }
 
-        // 置顶
-        ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
-            if self.topmost {
-                egui::WindowLevel::AlwaysOnTop
-            } else {
-                egui::WindowLevel::Normal
-            },
-        ));
+        // 置顶（仅在状态变化时发送）
+        if self.applied_topmost != Some(self.prefs.topmost) {
+            self.applied_topmost = Some(self.prefs.topmost);
+            ctx.send_viewport_cmd(egui::ViewportCommand::WindowLevel(
+                if self.prefs.topmost {
+                    egui::WindowLevel::AlwaysOnTop
+                } else {
+                    egui::WindowLevel::Normal
+                },
+            ));
+        }
 
         // 对局历史侧面板
-        let show_history = self.selected_teammate_idx.is_some();
+        let show_history = !self.prefs.compact
+            && !self.prefs.privacy
+            && (self.draft.selected_teammate_idx.is_some() || self.manual_search_active);
         if show_history != self.history_panel_open {
             self.history_panel_open = show_history;
             // 动态调整窗口宽度
             if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
                 let new_width = if show_history { rect.width() + 300.0 } else { (rect.width() - 300.0).max(365.0) };
-                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(new_width, rect.height())));
+                ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(egui::vec2(new_width, self.prefs.window_height())));
             }
         }
         if show_history {
             egui::SidePanel::right("match_history_panel")
                 .exact_width(290.0)
-                .show(ctx, |ui| { self.ui_match_history(ui); });
+                .show(ctx, |ui| { self.ui_match_history(ui, ctx); });
+        }
+
+        if let Some(path) = self.first_run_config.clone() {
+            let mut open = true;
+            egui::Window::new("首次运行")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+                .show(ctx, |ui| {
+                    ui.label("已生成配置文件模板：");
+                    ui.monospace(path.display().to_string());
+                    ui.label("填写 AI 引擎的 api_key（以及地区 region）后重启程序即可使用 AI 分析。");
+                    if ui.button("知道了").clicked() {
+                        open = false;
+                    }
+                });
+            if !open {
+                self.first_run_config = None;
+            }
+        }
+
+        if self.scout_open && !self.prefs.privacy {
+            let mut open = true;
+            egui::Window::new("批量侦察")
+                .open(&mut open)
+                .default_width(340.0)
+                .show(ctx, |ui| self.ui_scout(ui, ctx));
+            self.scout_open = open;
         }
 
         egui::CentralPanel::default().show(ctx, |ui| {
             self.ui_content(ui, ctx);
         });
+
+        if self.ai_detached {
+            self.show_ai_viewport(ctx);
+        }
     }
 }
 
 impl App {
     fn ui_content(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
         let row_h = ui.text_style_height(&egui::TextStyle::Body) + ui.spacing().item_spacing.y;
+        // 帧开始时没有输入框占用键盘，克制表才响应方向键 / Enter（避免输入框回车误触发分析）
+        let table_keys = !ctx.wants_keyboard_input();
 
         // === 选项 ===
-        ui.horizontal(|ui| {
-            ui.checkbox(&mut self.topmost, "置顶");
+        ui.horizontal_wrapped(|ui| {
+            let (color, status) = self.connection_status();
+            let (rect, resp) = ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+            ui.painter().circle_filled(rect.center(), 4.0, color);
+            resp.on_hover_text(format!("{status}\n最后更新：{}", self.last_update_time));
+            if let Some((phase, deadline)) = &self.phase_deadline {
+                let left = deadline.saturating_duration_since(std::time::Instant::now()).as_secs_f32().ceil() as u64;
+                let color = if left < 10 { egui::Color32::from_rgb(220, 60, 60) } else { ui.visuals().text_color() };
+                ui.colored_label(color, format!("{} {left}秒", champ_select_phase_cn(phase)))
+                    .on_hover_text("当前选人阶段剩余时间");
+                ctx.request_repaint_after(std::time::Duration::from_millis(250));
+            }
+            if self.queue_id > 0 {
+                let hint = if self.counters_unavailable().is_some() {
+                    "该模式不显示克制数据，出装 / 符文使用该模式的数据"
+                } else if self.queue_mode().is_some() {
+                    "出装 / 符文使用该模式的数据"
+                } else {
+                    "克制 / 出装 / 符文使用排位数据"
+                };
+                ui.weak(queue_name(self.queue_id)).on_hover_text(format!("队列 {}：{hint}", self.queue_id));
+            }
+            ui.checkbox(&mut self.prefs.topmost, "置顶")
+                .on_hover_text("窗口保持在最上层；部分窗口管理器下置顶会抢焦点，可关闭");
             ui.checkbox(&mut self.autodock, "吸附");
+            ui.add_enabled_ui(self.autodock, |ui| {
+                egui::ComboBox::from_id_salt("dock_side")
+                    .selected_text(self.prefs.dock_side.label())
+                    .width(44.0)
+                    .show_ui(ui, |ui| {
+                        for side in [DockSide::Right, DockSide::Left, DockSide::Auto] {
+                            ui.selectable_value(&mut self.prefs.dock_side, side, side.label());
+                        }
+                    });
+                ui.add(egui::DragValue::new(&mut self.prefs.dock_gap).range(0.0..=100.0).suffix("px"))
+                    .on_hover_text("与客户端窗口的间距");
+                ui.add(egui::DragValue::new(&mut self.prefs.dock_interval_ms).range(100..=2000).speed(10).suffix("ms"))
+                    .on_hover_text("查找客户端窗口的间隔：越小跟随越及时，越大越省资源");
+                ui.checkbox(&mut self.prefs.dock_smooth, "平滑")
+                    .on_hover_text("滑动到吸附位置，拖动客户端时不再跳动");
+            });
+            ui.checkbox(&mut self.lock_data, "锁定数据")
+                .on_hover_text("离开选人后保留上一局的敌方和队友，直到手动清空");
+            if self.lock_data && ui.small_button("清空").clicked() {
+                self.draft.clear();
+            }
+            if ui.checkbox(&mut self.prefs.privacy, "隐私")
+                .on_hover_text("隐藏玩家名与战绩，并从录屏/直播画面中排除本窗口")
+                .changed()
+            {
+                self.apply_privacy();
+            }
+            if ui.checkbox(&mut self.prefs.compact, "紧凑").changed() {
+                // 只改高度，宽度和位置（吸附）保持不变
+                if let Some(rect) = ctx.input(|i| i.viewport().inner_rect) {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(
+                        egui::vec2(rect.width(), self.prefs.window_height()),
+                    ));
+                }
+            }
             let debug_label = if self.show_debug { "调试 ▲" } else { "调试 ▼" };
             if ui.small_button(debug_label).clicked() {
                 self.show_debug = !self.show_debug;
@@ -683,14 +3642,60 @@ impl App {
                 ui.colored_label(egui::Color32::from_rgb(100, 180, 255), &self.debug_lol_win);
             }
             let my_pos_text = if self.my_pos.is_empty() { "未识别" } else { pos_cn(&self.my_pos) };
+            let font_text = if self.cjk_font.is_empty() { "未找到中文字体" } else { &self.cjk_font };
+            let hotkey_text = if self.hotkey_status.is_empty() { "未启用" } else { &self.hotkey_status };
             ui.label(format!(
-                "连接：{} | 语言：{} | 位置：{} | 更新：{}",
+                "连接：{} | 语言：{} | 位置：{} | 更新：{} | 字体：{font_text} | 快捷键：{hotkey_text}",
                 if self.connected { "YES" } else { "NO" },
                 self.champion_lang, my_pos_text, self.last_update_time,
             ));
-            if !self.error.is_empty() {
-                ui.colored_label(egui::Color32::RED, &self.error);
+            if !self.error.is_empty() && self.error != self.dismissed_error {
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(egui::Color32::RED, &self.error);
+                    if ui.small_button("✕").on_hover_text("不再显示此错误（内容变化时会重新显示）").clicked() {
+                        self.dismissed_error = self.error.clone();
+                    }
+                });
+            } else if !self.lcu_info.is_empty() {
+                ui.weak(&self.lcu_info);
             }
+            ui.horizontal(|ui| {
+                ui.label(format!("图标：{} 个", self.icon_textures.len()));
+                if !self.icon_status.is_empty() && self.slug_to_id.is_empty() {
+                    ui.colored_label(egui::Color32::from_rgb(220, 80, 80), &self.icon_status);
+                }
+                if ui.small_button("重载图标").on_hover_text("图标显示异常（如睡眠唤醒后）时，从内存中的图片数据重建纹理").clicked() {
+                    self.reload_icon_textures(ctx);
+                }
+                if ui.small_button("重载英雄数据").on_hover_text("按当前客户端语言重新加载英雄名和图标").clicked() {
+                    self.lcu_reload.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+                if ui.small_button("重置窗口").on_hover_text("恢复默认大小并移到主显示器中央（会关闭吸附）").clicked() {
+                    self.reset_window(ctx);
+                }
+            });
+            ui.horizontal_wrapped(|ui| {
+                if crate::config::config_encrypted() {
+                    ui.label("配置：已加密");
+                    if ui.small_button("解密配置").on_hover_text("还原为明文 config.toml 以便编辑，改完后可再次加密").clicked() {
+                        self.config_crypt_status = match crate::config::decrypt_config() {
+                            Ok(p) => format!("已还原：{}", p.display()),
+                            Err(e) => e,
+                        };
+                    }
+                } else if ui.small_button("加密配置")
+                    .on_hover_text("用 Windows DPAPI 把 config.toml 加密为 config.enc 并删除明文（仅本机当前用户可解密）")
+                    .clicked()
+                {
+                    self.config_crypt_status = match crate::config::encrypt_config() {
+                        Ok(p) => format!("已加密：{}", p.display()),
+                        Err(e) => e,
+                    };
+                }
+                if !self.config_crypt_status.is_empty() {
+                    ui.weak(&self.config_crypt_status);
+                }
+            });
             ui.horizontal(|ui| {
                 ui.label("添加敌方：");
                 let hero_display = self.opgg_cache.champions.get(&self.debug_slug)
@@ -707,34 +3712,77 @@ impl App {
                             ui.selectable_value(&mut self.debug_slug, hero.clone(), label);
                         }
                     });
-                let pos_display = pos_cn(&self.debug_pos);
+                let pos_label = |pos: &str| if pos.is_empty() { "自动" } else { pos_cn(pos) };
                 egui::ComboBox::from_id_salt("debug_pos")
-                    .selected_text(pos_display)
+                    .selected_text(pos_label(&self.debug_pos))
                     .show_ui(ui, |ui| {
                         for pos in &self.debug_pos_options {
-                            let label = pos_cn(pos);
-                            ui.selectable_value(&mut self.debug_pos, pos.clone(), label);
+                            ui.selectable_value(&mut self.debug_pos, pos.clone(), pos_label(pos));
                         }
-                    });
+                    })
+                    .response
+                    .on_hover_text("自动：按英雄常用位置分配");
                 if ui.button("添加").clicked() {
                     let slug = self.debug_slug.trim().to_lowercase().replace(' ', "-");
                     if !slug.is_empty() {
                         let cn_name = self.opgg_cache.champions.get(&slug).cloned().unwrap_or_else(|| slug.clone());
-                        let pos = self.debug_pos.clone();
-                        self.my_pos = pos.clone();
-                        self.enemies.push(EnemyInfo { champion_id: -1, name: cn_name, slug, pos });
+                        let pos = if self.debug_pos.is_empty() {
+                            crate::types::default_role(&slug).to_string()
+                        } else {
+                            self.debug_pos.clone()
+                        };
+                        // 我的位置单独设置；未设置时沿用第一个敌人的位置
+                        if self.my_pos.is_empty() {
+                            self.my_pos = pos.clone();
+                        }
+                        self.draft.enemies.push(EnemyInfo { champion_id: -1, name: cn_name, slug, pos });
                         self.connected = true;
                         self.error.clear();
+                        self.lcu_error_kind = None;
                         self.last_update_time = chrono::Local::now().format("%H:%M:%S").to_string();
                     }
                 }
                 if ui.button("清空").clicked() {
-                    self.enemies.clear();
-                    self.selected_enemy_idx = None;
-                    self.lane_enemy_id = None;
+                    self.draft.enemies.clear();
+                    self.draft.selected_enemy_idx = None;
+                    self.draft.lane_enemy_id = None;
+                    self.draft.pos_overrides.clear();
                     self.my_pos.clear();
+                    self.draft.my_champ_slug.clear();
                 }
             });
+            ui.horizontal(|ui| {
+                ui.label("我的英雄：");
+                let my_display = if self.draft.my_champ_slug.is_empty() {
+                    "未选择".to_string()
+                } else {
+                    self.opgg_cache.champions.get(&self.draft.my_champ_slug)
+                        .cloned()
+                        .unwrap_or_else(|| self.draft.my_champ_slug.clone())
+                };
+                egui::ComboBox::from_id_salt("debug_my_champ")
+                    .selected_text(my_display)
+                    .width(90.0)
+                    .show_ui(ui, |ui| {
+                        for hero in &self.debug_hero_options {
+                            let label = self.opgg_cache.champions.get(hero)
+                                .cloned()
+                                .unwrap_or_else(|| hero.clone());
+                            if ui.selectable_value(&mut self.draft.my_champ_slug, hero.clone(), label).changed() {
+                                self.draft.my_champ_from_lcu = false;
+                            }
+                        }
+                    });
+                ui.label("我的位置：");
+                let my_pos_label = |pos: &str| if pos.is_empty() { "未分配" } else { pos_cn(pos) };
+                egui::ComboBox::from_id_salt("debug_my_pos")
+                    .selected_text(my_pos_label(&self.my_pos))
+                    .show_ui(ui, |ui| {
+                        for pos in &self.debug_pos_options {
+                            ui.selectable_value(&mut self.my_pos, pos.clone(), my_pos_label(pos));
+                        }
+                    });
+            });
         }
 
         ui.separator();
@@ -742,11 +3790,13 @@ impl App {
         // === 敌方英雄 / 我方队友 ===
         let mut clicked_idx: Option<usize> = None;
         let mut clicked_teammate: Option<usize> = None;
+        let mut pos_action: Option<(i64, Option<String>)> = None;
         let half_w: f32 = 170.0;
-        let list_h = row_h * 5.0;
+        let list_h = row_h * self.prefs.list_rows();
 
         let section_h = list_h + row_h * 1.5; // 标题 + 列表
         let section_w = half_w * 2.0 + 12.0; // 两列 + 分隔符 + 间距
+        let mut open_config = false;
         ui.allocate_ui(egui::vec2(section_w.min(ui.available_width()), section_h), |ui| {
         ui.horizontal_top(|ui| {
             // 左侧：敌方英雄
@@ -758,13 +3808,23 @@ impl App {
                     .max_height(list_h)
                     .auto_shrink(false)
                     .show(ui, |ui| {
-                        for (i, enemy) in self.enemies.iter().enumerate() {
-                            let is_lane = self.lane_enemy_id.map_or(false, |id| id == enemy.champion_id);
+                        let lane_enemy = self.effective_lane_enemy();
+                        let my_pos = self.effective_my_pos();
+                        for (i, enemy) in self.draft.enemies.iter().enumerate() {
+                            let enemy_pos = self.effective_pos(enemy.champion_id, &enemy.pos);
+                            // 调试添加的敌方（id = -1）位置与我相同即视为对线对手
+                            let is_lane = lane_enemy == Some(enemy.champion_id)
+                                || (enemy.champion_id < 0 && !my_pos.is_empty() && enemy_pos == my_pos);
                             let star = if is_lane { "⭐ " } else { "" };
-                            let pc = pos_cn(&enemy.pos);
+                            let lane_wr = if is_lane {
+                                opgg::matchup_win_rate(&self.opgg_cache, &self.counter_overrides, &self.draft.my_champ_slug, &enemy.slug, &my_pos)
+                            } else {
+                                None
+                            };
+                            let pc = pos_cn(&enemy_pos);
                             let pos_text = if pc.is_empty() { String::new() } else { format!(" [{pc}]") };
                             let text = format!("{}{}{pos_text}", star, enemy.name);
-                            let selected = self.selected_enemy_idx == Some(i);
+                            let selected = self.draft.selected_enemy_idx == Some(i);
                             let clicked = ui.horizontal(|ui| {
                                 let tex = self.icon_textures.get(&enemy.champion_id)
                                     .or_else(|| self.slug_to_id.get(&enemy.slug)
@@ -772,10 +3832,35 @@ impl App {
                                 if let Some(tex) = tex {
                                     ui.image((tex.id(), egui::vec2(20.0, 20.0)));
                                 }
-                                ui.selectable_label(selected, &text).clicked()
+                                // 悬停时才计算各位置概况
+                                let label = ui.selectable_label(selected, &text)
+                                    .on_hover_ui(|ui| self.ui_position_breakdown(ui, &enemy.slug, enemy.champion_id > 0));
+                                let clicked = label.clicked();
+                                if enemy.champion_id > 0 {
+                                    label.context_menu(|ui| {
+                                        if let Some(a) = pos_override_menu(ui, self.draft.pos_overrides.get(&enemy.champion_id)) {
+                                            pos_action = Some((enemy.champion_id, a));
+                                        }
+                                    });
+                                }
+                                if let Some((wr, games)) = lane_wr {
+                                    let color = if wr > 52.0 {
+                                        egui::Color32::from_rgb(60, 180, 80)
+                                    } else if wr < 48.0 {
+                                        egui::Color32::from_rgb(220, 60, 60)
+                                    } else {
+                                        egui::Color32::GRAY
+                                    };
+                                    ui.colored_label(color, format!("{wr:.1}%"))
+                                        .on_hover_text(format!("我方英雄对线胜率（{games}场）"));
+                                }
+                                clicked
                             }).inner;
                             if clicked { clicked_idx = Some(i); }
                         }
+                        if self.draft.enemies.is_empty() {
+                            open_config = self.ui_players_empty(ui);
+                        }
                     });
             });
 
@@ -784,30 +3869,66 @@ impl App {
             // 右侧：全部玩家
             ui.vertical(|ui| {
                 ui.set_width(half_w);
-                ui.label("全部玩家：");
+                ui.horizontal(|ui| {
+                    ui.label("全部玩家：");
+                    let enemy_ids = enemy_riot_ids(&self.draft.teammates);
+                    ui.add_enabled_ui(!enemy_ids.is_empty(), |ui| {
+                        if ui.small_button("复制敌方").on_hover_text("按行复制敌方 name#tag，可粘贴到多人查询").clicked() {
+                            ctx.copy_text(enemy_ids.join("\n"));
+                        }
+                        if ui.small_button("复制多查链接").on_hover_text("OP.GG 多人查询链接").clicked() {
+                            ctx.copy_text(opgg::multisearch_url(&self.config.region, &enemy_ids));
+                        }
+                    });
+                });
+                self.ui_rank_summary(ui);
                 egui::ScrollArea::vertical()
                     .id_salt("teammate_scroll")
                     .max_height(list_h)
                     .auto_shrink(false)
                     .show(ui, |ui| {
-                        for (i, mate) in self.teammates.iter().enumerate() {
-                            let rank_text = if mate.rank_tier.is_empty() {
-                                "未定级".to_string()
-                            } else {
-                                format!("{}{} {}LP", rank_cn(&mate.rank_tier), mate.rank_division, mate.rank_lp)
-                            };
+                        for (i, mate) in self.draft.teammates.iter().enumerate() {
+                            let rank_text = rank_text(&mate.rank_tier, &mate.rank_division, mate.rank_lp);
                             let team_color = if mate.is_ally {
                                 egui::Color32::from_rgb(60, 140, 220)
                             } else {
                                 egui::Color32::from_rgb(220, 70, 70)
                             };
-                            let selected = self.selected_teammate_idx == Some(i);
+                            let selected = self.draft.selected_teammate_idx == Some(i);
+                            let warning = self.dodge_warning_for(mate);
                             let clicked = ui.horizontal(|ui| {
                                 ui.colored_label(team_color, if mate.is_ally { "友" } else { "敌" });
                                 if let Some(tex) = self.icon_textures.get(&mate.champion_id) {
                                     ui.image((tex.id(), egui::vec2(20.0, 20.0)));
                                 }
-                                ui.selectable_label(selected, format!("{} {}", mate.summoner_name, rank_text)).clicked()
+                                let text = if self.prefs.privacy {
+                                    // 隐私模式只显示英雄名
+                                    let champ = self.champ_names.get(&mate.champion_id).unwrap_or(&mate.champion_name);
+                                    if champ.is_empty() { "未选择".to_string() } else { champ.clone() }
+                                } else {
+                                    format!("{} {}", mate.summoner_name, rank_text)
+                                };
+                                let label = match warning {
+                                    Some((wr, weighted, games)) => ui
+                                        .selectable_label(
+                                            selected,
+                                            egui::RichText::new(format!("⚠ {text}")).color(egui::Color32::from_rgb(220, 60, 60)),
+                                        )
+                                        .on_hover_text(match weighted {
+                                            Some(w) => format!("近{games}场胜率 {wr:.0}%（近期加权 {w:.0}%）"),
+                                            None => format!("近{games}场胜率 {wr:.0}%"),
+                                        }),
+                                    None => ui.selectable_label(selected, text),
+                                };
+                                let clicked = label.clicked();
+                                if mate.champion_id > 0 {
+                                    label.context_menu(|ui| {
+                                        if let Some(a) = pos_override_menu(ui, self.draft.pos_overrides.get(&mate.champion_id)) {
+                                            pos_action = Some((mate.champion_id, a));
+                                        }
+                                    });
+                                }
+                                clicked
                             }).inner;
                             if clicked { clicked_teammate = Some(i); }
                         }
@@ -815,32 +3936,230 @@ impl App {
             });
         });
         }); // allocate_ui
+        if open_config {
+            if let Err(e) = open_config_file() {
+                self.export_status = format!("打开配置文件失败：{e}");
+            }
+        }
+
+        self.ui_bench(ui);
+        self.ui_duo_synergy(ui, ctx);
+        self.ui_runes(ui, ctx);
+        // 选人中显示自己的常用英雄（LCU 熟练度，比查 OP.GG 快）
+        if self.connected && !self.draft.teammates.is_empty() {
+            let me = self.my_puuid.clone();
+            self.ui_mastery_row(ui, "我的常用：", &me, ctx);
+        }
 
+        if let Some((champion_id, action)) = pos_action {
+            match action {
+                Some(pos) => { self.draft.pos_overrides.insert(champion_id, pos); }
+                None => { self.draft.pos_overrides.remove(&champion_id); }
+            }
+            // 当前克制表对应的敌人位置变了则重新加载
+            if let Some(enemy) = self.draft.selected_enemy_idx.and_then(|i| self.draft.enemies.get(i)).cloned() {
+                if enemy.champion_id == champion_id {
+                    self.selected_enemy_pos = self.effective_pos(enemy.champion_id, &enemy.pos);
+                    if !self.selected_enemy_pos.is_empty() {
+                        let pos = self.selected_enemy_pos.clone();
+                        self.load_counter_data(&enemy.slug, &enemy.name, &pos);
+                    }
+                }
+            }
+        }
         if let Some(idx) = clicked_idx {
-            self.selected_enemy_idx = Some(idx);
-            let enemy = self.enemies[idx].clone();
-            self.selected_enemy_pos = if enemy.pos.is_empty() { self.my_pos.clone() } else { enemy.pos.clone() };
-            self.load_counter_data(&enemy.slug, &enemy.name, &self.selected_enemy_pos.clone());
+            self.select_enemy(idx);
         }
         if let Some(idx) = clicked_teammate {
-            if self.selected_teammate_idx == Some(idx) {
+            self.manual_search_active = false;
+            if self.draft.selected_teammate_idx == Some(idx) {
                 // 再次点击取消选中，关闭面板
-                self.selected_teammate_idx = None;
+                self.draft.selected_teammate_idx = None;
             } else {
-                self.selected_teammate_idx = Some(idx);
-                let mate = self.teammates[idx].clone();
-                self.start_fetch_match_history(&mate.summoner_name, &mate.tag_line, &mate.summoner_name, ctx);
+                self.draft.selected_teammate_idx = Some(idx);
+                let mate = self.draft.teammates[idx].clone();
+                let query = HistoryQuery {
+                    game_name: mate.summoner_name.clone(),
+                    tag_line: mate.tag_line,
+                    display_name: mate.summoner_name,
+                    region: self.config.region.clone(),
+                    rank: Some(SoloRank {
+                        tier: mate.rank_tier,
+                        division: mate.rank_division,
+                        lp: mate.rank_lp,
+                    }),
+                };
+                self.start_fetch_match_history(query, false, ctx);
+            }
+        }
+
+        // === 手动查询玩家 ===
+        let mut do_search = false;
+        if !self.prefs.privacy {
+            ui.horizontal(|ui| {
+                let resp = ui.add(
+                    egui::TextEdit::singleline(&mut self.search_input)
+                        .hint_text("查询玩家 名字#标签")
+                        .desired_width(150.0),
+                );
+                if resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    do_search = true;
+                }
+                egui::ComboBox::from_id_salt("search_region")
+                    .selected_text(&self.search_region)
+                    .width(50.0)
+                    .show_ui(ui, |ui| {
+                        for r in OPGG_REGIONS {
+                            ui.selectable_value(&mut self.search_region, r.to_string(), *r);
+                        }
+                    });
+                if ui.button("查询").clicked() {
+                    do_search = true;
+                }
+                if ui.button("批量").on_hover_text("一次查询多名玩家的近期战绩").clicked() {
+                    self.scout_open = !self.scout_open;
+                }
+            });
+        }
+        if do_search {
+            match self.search_input.trim().split_once('#') {
+                Some((name, tag)) if !name.trim().is_empty() && !tag.trim().is_empty() => {
+                    self.search_error.clear();
+                    self.manual_search_active = true;
+                    self.draft.selected_teammate_idx = None;
+                    let query = HistoryQuery {
+                        game_name: name.trim().to_string(),
+                        tag_line: tag.trim().to_string(),
+                        display_name: format!("{}#{}", name.trim(), tag.trim()),
+                        region: self.search_region.clone(),
+                        rank: None,
+                    };
+                    self.start_fetch_match_history(query, false, ctx);
+                }
+                _ => {
+                    self.search_error = "格式应为 名字#标签（如 Faker#KR1），标签不能省略".into();
+                }
             }
         }
+        if !self.search_error.is_empty() {
+            ui.colored_label(egui::Color32::from_rgb(180, 120, 0), &self.search_error);
+        }
+
+        // === 游戏内实时数据 ===
+        if self.live.in_game {
+            self.ui_live_game(ui);
+        }
+
+        // === 数据过期提示 ===
+        if !self.updating && !self.stale_banner_dismissed && self.data_is_stale() {
+            egui::Frame::group(ui.style())
+                .fill(egui::Color32::from_rgb(90, 70, 20))
+                .show(ui, |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        let text = if self.opgg_cache.counters.is_empty() {
+                            "本地没有克制数据，首次使用需要全量更新".to_string()
+                        } else {
+                            format!("克制数据已超过 {} 天未更新", self.config.auto_update_days)
+                        };
+                        ui.colored_label(egui::Color32::from_rgb(255, 220, 120), text);
+                        if ui.button("立即更新").clicked() {
+                            self.start_update(ctx);
+                        }
+                        if ui.small_button("✖").on_hover_text("本次不再提示").clicked() {
+                            self.stale_banner_dismissed = true;
+                        }
+                    });
+                });
+        }
 
         // === 数据管理 ===
         ui.horizontal_wrapped(|ui| {
             let data_text = if self.updating { self.update_progress_text.clone() } else { self.data_time_text() };
             ui.label(&data_text);
+            if !self.updating && !self.update_progress_text.is_empty() {
+                ui.colored_label(egui::Color32::from_rgb(220, 80, 80), &self.update_progress_text);
+            }
+            let unparsed = opgg::empty_counter_count(&self.opgg_cache);
+            if !self.updating && unparsed > 0 {
+                ui.colored_label(
+                    egui::Color32::from_rgb(220, 150, 40),
+                    format!("⚠ {unparsed} 个页面解析不到数据"),
+                )
+                .on_hover_text("页面正常返回但没有克制数据：可能该位置样本太少，数量很多时多半是 OP.GG 改了页面结构");
+            }
+            let cache_patch = &self.opgg_cache.patch;
+            if !self.updating && !cache_patch.is_empty() && !self.game_version.is_empty() && *cache_patch != self.game_version {
+                ui.colored_label(
+                    egui::Color32::from_rgb(220, 150, 40),
+                    format!("⚠ 客户端已是 {}，建议更新", self.game_version),
+                );
+            }
             if ui.add_enabled(!self.updating, egui::Button::new("全量更新")).clicked() {
                 self.start_update(ctx);
             }
+            let missing = opgg::missing_counter_keys(&self.opgg_cache).len();
+            if !self.updating
+                && missing > 0
+                && ui.button(format!("补全 {missing} 个失败项"))
+                    .on_hover_text("只重新采集上次更新失败的英雄+位置，合并进现有数据")
+                    .clicked()
+            {
+                self.start_retry_missing(ctx);
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            if ui.add_enabled(!self.counter_data.is_empty(), egui::Button::new("导出CSV")).clicked() {
+                let rows: Vec<CounterDisplay> = self.counter_data.iter()
+                    .filter(|r| self.counter_row_visible(r))
+                    .cloned()
+                    .collect();
+                self.export_status = match export_counters_csv(
+                    &self.counter_champ_slug,
+                    &rows,
+                    &self.counter_favorites,
+                ) {
+                    Ok(path) => format!("已导出：{}", path.display()),
+                    Err(e) => format!("导出失败：{e}"),
+                };
+            }
+            if ui.button("导出收藏").clicked() {
+                self.export_status = match export_favorites(&self.counter_favorites) {
+                    Ok(path) => format!("已导出：{}", path.display()),
+                    Err(e) => format!("导出失败：{e}"),
+                };
+            }
+            if ui.button("导入收藏").on_hover_text(format!("从 exe 同目录的 {FAVORITES_IMPORT_FILE} 合并导入")).clicked() {
+                self.export_status = match import_favorites() {
+                    Ok(imported) => {
+                        let before = self.counter_favorites.len();
+                        self.counter_favorites.extend(imported);
+                        save_favorites(&self.counter_favorites);
+                        format!("已导入 {} 个新收藏", self.counter_favorites.len() - before)
+                    }
+                    Err(e) => format!("导入失败：{e}"),
+                };
+            }
+            ui.add_enabled_ui(!self.updating, |ui| {
+                ui.menu_button("导入数据", |ui| {
+                    let mut merge = None;
+                    if ui.button("合并（同一条目保留较新的）").clicked() {
+                        merge = Some(true);
+                    }
+                    if ui.button("替换当前数据").clicked() {
+                        merge = Some(false);
+                    }
+                    if let Some(merge) = merge {
+                        ui.close_menu();
+                        self.import_opgg_data(merge);
+                    }
+                })
+                .response
+                .on_hover_text("导入别人分享的 opgg_data.json");
+            });
         });
+        if !self.export_status.is_empty() {
+            ui.weak(&self.export_status);
+        }
 
         // === 克制数据表格（固定10行高度）===
         let mut pos_changed = false;
@@ -849,40 +4168,114 @@ impl App {
                 ui.label("克制数据（选择上方英雄后显示）：");
             } else {
                 ui.label(format!("克制 - {}", self.counter_champ_name));
+                self.ui_counter_age(ui);
+                if !self.counter_reverse && !self.is_aram {
+                    if let Some(used) = opgg::counter_fallback_position(
+                        &self.opgg_cache, &self.counter_champ_slug, &self.selected_enemy_pos,
+                    ) {
+                        ui.colored_label(egui::Color32::from_rgb(220, 150, 40), format!("(使用{}数据)", pos_cn(used)))
+                            .on_hover_text("该位置暂无克制数据，显示的是其他位置的数据");
+                    }
+                }
                 let old_pos = self.selected_enemy_pos.clone();
                 let pos_display = pos_cn(&self.selected_enemy_pos);
                 let pos_display = if pos_display.is_empty() { "选择位置" } else { pos_display };
-                egui::ComboBox::from_id_salt("enemy_pos_select")
-                    .selected_text(pos_display)
-                    .width(60.0)
-                    .show_ui(ui, |ui| {
-                        for pos in &["TOP", "JUNGLE", "MIDDLE", "BOTTOM", "UTILITY"] {
-                            ui.selectable_value(&mut self.selected_enemy_pos, pos.to_string(), pos_cn(pos));
-                        }
-                    });
+                if self.is_aram {
+                    ui.weak("大乱斗").on_hover_text("大乱斗不分位置，克制数据合并全部位置");
+                } else {
+                    egui::ComboBox::from_id_salt("enemy_pos_select")
+                        .selected_text(pos_display)
+                        .width(60.0)
+                        .show_ui(ui, |ui| {
+                            for pos in &["TOP", "JUNGLE", "MIDDLE", "BOTTOM", "UTILITY", ALL_POSITIONS] {
+                                ui.selectable_value(&mut self.selected_enemy_pos, pos.to_string(), pos_cn(pos));
+                            }
+                        })
+                        .response
+                        .on_hover_text("未分配位置时按英雄常用位置推测，可手动修改");
+                }
                 if self.selected_enemy_pos != old_pos {
                     pos_changed = true;
                 }
                 let order = if self.counter_sort_desc { "降序" } else { "升序" };
-                ui.label(format!("（{}个，{order}）", self.counter_data.len()));
+                let shown = self.counter_data.iter().filter(|r| self.counter_row_visible(r)).count();
+                ui.label(format!("（{shown}/{}个，{order}）", self.counter_data.len()));
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            let old_reverse = self.counter_reverse;
+            ui.selectable_value(&mut self.counter_reverse, false, "被对方克制");
+            ui.selectable_value(&mut self.counter_reverse, true, "克制对方");
+            if self.counter_reverse != old_reverse {
+                pos_changed = true;
             }
+            ui.label("最少场次：");
+            ui.add(
+                egui::DragValue::new(&mut self.prefs.min_games)
+                    .range(0..=100_000)
+                    .speed(50),
+            );
+            ui.label("表格行数：");
+            ui.add(egui::DragValue::new(&mut self.prefs.table_rows).range(3..=30));
+            ui.checkbox(&mut self.prefs.colorblind_palette, "色盲配色");
+            ui.checkbox(&mut self.prefs.favorites_only, "只看收藏");
         });
-        if pos_changed {
+        if pos_changed && !self.counter_champ_slug.is_empty() {
             let slug = self.counter_champ_slug.clone();
             let name = self.counter_champ_name.clone();
             let pos = self.selected_enemy_pos.clone();
             self.load_counter_data(&slug, &name, &pos);
         }
-        if !self.counter_error.is_empty() {
+        if self.opgg_cache.counters.is_empty() && !self.updating {
+            ui.horizontal_wrapped(|ui| {
+                ui.colored_label(
+                    egui::Color32::from_rgb(180, 120, 0),
+                    "本地还没有克制数据：请先全量更新（需要几分钟），或「导入数据」",
+                );
+                if ui.button("全量更新").clicked() {
+                    self.start_update(ctx);
+                }
+            });
+        } else if !self.counter_error.is_empty() {
             ui.colored_label(egui::Color32::from_rgb(180, 120, 0), &self.counter_error);
         }
+        self.ui_ai_pick(ui);
 
         self.sort_counter_data();
-        let mut ai_trigger: Option<(String, f64)> = None;
+        // (英雄名, slug, 胜率, 是否按住 Shift)
+        let mut ai_trigger: Option<(String, String, bool)> = None;
+
+        // 键盘选行：↑↓ 移动（首次按下从第一行开始），Enter 分析选中行，Shift+Enter 同 Shift+点击
+        let visible_rows = self.counter_data.iter().filter(|r| self.counter_row_visible(r)).count();
+        let mut scroll_to_selected = false;
+        self.counter_selected_row = self.counter_selected_row.filter(|&i| i < visible_rows);
+        if table_keys && visible_rows > 0 {
+            let (down, up, enter, shift) = ui.input_mut(|i| (
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::Enter),
+                i.modifiers.shift,
+            ));
+            if down || up {
+                self.counter_selected_row = Some(match (self.counter_selected_row, down) {
+                    (None, _) => 0,
+                    (Some(i), true) => (i + 1).min(visible_rows - 1),
+                    (Some(i), false) => i.saturating_sub(1),
+                });
+                scroll_to_selected = true;
+            }
+            if enter {
+                if let Some(row) = self.counter_selected_row
+                    .and_then(|i| self.counter_data.iter().filter(|r| self.counter_row_visible(r)).nth(i))
+                {
+                    ai_trigger = Some((row.name.clone(), row.key.clone(), shift));
+                }
+            }
+        }
 
         egui::ScrollArea::vertical()
             .id_salt("counter_scroll")
-            .max_height(row_h * 11.0)
+            .max_height(row_h * (self.prefs.counter_rows() + 1.0)) // 表头 + 数据行
             .auto_shrink(false)
             .show(ui, |ui| {
                 egui::Grid::new("counter_grid")
@@ -893,6 +4286,11 @@ impl App {
                         if ui.button("英雄").clicked() { header_click = Some("name"); }
                         if ui.button("克制率(%)").clicked() { header_click = Some("win_rate"); }
                         if ui.button("场次").clicked() { header_click = Some("games"); }
+                        if ui.button("相对优势").on_hover_text("对位胜率 - 该英雄整体胜率，正值才是真正的克制\n整体胜率暂不从 OP.GG 采集，只有导入的数据中带有时才显示").clicked() {
+                            header_click = Some("delta");
+                        }
+                        let merged = self.counter_data.iter().any(|r| !r.positions.is_empty());
+                        if merged { ui.label("位置"); }
                         if ui.button("收藏").clicked() { header_click = Some("fav"); }
                         ui.end_row();
 
@@ -907,28 +4305,104 @@ impl App {
                         }
 
                         let mut fav_toggle: Option<String> = None;
-                        for row in &self.counter_data {
+                        let mut open_url: Option<String> = None;
+                        let colorblind = self.prefs.colorblind_palette;
+                        let mut clicked_row = None;
+                        let override_base = self.override_base_key();
+                        let mut edit_override: Option<OverrideEdit> = None;
+                        for (row_idx, row) in self.counter_data.iter().filter(|r| self.counter_row_visible(r)).enumerate() {
+                            let selected = self.counter_selected_row == Some(row_idx);
                             let icon_id = self.slug_to_id.get(&row.key)
                                 .or_else(|| self.name_to_id.get(&row.name))
                                 .copied();
+                            let mut is_fav = self.counter_favorites.contains(&row.key);
+                            let tex = icon_id.and_then(|id| self.icon_textures.get(&id));
                             let clicked = ui.horizontal(|ui| {
-                                if let Some(tex) = icon_id.and_then(|id| self.icon_textures.get(&id)) {
+                                if let Some(tex) = tex {
                                     ui.image((tex.id(), egui::vec2(18.0, 18.0)));
                                 }
-                                ui.selectable_label(false, &row.name).clicked()
+                                // 收藏行用选中底色标识，不与胜率色阶冲突
+                                let name = if is_fav { format!("★ {}", row.name) } else { row.name.clone() };
+                                let name = if selected { format!("▶ {name}") } else { name };
+                                let resp = ui.selectable_label(is_fav, name)
+                                    .on_hover_ui(|ui| {
+                                        ui.horizontal(|ui| {
+                                            if let Some(tex) = tex {
+                                                ui.image((tex.id(), egui::vec2(40.0, 40.0)));
+                                            }
+                                            ui.vertical(|ui| {
+                                                let en = self.slug_to_alias.get(&row.key).unwrap_or(&row.key);
+                                                ui.strong(format!("{} / {en}", row.name));
+                                                ui.label(format!("胜率：{:.2}%", row.win_rate));
+                                                ui.label(format!("样本：{} 场", row.games));
+                                                let pos = pos_cn(&self.selected_enemy_pos);
+                                                ui.label(format!("位置：{}", if pos.is_empty() { "不限" } else { pos }));
+                                            });
+                                        });
+                                    });
+                                resp.context_menu(|ui| {
+                                    if ui.button("在 OP.GG 查看出装").clicked() {
+                                        let pos = lcu_pos_to_opgg(&self.selected_enemy_pos);
+                                        open_url = Some(opgg::champion_build_url(&row.key, pos, self.queue_mode()));
+                                        ui.close_menu();
+                                    }
+                                    if let Some(base) = &override_base {
+                                        if ui.button("自定义胜率…").clicked() {
+                                            edit_override = Some(OverrideEdit {
+                                                key: format!("{base}:{}", row.key),
+                                                name: row.name.clone(),
+                                                win_rate: row.win_rate,
+                                                games: row.games,
+                                            });
+                                            ui.close_menu();
+                                        }
+                                    }
+                                });
+                                if selected && scroll_to_selected {
+                                    resp.scroll_to_me(None);
+                                }
+                                resp.clicked()
                             }).inner;
                             if clicked {
-                                ai_trigger = Some((row.name.clone(), row.win_rate));
+                                clicked_row = Some(row_idx);
+                                let shift = ui.input(|i| i.modifiers.shift);
+                                ai_trigger = Some((row.name.clone(), row.key.clone(), shift));
                             }
-                            ui.label(format!("{:.2}%", row.win_rate));
+                            let margin = win_rate_margin(row.win_rate, row.games);
+                            let (wr_color, mark) = confidence_shade(win_rate_color(row.win_rate, colorblind), margin);
+                            let badge = if row.overridden { " ✎" } else { "" };
+                            ui.colored_label(wr_color, format!("{:.2}%{mark}{badge}", row.win_rate))
+                                .on_hover_text(format!(
+                                    "95% 置信区间 ±{margin:.1}%（{} 场）\n样本越少区间越宽，颜色越淡；带 * 表示参考价值低{}",
+                                    row.games,
+                                    if row.overridden { "\n✎ 手动数据（overrides.json），优先于 OP.GG" } else { "" },
+                                ));
                             ui.label(format!("{}", row.games));
-                            let mut is_fav = self.counter_favorites.contains(&row.key);
+                            match row.delta {
+                                Some(d) => { ui.colored_label(delta_color(d, colorblind), format!("{d:+.2}")); }
+                                None => { ui.weak("-"); }
+                            }
+                            if merged {
+                                let pos: Vec<&str> = row.positions.iter().map(|p| pos_cn(p)).collect();
+                                ui.weak(pos.join("/"));
+                            }
                             if ui.checkbox(&mut is_fav, "").clicked() {
                                 fav_toggle = Some(row.key.clone());
                             }
                             ui.end_row();
                         }
 
+                        if clicked_row.is_some() {
+                            self.counter_selected_row = clicked_row;
+                        }
+                        if edit_override.is_some() {
+                            self.override_edit = edit_override;
+                        }
+                        if let Some(url) = open_url {
+                            if let Err(e) = win32::open_url(&url) {
+                                self.export_status = e;
+                            }
+                        }
                         if let Some(key) = fav_toggle {
                             if !self.counter_favorites.remove(&key) {
                                 self.counter_favorites.insert(key);
@@ -938,16 +4412,66 @@ impl App {
                     });
             });
 
-        if let Some((counter_name, win_rate)) = ai_trigger {
-            let enemy_name = self.counter_champ_name.clone();
-            let opgg_pos = lcu_pos_to_opgg(&self.my_pos).to_string();
-            self.start_ai_analysis(&counter_name, &enemy_name, &opgg_pos, win_rate, ctx);
+        self.ui_override_editor(ui);
+
+        if let Some((row_name, row_key, shift)) = ai_trigger {
+            self.clear_ai_batch();
+            // 默认用我的英雄对当前敌方分析；Shift+点击（或未选英雄）时以点击的英雄为我方
+            let pick = self.ai_pick_slug().to_string();
+            if shift || pick.is_empty() || pick == self.counter_champ_slug {
+                self.analyze_vs_counter_champ(row_name, row_key, ctx);
+            } else {
+                self.analyze_pick_vs_counter_champ(ctx);
+            }
         }
 
         ui.separator();
 
+        // === ban / 预选意向、英雄池威胁、敌方威胁 ===
+        self.ui_intents(ui);
+        self.ui_pool_threats(ui);
+        self.ui_enemy_threats(ui);
+        self.ui_pool_heatmap(ui);
+
+        // === 对线笔记 ===
+        self.ui_matchup_note(ui);
+
         // === AI 分析面板（占满剩余空间）===
-        ui.horizontal(|ui| {
+        if self.ai_detached {
+            ui.horizontal(|ui| {
+                ui.weak("AI 分析已在独立窗口显示");
+                if ui.small_button("收回").clicked() {
+                    self.ai_detached = false;
+                }
+            });
+        } else {
+            self.ui_ai_panel(ui, ctx);
+        }
+    }
+
+    /// 独立的 AI 分析窗口（随主窗口每帧绘制，流式输出同步刷新），关闭即收回主窗口
+    fn show_ai_viewport(&mut self, ctx: &egui::Context) {
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("ai_panel"),
+            egui::ViewportBuilder::default()
+                .with_title("AI 分析")
+                .with_inner_size([460.0, 640.0])
+                .with_min_inner_size([300.0, 200.0]),
+            |ctx, _class| {
+                egui::CentralPanel::default().show(ctx, |ui| self.ui_ai_panel(ui, ctx));
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.ai_detached = false;
+                }
+            },
+        );
+    }
+
+    /// AI 分析面板：主窗口内或独立窗口中显示，状态共用
+    fn ui_ai_panel(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.horizontal_wrapped(|ui| {
+            if let Some(info) = &self.ai_analysis_info {
+                self.ui_matchup_portraits(ui, info);
+            }
             ui.label(&self.ai_title);
             if self.ai_engines.len() > 1 {
                 let old_engine_idx = self.ai_engine_idx;
@@ -964,6 +4488,7 @@ impl App {
                 // 切换引擎时重置模型索引
                 if self.ai_engine_idx != old_engine_idx {
                     self.ai_model_idx = 0;
+                    self.ai_probe_status.clear();
                 }
             } else if self.ai_engines.len() == 1 {
                 ui.weak(&self.ai_engines[0].name);
@@ -985,14 +4510,87 @@ impl App {
                         });
                 }
             }
+            // 提示词模板（配置了自定义模板时显示）
+            if !self.prompt_templates.is_empty() {
+                let current = self.current_prompt_template().map(|t| t.name.as_str()).unwrap_or("默认提示词");
+                egui::ComboBox::from_id_salt("ai_template_select")
+                    .selected_text(current)
+                    .width(100.0)
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.ai_template_idx, 0, "默认提示词");
+                        for (i, t) in self.prompt_templates.iter().enumerate() {
+                            ui.selectable_value(&mut self.ai_template_idx, i + 1, &t.name);
+                        }
+                    });
+            }
             if self.ai_loading {
                 ui.spinner();
             }
+            let can_batch = !self.ai_loading && !self.ai_pick_slug().is_empty() && !self.draft.enemies.is_empty();
+            if ui.add_enabled(can_batch, egui::Button::new("全部对位").small())
+                .on_hover_text("依次分析我的英雄对每个敌方英雄（已缓存的不重复请求）")
+                .clicked()
+            {
+                self.start_ai_batch(ctx);
+            }
+            if !self.ai_batch.is_empty() {
+                let done = self.ai_batch.iter().filter(|i| i.done).count();
+                let total = self.ai_batch.len();
+                if done < total {
+                    ui.weak(format!("分析 {}/{total}", done + 1));
+                } else if ui.small_button("✕").on_hover_text("关闭批量结果").clicked() {
+                    self.clear_ai_batch();
+                }
+            }
+            let can_save = !self.ai_loading && !self.ai_text.is_empty() && self.ai_analysis_info.is_some();
+            if ui.add_enabled(can_save, egui::Button::new("保存").small())
+                .on_hover_text("保存为 Markdown 到 analyses 目录")
+                .clicked()
+            {
+                if let Some(info) = &self.ai_analysis_info {
+                    self.ai_save_status = match save_ai_analysis(info, strip_debug_header(&self.ai_text)) {
+                        Ok(p) => format!("已保存：{}", p.display()),
+                        Err(e) => e,
+                    };
+                }
+            }
+            let has_engine = !self.ai_engines.is_empty();
+            if ui.add_enabled(has_engine && !self.ai_probing, egui::Button::new("测试连接").small())
+                .on_hover_text("发送一个极短的请求，检查 API Key 和模型名")
+                .clicked()
+            {
+                self.start_ai_test(ctx);
+            }
+            if ui.add_enabled(has_engine && !self.ai_probing, egui::Button::new("⟳").small())
+                .on_hover_text("从接口 /models 获取可用模型（部分接口不支持）")
+                .clicked()
+            {
+                self.start_fetch_models(ctx);
+            }
             let chat_label = if self.ai_chat_visible { "对话 ▲" } else { "对话 ▼" };
             if ui.small_button(chat_label).clicked() {
                 self.ai_chat_visible = !self.ai_chat_visible;
             }
+            let (detach_label, detach_hint) = if self.ai_detached {
+                ("收回", "放回主窗口")
+            } else {
+                ("弹出", "在可移动、可调整大小的独立窗口中显示")
+            };
+            if ui.small_button(detach_label).on_hover_text(detach_hint).clicked() {
+                self.ai_detached = !self.ai_detached;
+            }
         });
+        if !self.ai_save_status.is_empty() {
+            ui.weak(&self.ai_save_status);
+        }
+        if !self.ai_probe_status.is_empty() {
+            let color = if self.ai_probe_status.starts_with('✘') || self.ai_probe_status.contains("失败") {
+                egui::Color32::from_rgb(220, 60, 60)
+            } else {
+                ui.visuals().weak_text_color()
+            };
+            ui.colored_label(color, &self.ai_probe_status);
+        }
         // 对话输入框
         let mut send_chat = false;
         if self.ai_chat_visible {
@@ -1013,26 +4611,231 @@ impl App {
         if send_chat && !self.ai_chat_input.trim().is_empty() && !self.ai_loading {
             let prompt = self.ai_chat_input.clone();
             self.ai_chat_input.clear();
+            self.clear_ai_batch();
             self.start_ai_chat(&prompt, ctx);
         }
         egui::ScrollArea::vertical()
             .id_salt("ai_scroll")
             .auto_shrink(false)
+            // 鼠标拖动用于选中文字，滚动用滚轮/滚动条
+            .drag_to_scroll(false)
             .show(ui, |ui| {
                 ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Wrap);
+                if !self.ai_batch.is_empty() {
+                    self.ui_ai_batch(ui);
+                    return;
+                }
                 if self.ai_text.is_empty() {
-                    ui.label("点击上方克制英雄触发 AI 分析");
+                    if self.ai_engines.is_empty() {
+                        ui.colored_label(egui::Color32::from_rgb(220, 150, 40), "请在 config.toml 配置 AI 引擎（填写 api_key 后重启程序）");
+                        if ui.button("打开配置文件").clicked() {
+                            if let Err(e) = open_config_file() {
+                                self.ai_probe_status = format!("打开失败：{e}");
+                            }
+                        }
+                    } else {
+                        ui.label("点击上方克制英雄触发 AI 分析（Shift+点击以该英雄为我方）");
+                    }
+                } else if self.prefs.compact {
+                    ai_text_label(ui, strip_debug_header(&self.ai_text));
                 } else {
-                    ui.label(&self.ai_text);
+                    ai_text_label(ui, &self.ai_text);
+                }
+                // 没有 AI 输出（或未配置 AI，只有错误提示）时给出内置提示
+                if self.ai_text.is_empty() || self.ai_engines.is_empty() {
+                    self.ui_builtin_tip(ui);
+                }
+                if let Some(u) = self.ai_usage {
+                    ui.weak(format!(
+                        "本次消耗 {} tokens（输入 {} / 输出 {}）",
+                        u.prompt_tokens + u.completion_tokens,
+                        u.prompt_tokens,
+                        u.completion_tokens,
+                    ));
+                }
+            });
+    }
+
+    /// AI 面板标题的对位头像：我方 vs 敌方，缺图标时显示英雄名
+    fn ui_matchup_portraits(&self, ui: &mut egui::Ui, info: &AiAnalysisInfo) {
+        let portrait = |ui: &mut egui::Ui, slug: &str, name: &str| {
+            let tex = self.slug_to_id.get(slug)
+                .or_else(|| self.name_to_id.get(name))
+                .and_then(|id| self.icon_textures.get(id));
+            match tex {
+                Some(tex) => { ui.image((tex.id(), egui::vec2(28.0, 28.0))).on_hover_text(name); }
+                None => { ui.strong(name); }
+            }
+        };
+        portrait(ui, &info.my_slug, &info.my_name);
+        ui.weak("vs");
+        portrait(ui, &info.enemy_slug, &info.enemy_name);
+    }
+
+    /// 批量分析结果：每个对位一个可折叠分区，进行中的一项显示实时输出
+    fn ui_ai_batch(&self, ui: &mut egui::Ui) {
+        for (i, item) in self.ai_batch.iter().enumerate() {
+            let running = self.ai_batch_running == Some(i);
+            let status = match (item.done, item.cached, running) {
+                (true, true, _) => "（缓存）",
+                (true, false, _) => "",
+                (false, _, true) => "（分析中…）",
+                (false, _, false) => "（等待）",
+            };
+            let title = format!("{} vs {}{status}", item.info.my_name, item.info.enemy_name);
+            egui::CollapsingHeader::new(title)
+                .id_salt(("ai_batch", i))
+                .default_open(true)
+                .show(ui, |ui| {
+                    let text = if running { &self.ai_text } else { &item.text };
+                    if self.prefs.compact {
+                        ai_text_label(ui, strip_debug_header(text));
+                    } else {
+                        ai_text_label(ui, text);
+                    }
+                });
+        }
+    }
+
+    /// 资源刷新提醒横幅
+    fn ui_objective_banners(&self, ui: &mut egui::Ui) {
+        if !self.prefs.objective_reminders {
+            return;
+        }
+        let due = live::due_reminders(
+            self.live.game_time,
+            &self.prefs.reminder_objectives,
+            self.prefs.reminder_lead_secs as f64,
+        );
+        for (objective, until) in due {
+            let text = if until > 0.0 {
+                let secs = until.ceil() as i64;
+                format!("⚠ 注意{}刷新（{}:{:02} 后）", objective.name(), secs / 60, secs % 60)
+            } else {
+                format!("⚠ {}已刷新", objective.name())
+            };
+            egui::Frame::group(ui.style())
+                .fill(egui::Color32::from_rgb(90, 70, 20))
+                .show(ui, |ui| {
+                    ui.set_width(ui.available_width());
+                    ui.label(egui::RichText::new(text).strong().color(egui::Color32::from_rgb(255, 220, 120)));
+                });
+        }
+    }
+
+    /// 资源刷新提醒设置
+    fn ui_reminder_settings(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("刷新提醒")
+            .id_salt("objective_reminders")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.checkbox(&mut self.prefs.objective_reminders, "启用")
+                        .on_hover_text("按固定时间表提醒，不跟踪实际击杀，仅供参考");
+                    ui.label("提前");
+                    ui.add(egui::DragValue::new(&mut self.prefs.reminder_lead_secs).range(5..=120).suffix(" 秒"));
+                });
+                ui.add_enabled_ui(self.prefs.objective_reminders, |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        for objective in live::Objective::ALL {
+                            let mut on = self.prefs.reminder_objectives.contains(&objective);
+                            if ui.checkbox(&mut on, objective.name()).changed() {
+                                if on {
+                                    self.prefs.reminder_objectives.push(objective);
+                                } else {
+                                    self.prefs.reminder_objectives.retain(|o| *o != objective);
+                                }
+                            }
+                        }
+                    });
+                });
+            });
+    }
+
+    fn ui_live_game(&mut self, ui: &mut egui::Ui) {
+        self.ui_objective_banners(ui);
+        let t = self.live.game_time as i64;
+        egui::CollapsingHeader::new(format!("对局实况 {}:{:02}", t / 60, t % 60))
+            .id_salt("live_game")
+            .default_open(true)
+            .show(ui, |ui| {
+                for p in self.live.players.iter().filter(|p| p.is_enemy) {
+                    ui.horizontal(|ui| {
+                        let icon_id = self.name_to_id.get(&p.champion_name)
+                            .or_else(|| self.slug_to_id.get(&to_opgg_slug("", &p.champion_name)));
+                        if let Some(tex) = icon_id.and_then(|id| self.icon_textures.get(id)) {
+                            ui.image((tex.id(), egui::vec2(20.0, 20.0)));
+                        }
+                        ui.label(format!(
+                            "{} Lv{}  {}/{}/{}  {}刀  装备≈{}g",
+                            p.champion_name, p.level, p.kills, p.deaths, p.assists,
+                            p.creep_score, p.item_gold,
+                        ))
+                        .on_hover_text(if self.prefs.privacy { "" } else { &p.name });
+                    });
+                    if !p.items.is_empty() {
+                        ui.weak(p.items.join("、"));
+                    }
                 }
             });
+        self.ui_reminder_settings(ui);
+    }
+
+    /// 对局记录中的英雄显示名（OP.GG 未给中文名时按 id / key 回退）
+    fn history_champ_name(&self, entry: &MatchEntry) -> String {
+        if !entry.champion_name.is_empty() {
+            entry.champion_name.clone()
+        } else if entry.champion_id > 0 {
+            self.champ_names.get(&entry.champion_id)
+                .cloned()
+                .unwrap_or_else(|| entry.champion_key.clone())
+        } else {
+            entry.champion_key.clone()
+        }
     }
 
-    fn ui_match_history(&self, ui: &mut egui::Ui) {
-        ui.heading(&self.match_history_name);
+    fn ui_match_history(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
+        ui.horizontal(|ui| {
+            ui.heading(&self.match_history_name);
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if self.match_history_loading {
+                    ui.spinner();
+                } else if ui.small_button("🔄").on_hover_text("刷新对局记录").clicked() {
+                    let query = self.match_history_query.clone();
+                    self.start_fetch_match_history(query, true, ctx);
+                }
+                let q = &self.match_history_query;
+                if !q.game_name.is_empty()
+                    && ui.small_button("🌐").on_hover_text("在浏览器打开 OP.GG 主页").clicked()
+                {
+                    let url = opgg::match_history_url(&q.region, &q.game_name, &q.tag_line);
+                    if let Err(e) = win32::open_url(&url) {
+                        self.match_history_name = format!("{}\n{e}", self.match_history_name);
+                    }
+                }
+                if self.manual_search_active && ui.small_button("返回").on_hover_text("返回对局玩家").clicked() {
+                    self.manual_search_active = false;
+                }
+            });
+        });
+        if let Some(rank) = &self.match_history_rank {
+            ui.label(format!("单双排：{}", rank.text()));
+        }
+        if let Some(profile) = history_profile_text(&self.match_history, &self.match_detail_cache) {
+            ui.weak(profile).on_hover_text("按已加载的对局统计：平均时长、最常玩的队列和位置");
+        }
+        // 对局中的玩家（有 puuid）额外显示 LCU 熟练度
+        let mate_puuid = self.draft.selected_teammate_idx
+            .filter(|_| !self.manual_search_active && self.connected)
+            .and_then(|i| self.draft.teammates.get(i))
+            .map(|m| m.puuid.clone())
+            .filter(|p| !p.is_empty());
+        if let Some(puuid) = mate_puuid {
+            self.ui_mastery_row(ui, "常用英雄：", &puuid, ctx);
+        }
         ui.separator();
 
-        if self.match_history_loading {
+        if self.match_history_loading && self.match_history.is_empty() {
             ui.spinner();
             ui.label("加载中…");
             return;
@@ -1043,27 +4846,63 @@ impl App {
             return;
         }
 
-        // 统计胜率
-        let wins = self.match_history.iter().filter(|e| e.win).count();
-        let total = self.match_history.len();
+        // 按英雄筛选（按场次排序）
+        let mut champ_counts: Vec<(String, usize)> = Vec::new();
+        for e in &self.match_history {
+            let name = self.history_champ_name(e);
+            match champ_counts.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, c)) => *c += 1,
+                None => champ_counts.push((name, 1)),
+            }
+        }
+        champ_counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+        if self.history_champ_filter.as_ref().is_some_and(|f| !champ_counts.iter().any(|(n, _)| n == f)) {
+            self.history_champ_filter = None;
+        }
+        let filtered: Vec<&MatchEntry> = self
+            .match_history
+            .iter()
+            .filter(|e| self.history_champ_filter.as_ref().is_none_or(|f| *f == self.history_champ_name(e)))
+            .collect();
+
+        // 统计胜率（筛选后的对局）
+        let wins = filtered.iter().filter(|e| e.win).count();
+        let total = filtered.len();
         let wr = if total > 0 { wins as f64 / total as f64 * 100.0 } else { 0.0 };
-        ui.label(format!("近{total}场：{wins}胜{}负 ({wr:.0}%)", total - wins));
+        ui.horizontal(|ui| {
+            ui.label(format!("近{total}场：{wins}胜{}负 ({wr:.0}%)", total - wins));
+            if let Some(w) = weighted_win_rate(filtered.iter().copied(), self.config.form_half_life_hours) {
+                ui.weak(format!("加权 {w:.0}%")).on_hover_text(format!(
+                    "近期状态：每过 {} 小时权重减半，越近的对局影响越大",
+                    self.config.form_half_life_hours,
+                ));
+            }
+            let mut filter = self.history_champ_filter.clone();
+            egui::ComboBox::from_id_salt("history_champ_filter")
+                .selected_text(filter.as_deref().unwrap_or("全部"))
+                .width(80.0)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut filter, None, "全部");
+                    for (name, count) in &champ_counts {
+                        ui.selectable_value(&mut filter, Some(name.clone()), format!("{name} ({count})"));
+                    }
+                });
+            if filter != self.history_champ_filter {
+                self.history_champ_filter = filter;
+                self.expanded_match = None;
+            }
+        });
+        history_sparklines(ui, &filtered, self.prefs.colorblind_palette);
         ui.separator();
 
+        let mut toggled: Option<MatchEntry> = None;
+        let mut retry: Option<MatchEntry> = None;
         egui::ScrollArea::vertical()
             .id_salt("history_scroll")
             .auto_shrink(false)
             .show(ui, |ui| {
-                for entry in &self.match_history {
-                    let champ_name = if !entry.champion_name.is_empty() {
-                        entry.champion_name.clone()
-                    } else if entry.champion_id > 0 {
-                        self.champ_names.get(&entry.champion_id)
-                            .cloned()
-                            .unwrap_or_else(|| entry.champion_key.clone())
-                    } else {
-                        entry.champion_key.clone()
-                    };
+                for entry in filtered {
+                    let champ_name = self.history_champ_name(entry);
                     let result_text = if entry.win { "胜" } else { "败" };
                     let result_color = if entry.win {
                         egui::Color32::from_rgb(60, 180, 80)
@@ -1083,7 +4922,7 @@ impl App {
                         queue_name(entry.queue_id)
                     };
 
-                    ui.horizontal(|ui| {
+                    let row = ui.horizontal(|ui| {
                         let icon_id = if entry.champion_id > 0 {
                             Some(entry.champion_id)
                         } else {
@@ -1116,8 +4955,239 @@ impl App {
                             });
                         });
                     });
+                    let row = row.response.interact(egui::Sense::click());
+                    if row.on_hover_text("点击查看出装").clicked() {
+                        toggled = Some(entry.clone());
+                    }
+                    let expanded = !entry.game_id.is_empty() && self.expanded_match.as_ref() == Some(&entry.game_id);
+                    if expanded && self.ui_match_detail(ui, &entry.game_id) {
+                        retry = Some(entry.clone());
+                    }
                     ui.separator();
                 }
             });
+
+        if let Some(entry) = toggled {
+            if self.expanded_match.as_ref() == Some(&entry.game_id) {
+                self.expanded_match = None;
+            } else {
+                self.expanded_match = Some(entry.game_id.clone());
+                self.start_fetch_match_detail(&entry, ctx);
+            }
+        }
+        if let Some(entry) = retry {
+            self.start_fetch_match_detail(&entry, ctx);
+        }
+    }
+
+    /// 展开的单局出装；获取失败时点击「重试」返回 true
+    fn ui_match_detail(&self, ui: &mut egui::Ui, game_id: &str) -> bool {
+        let mut retry = false;
+        match self.match_detail_cache.get(game_id) {
+            None => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.weak("加载出装…");
+                });
+            }
+            Some(Err(e)) => {
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(egui::Color32::from_rgb(180, 120, 0), e);
+                    retry = ui.small_button("重试").clicked();
+                });
+            }
+            Some(Ok(detail)) => {
+                let name_of = |names: &HashMap<i64, String>, id: &i64| {
+                    names.get(id).cloned().unwrap_or_else(|| format!("#{id}"))
+                };
+                if !detail.spells.is_empty() {
+                    let spells: Vec<String> = detail.spells.iter().map(|id| name_of(&self.spell_names, id)).collect();
+                    ui.weak(format!("技能：{}", spells.join(" / ")));
+                }
+                if detail.items.is_empty() {
+                    ui.weak("无出装数据");
+                } else {
+                    // 有图标的显示图标（悬停看名称），图标未加载或获取失败时显示名称
+                    ui.horizontal_wrapped(|ui| {
+                        ui.weak("出装：");
+                        for id in &detail.items {
+                            let name = name_of(&self.item_names, id);
+                            match self.item_textures.get(id) {
+                                Some(tex) => {
+                                    ui.image((tex.id(), egui::vec2(22.0, 22.0))).on_hover_text(name);
+                                }
+                                None => {
+                                    ui.weak(name);
+                                }
+                            }
+                        }
+                    });
+                }
+            }
+        }
+        retry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enemy(id: i64, slug: &str) -> EnemyInfo {
+        EnemyInfo { champion_id: id, name: slug.into(), slug: slug.into(), pos: "MIDDLE".into() }
+    }
+
+    fn teammate(name: &str) -> TeamMateInfo {
+        TeamMateInfo {
+            summoner_name: name.into(),
+            tag_line: "0001".into(),
+            puuid: String::new(),
+            account_id: 0,
+            champion_id: 0,
+            champion_name: String::new(),
+            position: String::new(),
+            rank_tier: String::new(),
+            rank_division: String::new(),
+            rank_lp: 0,
+            is_ally: true,
+        }
+    }
+
+    fn lcu_state(session_id: i64, enemies: Vec<EnemyInfo>, teammates: Vec<TeamMateInfo>, my_champ: &str) -> LcuState {
+        LcuState {
+            connected: true,
+            error: String::new(),
+            error_kind: None,
+            info: String::new(),
+            enemies,
+            teammates,
+            my_pos: "MIDDLE".into(),
+            lane_enemy_id: None,
+            my_champ_slug: my_champ.into(),
+            champion_lang: "zh_CN".into(),
+            game_version: String::new(),
+            session_id,
+            is_aram: false,
+            queue_id: 420,
+            bench: vec![],
+            intents: vec![],
+            timer: None,
+            champion_data: None,
+            auth: None,
+        }
+    }
+
+    /// 第一局选完后手动选中了敌方 / 队友、指定了位置
+    fn first_game(lock_data: bool) -> Draft {
+        let mut draft = Draft::default();
+        let mut state = lcu_state(1, vec![enemy(238, "zed"), enemy(64, "leesin")], vec![teammate("a"), teammate("b")], "ahri");
+        state.lane_enemy_id = Some(238);
+        let change = draft.apply_lcu(&mut state, lock_data, true);
+        assert!(change.enemies && change.teammates);
+        draft.selected_enemy_idx = Some(1);
+        draft.selected_teammate_idx = Some(0);
+        draft.pos_overrides.insert(64, "TOP".into());
+        draft
+    }
+
+    #[test]
+    fn new_session_resets_draft() {
+        for lock_data in [false, true] {
+            let mut draft = first_game(lock_data);
+            // 新一局刚进选人，敌方和队友都还没有数据
+            let change = draft.apply_lcu(&mut lcu_state(2, vec![], vec![], ""), lock_data, true);
+            assert!(!change.teammates);
+            assert!(draft.enemies.is_empty(), "lock_data={lock_data}");
+            assert!(draft.teammates.is_empty());
+            assert_eq!(draft.selected_enemy_idx, None);
+            assert_eq!(draft.selected_teammate_idx, None);
+            assert_eq!(draft.lane_enemy_id, None);
+            assert!(draft.pos_overrides.is_empty());
+            assert!(draft.my_champ_slug.is_empty());
+            assert_eq!(draft.last_session_id, 2);
+        }
+    }
+
+    #[test]
+    fn lock_data_keeps_draft_in_lobby() {
+        let mut draft = first_game(true);
+        // 回到大厅：session_id 为 0，不算新局，锁定时保留上一局
+        let mut lobby = lcu_state(0, vec![], vec![], "");
+        lobby.info = "不在选人阶段".into();
+        draft.apply_lcu(&mut lobby, true, true);
+        assert_eq!(draft.enemies.len(), 2);
+        assert_eq!(draft.teammates.len(), 2);
+        assert_eq!(draft.selected_enemy_idx, Some(1));
+        assert_eq!(draft.lane_enemy_id, Some(238));
+        assert_eq!(draft.my_champ_slug, "ahri");
+
+        // 不锁定时离开选人就清掉我的英雄（敌方在有提示信息时保留）
+        let mut draft = first_game(false);
+        draft.apply_lcu(&mut lobby, false, true);
+        assert!(draft.my_champ_slug.is_empty());
+        assert_eq!(draft.lane_enemy_id, None);
+    }
+
+    #[test]
+    fn same_session_keeps_selection() {
+        let mut draft = first_game(false);
+        let change = draft.apply_lcu(&mut lcu_state(1, vec![enemy(238, "zed"), enemy(64, "leesin")], vec![], "ahri"), false, true);
+        assert!(change.enemies && !change.teammates);
+        assert_eq!(draft.selected_enemy_idx, Some(1));
+        assert_eq!(draft.teammates.len(), 2);
+        assert_eq!(draft.pos_overrides.get(&64).map(String::as_str), Some("TOP"));
+    }
+
+    #[test]
+    fn new_session_without_clear_only_drops_overrides() {
+        let mut draft = first_game(false);
+        draft.apply_lcu(&mut lcu_state(2, vec![enemy(1, "annie")], vec![], ""), false, false);
+        assert!(draft.pos_overrides.is_empty());
+        assert_eq!(draft.selected_teammate_idx, Some(0));
+        assert_eq!(draft.teammates.len(), 2);
+    }
+
+    #[test]
+    fn enemy_threat_score_direction() {
+        let mut cache = OpggCache::default();
+        // 劫打阿狸 55%，阿狸打安妮 58%
+        cache.counters.insert("ahri:MID".into(), vec![CounterEntry { key: "zed".into(), win_rate: 55.0, games: 900 }]);
+        cache.counters.insert("annie:MID".into(), vec![CounterEntry { key: "ahri".into(), win_rate: 58.0, games: 400 }]);
+        let picks = vec!["ahri".to_string()];
+
+        // 劫克制阿狸：阿狸胜率 45%，计 5 分
+        let worst = worst_matchup(&cache, &opgg::CounterOverrides::new(), &picks, "zed", "MIDDLE", 100);
+        assert_eq!(worst, Some(("ahri", 45.0)));
+        assert_eq!(counter_threat_points(worst.map(|(_, wr)| wr)), 5.0);
+
+        // 阿狸克制安妮：不算威胁
+        let worst = worst_matchup(&cache, &opgg::CounterOverrides::new(), &picks, "annie", "MIDDLE", 100);
+        assert_eq!(worst, Some(("ahri", 58.0)));
+        assert_eq!(counter_threat_points(worst.map(|(_, wr)| wr)), 0.0);
+
+        // 场次不足时不计入
+        assert_eq!(worst_matchup(&cache, &opgg::CounterOverrides::new(), &picks, "annie", "MIDDLE", 500), None);
+    }
+
+    #[test]
+    fn heatmap_rows_my_perspective() {
+        let mut cache = OpggCache::default();
+        // 劫打阿狸 55%，劫打安妮 42%
+        cache.counters.insert("ahri:MID".into(), vec![CounterEntry { key: "zed".into(), win_rate: 55.0, games: 900 }]);
+        cache.counters.insert("annie:MID".into(), vec![CounterEntry { key: "zed".into(), win_rate: 42.0, games: 300 }]);
+        let picks: HashSet<String> = ["ahri".to_string(), "annie".to_string(), "lux".to_string()].into();
+
+        let rows = heatmap_rows(&cache, &opgg::CounterOverrides::new(), &picks, &["zed"], "MIDDLE");
+        let cells: Vec<(&str, Option<(f64, i64)>)> = rows.iter().map(|(p, c, _)| (p.as_str(), c[0])).collect();
+        // 安妮打劫 58% 排第一，无数据的排最后
+        assert_eq!(cells, [("annie", Some((58.0, 300))), ("ahri", Some((45.0, 900))), ("lux", None)]);
+    }
+
+    #[test]
+    fn riot_id_is_trimmed() {
+        assert_eq!(parse_riot_id("Faker # KR1 "), Some(("Faker".into(), "KR1".into())));
+        assert_eq!(parse_riot_id("Faker#"), None);
+        assert_eq!(parse_riot_id(" #KR1"), None);
+        assert_eq!(parse_riot_id("Faker"), None);
     }
 }
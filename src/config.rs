@@ -1,5 +1,8 @@
+use crate::types::ClientVariant;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// 单个 AI 引擎配置
 #[derive(Debug, Deserialize, Clone)]
@@ -14,6 +17,27 @@ pub struct AiEngine {
     /// 多模型列表
     #[serde(default)]
     pub models: Vec<String>,
+    /// 最大输出 token 数
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    /// 采样温度（不填则不发送，使用接口默认值）
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// token 上限的参数名（部分接口只认 max_tokens）
+    #[serde(default = "default_token_param_name")]
+    pub token_param_name: String,
+    /// 全局 proxy_url，由 get_engines 填入（不从引擎配置读取）
+    #[serde(skip)]
+    pub proxy_url: String,
+    /// 请求流式 token 用量（stream_options.include_usage），不支持的接口可关闭
+    #[serde(default = "default_true")]
+    pub stream_usage: bool,
+    /// 主模型繁忙（429/5xx）时依次尝试的备用模型
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    /// 额外请求头（网关鉴权、组织路由等），值须为 ASCII
+    #[serde(default)]
+    pub extra_headers: HashMap<String, String>,
 }
 
 impl AiEngine {
@@ -29,10 +53,32 @@ impl AiEngine {
     }
 }
 
+/// AI 分析提示词模板，可用占位符 {my_champ} {enemy_champ} {position} {win_rate}
+#[derive(Debug, Deserialize, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub prompt: String,
+    /// 为空时使用内置的 system prompt
+    #[serde(default)]
+    pub system_prompt: String,
+}
+
 fn default_api_url() -> String {
     "https://api.openai.com/v1/chat/completions".to_string()
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_tokens() -> u32 {
+    4096
+}
+
+fn default_token_param_name() -> String {
+    "max_completion_tokens".to_string()
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     /// 兼容旧配置
@@ -44,6 +90,72 @@ pub struct AppConfig {
     pub lockfile_dir: String,
     #[serde(default = "default_region")]
     pub region: String,
+    /// 本地 OP.GG 数据超过多少天视为过期（0 表示只在无数据时提示）
+    #[serde(default = "default_auto_update_days")]
+    pub auto_update_days: u32,
+    /// 数据过期时启动后自动全量更新
+    #[serde(default)]
+    pub auto_update: bool,
+    /// 外部请求（OP.GG / AI）代理，如 http://127.0.0.1:7890 或 socks5://127.0.0.1:1080
+    #[serde(default)]
+    pub proxy_url: String,
+    /// OP.GG 页面语言路径（如 zh-cn、ja，留空为英文），影响英雄名和页面结构
+    #[serde(default = "default_opgg_locale")]
+    pub opgg_locale: String,
+    /// OP.GG 英雄统计数据地区（global / kr / na 等，与战绩查询的 region 无关）
+    #[serde(default = "default_opgg_region")]
+    pub opgg_region: String,
+    /// 客户端版本（Global / Tencent），决定 lockfile 查找、英雄名语言和 OP.GG 页面语言的优先顺序
+    #[serde(default)]
+    pub client_variant: ClientVariant,
+    /// LCU 请求超时（秒，最小 1），机器较慢、图标加载失败时可调大
+    #[serde(default = "default_lcu_timeout_secs")]
+    pub lcu_timeout_secs: u64,
+    /// LCU 轮询间隔（毫秒，最小 300），出错时会自动放慢
+    #[serde(default = "default_lcu_poll_ms")]
+    pub lcu_poll_ms: u64,
+    /// 显示/隐藏窗口的全局快捷键，如 "Ctrl+Alt+L"（为空则不注册，仅 Windows）
+    #[serde(default = "default_hotkey")]
+    pub hotkey: String,
+    /// 选人时后台预取队友战绩，近期胜率过低时标红提醒（会额外请求 OP.GG，默认关闭）
+    #[serde(default)]
+    pub dodge_warning: bool,
+    /// 统计最近多少场
+    #[serde(default = "default_dodge_games")]
+    pub dodge_games: usize,
+    /// 胜率低于此值（%）时提醒
+    #[serde(default = "default_dodge_win_rate")]
+    pub dodge_win_rate: f64,
+    /// 近期状态加权的半衰期（小时）：越近的对局权重越高，用于战绩胜率和队友提醒（0 = 不加权）
+    #[serde(default)]
+    pub form_half_life_hours: f64,
+    /// 选人开始后并发预取友方玩家战绩，点开玩家时无需等待（会额外请求 OP.GG，默认关闭）
+    #[serde(default)]
+    pub prefetch_histories: bool,
+    /// 预取时同时包括敌方玩家（仅对局中能拿到敌方名字时有效）
+    #[serde(default)]
+    pub prefetch_enemies: bool,
+    /// 进入新的选人（gameId 变化）时清空上一局的敌方、队友和选中状态，「锁定数据」下同样生效
+    #[serde(default = "default_true")]
+    pub clear_on_new_session: bool,
+    /// 对线敌人确定后自动选中并加载克制数据
+    #[serde(default)]
+    pub auto_select_lane_enemy: bool,
+    /// 自动选中对线敌人时，同时用我的英雄对其做 AI 分析（会消耗 token）
+    #[serde(default)]
+    pub auto_analyze_lane_enemy: bool,
+    /// 自定义 AI 分析提示词（为空使用内置模板），占位符见 PromptTemplate
+    #[serde(default)]
+    pub prompt_template: String,
+    /// 自定义 system prompt（为空使用内置）
+    #[serde(default)]
+    pub system_prompt: String,
+    /// 多个命名提示词模板，在 AI 面板下拉选择
+    #[serde(default)]
+    pub prompt_templates: Vec<PromptTemplate>,
+    /// AI 输出开头显示引擎/模型/Prompt 调试头（默认关闭，关闭时缓存和保存的内容也不含）
+    #[serde(default)]
+    pub show_ai_debug_header: bool,
     /// 多 AI 引擎列表
     #[serde(default)]
     pub ai_engines: Vec<AiEngine>,
@@ -57,6 +169,38 @@ fn default_region() -> String {
     "jp".to_string()
 }
 
+fn default_auto_update_days() -> u32 {
+    7
+}
+
+fn default_opgg_locale() -> String {
+    "zh-cn".to_string()
+}
+
+fn default_opgg_region() -> String {
+    "global".to_string()
+}
+
+fn default_lcu_timeout_secs() -> u64 {
+    3
+}
+
+fn default_lcu_poll_ms() -> u64 {
+    900
+}
+
+fn default_hotkey() -> String {
+    "Ctrl+Alt+L".to_string()
+}
+
+fn default_dodge_games() -> usize {
+    10
+}
+
+fn default_dodge_win_rate() -> f64 {
+    40.0
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         Self {
@@ -64,16 +208,62 @@ impl Default for AppConfig {
             openai_model: default_model(),
             lockfile_dir: String::new(),
             region: default_region(),
+            auto_update_days: default_auto_update_days(),
+            auto_update: false,
+            proxy_url: String::new(),
+            opgg_locale: default_opgg_locale(),
+            opgg_region: default_opgg_region(),
+            client_variant: ClientVariant::Global,
+            lcu_timeout_secs: default_lcu_timeout_secs(),
+            lcu_poll_ms: default_lcu_poll_ms(),
+            hotkey: default_hotkey(),
+            dodge_warning: false,
+            dodge_games: default_dodge_games(),
+            dodge_win_rate: default_dodge_win_rate(),
+            form_half_life_hours: 0.0,
+            prefetch_histories: false,
+            prefetch_enemies: false,
+            clear_on_new_session: true,
+            auto_select_lane_enemy: false,
+            auto_analyze_lane_enemy: false,
+            prompt_template: String::new(),
+            system_prompt: String::new(),
+            prompt_templates: vec![],
+            show_ai_debug_header: false,
             ai_engines: vec![],
         }
     }
 }
 
 impl AppConfig {
+    /// 可选的提示词模板（不含内置默认模板）：顶层 prompt_template 记为「自定义」，其后为 prompt_templates
+    pub fn get_prompt_templates(&self) -> Vec<PromptTemplate> {
+        let mut templates = Vec::new();
+        if !self.prompt_template.trim().is_empty() {
+            templates.push(PromptTemplate {
+                name: "自定义".into(),
+                prompt: self.prompt_template.clone(),
+                system_prompt: self.system_prompt.clone(),
+            });
+        }
+        templates.extend(self.prompt_templates.iter().filter(|t| !t.prompt.trim().is_empty()).cloned());
+        templates
+    }
+
     /// 获取最终的 AI 引擎列表（兼容旧配置）
     pub fn get_engines(&self) -> Vec<AiEngine> {
         if !self.ai_engines.is_empty() {
-            return self.ai_engines.clone();
+            return self
+                .ai_engines
+                .iter()
+                // 跳过仍是模板占位 key（如 "sk-xxx"）的引擎
+                .filter(|e| !e.api_key.ends_with("-xxx"))
+                .cloned()
+                .map(|mut e| {
+                    e.proxy_url = self.proxy_url.clone();
+                    e
+                })
+                .collect();
         }
         // 兼容旧的 openai_api_key / openai_model 配置
         if !self.openai_api_key.is_empty() && self.openai_api_key != "sk-proj-xxx" {
@@ -83,6 +273,13 @@ impl AppConfig {
                 api_key: self.openai_api_key.clone(),
                 model: self.openai_model.clone(),
                 models: vec![],
+                max_tokens: default_max_tokens(),
+                temperature: None,
+                token_param_name: default_token_param_name(),
+                proxy_url: self.proxy_url.clone(),
+                stream_usage: true,
+                fallback_models: vec![],
+                extra_headers: HashMap::new(),
             }]
         } else {
             vec![]
@@ -90,13 +287,102 @@ impl AppConfig {
     }
 }
 
+/// 创建访问外部服务（OP.GG / AI 接口）的 HTTP 客户端，配置了代理时走代理
+///
+/// LCU 客户端只访问 127.0.0.1，不要用这个函数创建。
+pub fn http_client(proxy_url: &str, timeout: Duration) -> Result<reqwest::Client, String> {
+    let mut builder = reqwest::Client::builder().timeout(timeout);
+    if !proxy_url.is_empty() {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| format!("代理地址无效 ({proxy_url}): {e}"))?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+/// 格式化网络错误，启用代理时连接失败会提示检查代理
+pub fn net_error(e: &reqwest::Error, proxy_url: &str) -> String {
+    if !proxy_url.is_empty() && (e.is_connect() || e.is_timeout()) {
+        format!("{e}（通过代理 {proxy_url} 连接失败，请检查代理设置）")
+    } else {
+        e.to_string()
+    }
+}
+
 /// 获取 exe 同目录下的配置文件路径
-fn config_path() -> PathBuf {
+pub fn config_path() -> PathBuf {
     let exe = std::env::current_exe().unwrap_or_default();
     exe.parent().unwrap_or(std::path::Path::new(".")).join("config.toml")
 }
 
+/// DPAPI 加密后的配置文件路径（存在时优先于 config.toml）
+fn config_enc_path() -> PathBuf {
+    config_path().with_file_name("config.enc")
+}
+
+/// 是否已使用加密配置
+pub fn config_encrypted() -> bool {
+    config_enc_path().exists()
+}
+
+/// 把 config.toml 加密为 config.enc（仅本机当前用户可解密），校验可解密后删除明文
+pub fn encrypt_config() -> Result<PathBuf, String> {
+    let plain_path = config_path();
+    let content = std::fs::read_to_string(&plain_path)
+        .map_err(|e| format!("读取 {} 失败: {e}", plain_path.display()))?;
+    toml::from_str::<AppConfig>(&content).map_err(|e| format!("config.toml 解析失败，未加密: {e}"))?;
+    let data = crate::win32::protect_data(content.as_bytes())?;
+    if crate::win32::unprotect_data(&data)? != content.as_bytes() {
+        return Err("加密校验失败，未修改配置".into());
+    }
+    let enc_path = config_enc_path();
+    std::fs::write(&enc_path, data).map_err(|e| format!("写入 {} 失败: {e}", enc_path.display()))?;
+    std::fs::remove_file(&plain_path)
+        .map_err(|e| format!("已生成 config.enc，但删除明文 config.toml 失败: {e}"))?;
+    Ok(enc_path)
+}
+
+/// 把 config.enc 还原为明文 config.toml（需要修改配置时使用）
+pub fn decrypt_config() -> Result<PathBuf, String> {
+    let plain_path = config_path();
+    let content = read_encrypted_config()?;
+    std::fs::write(&plain_path, content).map_err(|e| format!("写入 {} 失败: {e}", plain_path.display()))?;
+    let _ = std::fs::remove_file(config_enc_path());
+    Ok(plain_path)
+}
+
+fn read_encrypted_config() -> Result<String, String> {
+    let data = std::fs::read(config_enc_path()).map_err(|e| format!("读取 config.enc 失败: {e}"))?;
+    let plain = crate::win32::unprotect_data(&data)?;
+    String::from_utf8(plain).map_err(|e| format!("config.enc 内容无效: {e}"))
+}
+
+/// 首次运行时生成的配置模板（与 config.example.toml 相同）
+const CONFIG_TEMPLATE: &str = include_str!("../config.example.toml");
+
+/// 配置文件不存在时按模板生成，返回新建文件的路径；已存在则不动
+pub fn ensure_config_file() -> Option<PathBuf> {
+    let path = config_path();
+    if path.exists() || config_encrypted() {
+        return None;
+    }
+    match std::fs::write(&path, CONFIG_TEMPLATE) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            eprintln!("生成 config.toml 失败 ({}): {e}", path.display());
+            None
+        }
+    }
+}
+
 pub fn load_config() -> AppConfig {
+    // 优先读取加密配置，失败时回退到明文 config.toml
+    if config_encrypted() {
+        match read_encrypted_config().and_then(|c| toml::from_str(&c).map_err(|e| e.to_string())) {
+            Ok(config) => return config,
+            Err(e) => eprintln!("config.enc 读取失败，改用 config.toml: {e}"),
+        }
+    }
     let path = config_path();
     match std::fs::read_to_string(&path) {
         Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
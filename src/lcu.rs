@@ -1,8 +1,10 @@
-use crate::types::{ChampionSummary, EnemyInfo, LcuAuth, TeamMateInfo, looks_like_chinese, to_opgg_slug};
+use crate::types::{ChampIntent, ChampionMastery, ChampionSummary, ClientVariant, EnemyInfo, LcuAuth, RunePage, TeamMateInfo, looks_like_chinese, to_opgg_slug};
 use base64::Engine;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
 /// 英雄图标数据（一次性从 LCU 加载）
@@ -12,18 +14,264 @@ pub struct ChampionIconData {
     pub slug_to_id: HashMap<String, i64>,
     pub name_to_id: HashMap<String, i64>,
     pub id_to_name: HashMap<i64, String>,
+    /// slug → 英文名（LCU alias，本地缓存中没有）
+    pub slug_to_alias: HashMap<String, String>,
+    /// slug → 定位标签（LCU roles，本地缓存中没有）
+    pub slug_to_roles: HashMap<String, Vec<String>>,
+    /// 装备 / 召唤师技能 id → 名称
+    pub item_names: HashMap<i64, String>,
+    pub spell_names: HashMap<i64, String>,
+}
+
+/// exe 同目录下的英雄图标缓存目录
+fn icons_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap_or_default();
+    exe.parent().unwrap_or(Path::new(".")).join("icons")
+}
+
+/// 图标缓存索引：champion_id → 英雄信息
+type IconIndex = HashMap<i64, IconIndexEntry>;
+
+/// 图标缓存索引中的一个英雄
+///
+/// 旧版本写的是 [slug, 名称] 数组，按顺序读入同样可用，alias / roles 为空。
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct IconIndexEntry {
+    slug: String,
+    name: String,
+    #[serde(default)]
+    alias: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+fn decode_icon(bytes: &[u8]) -> Option<(Vec<u8>, u32, u32)> {
+    let rgba = image::load_from_memory(bytes).ok()?.to_rgba8();
+    let (w, h) = rgba.dimensions();
+    Some((rgba.into_raw(), w, h))
+}
+
+/// 从本地缓存加载英雄图标（客户端未启动时也能显示图标）
+pub fn load_cached_icons() -> Option<ChampionIconData> {
+    let dir = icons_dir();
+    let index: IconIndex = serde_json::from_str(&std::fs::read_to_string(dir.join("index.json")).ok()?).ok()?;
+    let mut data = ChampionIconData {
+        icons: HashMap::new(),
+        slug_to_id: HashMap::new(),
+        name_to_id: HashMap::new(),
+        id_to_name: HashMap::new(),
+        slug_to_alias: HashMap::new(),
+        slug_to_roles: HashMap::new(),
+        item_names: HashMap::new(),
+        spell_names: HashMap::new(),
+    };
+    for (id, IconIndexEntry { slug, name, alias, roles }) in index {
+        if let Some(icon) = std::fs::read(dir.join(format!("{id}.png"))).ok().and_then(|b| decode_icon(&b)) {
+            data.icons.insert(id, icon);
+        }
+        if !alias.is_empty() {
+            data.slug_to_alias.insert(slug.clone(), alias);
+        }
+        if !roles.is_empty() {
+            data.slug_to_roles.insert(slug.clone(), roles);
+        }
+        data.slug_to_id.insert(slug, id);
+        data.name_to_id.insert(name.clone(), id);
+        data.id_to_name.insert(id, name);
+    }
+    Some(data)
+}
+
+const DDRAGON: &str = "https://ddragon.leagueoflegends.com";
+
+/// OP.GG 页面语言 → Data Dragon locale（腾讯客户端固定简体中文）
+pub fn ddragon_locale(opgg_locale: &str, variant: ClientVariant) -> &'static str {
+    if variant == ClientVariant::Tencent {
+        return "zh_CN";
+    }
+    match opgg_locale.trim_matches('/').to_lowercase().as_str() {
+        "zh-cn" => "zh_CN",
+        "zh-tw" => "zh_TW",
+        "ja" => "ja_JP",
+        "ko" => "ko_KR",
+        _ => "en_US",
+    }
+}
+
+/// Data Dragon 最新版本号（成功后进程内不再重复查询）
+async fn ddragon_version(client: &reqwest::Client) -> Result<&'static str, String> {
+    static VERSION: tokio::sync::OnceCell<String> = tokio::sync::OnceCell::const_new();
+    VERSION
+        .get_or_try_init(|| async {
+            let versions: Vec<String> = client
+                .get(format!("{DDRAGON}/api/versions.json"))
+                .send()
+                .await
+                .map_err(|e| format!("获取 Data Dragon 版本失败: {e}"))?
+                .json()
+                .await
+                .map_err(|e| format!("解析 Data Dragon 版本失败: {e}"))?;
+            versions.into_iter().next().ok_or("Data Dragon 版本列表为空".to_string())
+        })
+        .await
+        .map(String::as_str)
+}
+
+/// 装备图标（RGBA）：优先读 icons/items/ 缓存，没有时从 Data Dragon 下载并写入缓存
+pub async fn fetch_item_icon(client: &reqwest::Client, item_id: i64) -> Result<(Vec<u8>, u32, u32), String> {
+    let dir = icons_dir().join("items");
+    let file = dir.join(format!("{item_id}.png"));
+    let bytes = match std::fs::read(&file) {
+        Ok(b) => b,
+        Err(_) => {
+            let version = ddragon_version(client).await?;
+            let b = client
+                .get(format!("{DDRAGON}/cdn/{version}/img/item/{item_id}.png"))
+                .send()
+                .await
+                .and_then(|r| r.error_for_status())
+                .map_err(|e| format!("获取装备图标失败: {e}"))?
+                .bytes()
+                .await
+                .map_err(|e| format!("获取装备图标失败: {e}"))?;
+            let _ = std::fs::create_dir_all(&dir);
+            let _ = std::fs::write(&file, &b);
+            b.to_vec()
+        }
+    };
+    decode_icon(&bytes).ok_or_else(|| format!("装备图标 {item_id} 解码失败"))
+}
+
+/// 客户端未启动且本地没有图标缓存时，从 Data Dragon 获取英雄名和图标，并按 LCU 相同格式写入 icons/
+///
+/// 写入后下次启动直接走 load_cached_icons；之后连上客户端时 LCU 数据会覆盖这里的结果。
+pub async fn fetch_ddragon_icons(client: &reqwest::Client, locale: &str) -> Result<ChampionIconData, String> {
+    let version = ddragon_version(client).await?;
+    let list: serde_json::Value = client
+        .get(format!("{DDRAGON}/cdn/{version}/data/{locale}/champion.json"))
+        .send()
+        .await
+        .map_err(|e| format!("获取 Data Dragon 英雄列表失败: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("解析 Data Dragon 英雄列表失败: {e}"))?;
+    let champs = list.get("data").and_then(|d| d.as_object()).ok_or("Data Dragon 英雄列表格式不符")?;
+
+    let mut data = ChampionIconData {
+        icons: HashMap::new(),
+        slug_to_id: HashMap::new(),
+        name_to_id: HashMap::new(),
+        id_to_name: HashMap::new(),
+        slug_to_alias: HashMap::new(),
+        slug_to_roles: HashMap::new(),
+        item_names: HashMap::new(),
+        spell_names: HashMap::new(),
+    };
+    let mut index = IconIndex::new();
+    // (champion_id, Data Dragon id)，id 即 LCU alias，也是图标文件名
+    let mut to_fetch = Vec::new();
+    for champ in champs.values() {
+        let alias = champ.get("id").and_then(|v| v.as_str()).unwrap_or("");
+        let name = champ.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        let Some(id) = champ.get("key").and_then(|v| v.as_str()).and_then(|k| k.parse::<i64>().ok()) else { continue };
+        if alias.is_empty() || name.is_empty() {
+            continue;
+        }
+        let slug = to_opgg_slug(alias, name);
+        let roles: Vec<String> = champ
+            .get("tags")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|t| t.as_str()).map(str::to_lowercase).collect())
+            .unwrap_or_default();
+        if !roles.is_empty() {
+            data.slug_to_roles.insert(slug.clone(), roles.clone());
+        }
+        data.slug_to_alias.insert(slug.clone(), alias.to_string());
+        data.slug_to_id.insert(slug.clone(), id);
+        data.name_to_id.insert(name.to_string(), id);
+        data.id_to_name.insert(id, name.to_string());
+        index.insert(id, IconIndexEntry { slug, name: name.to_string(), alias: alias.to_string(), roles });
+        to_fetch.push((id, alias.to_string()));
+    }
+
+    // 图标走公共 CDN，限制并发
+    let dir = icons_dir();
+    let _ = std::fs::create_dir_all(&dir);
+    let limit = Arc::new(tokio::sync::Semaphore::new(8));
+    let mut set = tokio::task::JoinSet::new();
+    for (id, alias) in to_fetch {
+        let client = client.clone();
+        let limit = limit.clone();
+        let file = dir.join(format!("{id}.png"));
+        let url = format!("{DDRAGON}/cdn/{version}/img/champion/{alias}.png");
+        set.spawn(async move {
+            let bytes = match std::fs::read(&file) {
+                Ok(b) => b,
+                Err(_) => {
+                    let _permit = limit.acquire().await.ok()?;
+                    let b = client.get(&url).send().await.ok()?.error_for_status().ok()?.bytes().await.ok()?;
+                    let _ = std::fs::write(&file, &b);
+                    b.to_vec()
+                }
+            };
+            Some((id, decode_icon(&bytes)?))
+        });
+    }
+    while let Some(result) = set.join_next().await {
+        if let Ok(Some((id, icon))) = result {
+            data.icons.insert(id, icon);
+        }
+    }
+    // 期间已连上客户端并写入了 LCU 的索引时不覆盖
+    let index_path = dir.join("index.json");
+    if !index_path.exists() {
+        if let Ok(json) = serde_json::to_string(&index) {
+            let _ = std::fs::write(index_path, json);
+        }
+    }
+    Ok(data)
+}
+
+/// LCU 连接失败的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LcuErrorKind {
+    /// 所有候选位置都没有 lockfile（客户端未启动，或装在未知目录）
+    LockfileNotFound,
+    /// 找到了 lockfile 但读取或解析失败
+    LockfileInvalid,
 }
 
 /// LCU 状态更新消息
 #[derive(Debug)]
 pub struct LcuState {
     pub connected: bool,
+    /// 需要用户处理的错误（红色显示，可关闭）
     pub error: String,
+    /// 错误类别，界面按它显示引导（不去匹配 error 的文字）
+    pub error_kind: Option<LcuErrorKind>,
+    /// 正常状态提示（如不在选人界面），灰色显示
+    pub info: String,
     pub enemies: Vec<EnemyInfo>,
     pub teammates: Vec<TeamMateInfo>,
     pub my_pos: String,
     pub lane_enemy_id: Option<i64>,
+    /// 我选择（或预选）的英雄 slug
+    pub my_champ_slug: String,
     pub champion_lang: String,
+    /// 客户端游戏版本（大版本.小版本，如 "14.23"），未获取时为空
+    pub game_version: String,
+    /// 选人 session 的 gameId（不在选人阶段时为 0），用于识别新的一局
+    pub session_id: i64,
+    /// 大乱斗（ARAM）等可重随模式
+    pub is_aram: bool,
+    /// 游戏队列 id（gameflow session 的 gameData.queue.id），未知或不在选人 / 游戏中时为 0
+    pub queue_id: i64,
+    /// 大乱斗候选席（可交换的英雄 id）
+    pub bench: Vec<i64>,
+    /// 其他玩家未锁定的 ban / 选英雄意向
+    pub intents: Vec<ChampIntent>,
+    /// 当前选人阶段的倒计时（无限时间的自定义房间或没有计时数据时为 None）
+    pub timer: Option<ChampSelectTimer>,
     pub champion_data: Option<ChampionIconData>,
     pub auth: Option<LcuAuth>,
 }
@@ -44,6 +292,21 @@ fn find_lockfile(config_dir: &str) -> Option<PathBuf> {
         }
     }
 
+    // Riot Client 记录的 League 安装目录（非默认盘符安装时靠这里找到）
+    for dir in riot_league_install_dirs() {
+        candidates.push(dir.join("lockfile"));
+    }
+
+    // 腾讯（WeGame）客户端的常见安装目录
+    if client_variant() == ClientVariant::Tencent {
+        for root in [r"C:\WeGameApps", r"D:\WeGameApps", r"E:\WeGameApps", r"C:\Program Files (x86)\WeGameApps"] {
+            candidates.push(PathBuf::from(root).join(r"英雄联盟\LeagueClient\lockfile"));
+        }
+        for root in [r"C:\", r"D:\", r"E:\"] {
+            candidates.push(PathBuf::from(root).join(r"英雄联盟\LeagueClient\lockfile"));
+        }
+    }
+
     // 常见路径
     let paths = [
         r"C:\Riot Games\League of Legends\lockfile",
@@ -68,30 +331,333 @@ fn find_lockfile(config_dir: &str) -> Option<PathBuf> {
     candidates.into_iter().find(|p| p.exists())
 }
 
+/// 从 Riot Client 的安装元数据读取 League 安装目录
+///
+/// - `%PROGRAMDATA%\Riot Games\RiotClientInstalls.json` 的 associated_client（键为游戏目录）
+/// - `%PROGRAMDATA%\Riot Games\Metadata\league_of_legends.*\*.product_settings.yaml`
+///   中的 product_install_full_path
+fn riot_league_install_dirs() -> Vec<PathBuf> {
+    let Ok(pd) = std::env::var("PROGRAMDATA") else {
+        return vec![];
+    };
+    let riot = PathBuf::from(pd).join("Riot Games");
+    let mut dirs = Vec::new();
+
+    if let Ok(raw) = std::fs::read_to_string(riot.join("RiotClientInstalls.json")) {
+        if let Ok(val) = serde_json::from_str::<serde_json::Value>(&raw) {
+            if let Some(map) = val.get("associated_client").and_then(|v| v.as_object()) {
+                // 其他 Riot 游戏也记在这里；目录名不一定含 league（如 D:\Games\LoL），按目录内容判断
+                dirs.extend(map.keys().map(PathBuf::from).filter(|d| is_league_dir(d)));
+            }
+        }
+    }
+
+    if let Ok(entries) = std::fs::read_dir(riot.join("Metadata")) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with("league_of_legends") {
+                continue;
+            }
+            let settings = entry.path().join(format!("{name}.product_settings.yaml"));
+            let Ok(raw) = std::fs::read_to_string(settings) else { continue };
+            // 简单按行读取，不引入 yaml 依赖
+            let path = raw.lines().find_map(|l| l.trim().strip_prefix("product_install_full_path:"));
+            if let Some(path) = path.map(|p| p.trim().trim_matches('"')).filter(|p| !p.is_empty()) {
+                dirs.push(PathBuf::from(path));
+            }
+        }
+    }
+
+    dedup_dirs(dirs)
+}
+
+/// 目录里有 LeagueClient.exe 或 lockfile 才算 League 安装目录
+fn is_league_dir(dir: &Path) -> bool {
+    dir.join("LeagueClient.exe").exists() || dir.join("lockfile").exists()
+}
+
+/// 去重（Windows 路径不区分大小写，忽略分隔符写法和末尾分隔符），保留第一次出现的顺序
+fn dedup_dirs(dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = std::collections::HashSet::new();
+    dirs.into_iter()
+        .filter(|d| {
+            let norm = d.to_string_lossy().replace('/', "\\").trim_end_matches('\\').to_lowercase();
+            seen.insert(norm)
+        })
+        .collect()
+}
+
 /// 读取 lockfile
 fn read_lockfile(path: &Path) -> Result<LcuAuth, String> {
     let raw = std::fs::read_to_string(path).map_err(|e| format!("读取 lockfile 失败: {e}"))?;
-    let parts: Vec<&str> = raw.trim().split(':').collect();
-    if parts.len() != 5 {
-        return Err(format!("lockfile 格式不符: {raw}"));
+    parse_lockfile(&raw)
+}
+
+/// 解析 lockfile 内容 `name:pid:port:password:protocol`
+///
+/// 从右侧取已知字段，进程名里带冒号也不影响；报错时不输出原文（含密码）。
+fn parse_lockfile(raw: &str) -> Result<LcuAuth, String> {
+    let mut parts = raw.trim().rsplitn(4, ':');
+    let (Some(protocol), Some(password), Some(port), Some(_name_pid)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err("lockfile 格式不符：应为 name:pid:port:password:protocol".into());
+    };
+    if !protocol.starts_with("http") {
+        return Err(format!("lockfile 协议字段无效: {protocol}"));
+    }
+    let port: u16 = port
+        .parse()
+        .ok()
+        .filter(|p| *p != 0)
+        .ok_or_else(|| format!("lockfile 端口解析失败: {port}"))?;
+    if password.is_empty() {
+        return Err("lockfile 密码为空".into());
     }
-    let port: u16 = parts[2].parse().map_err(|_| "端口解析失败".to_string())?;
     Ok(LcuAuth {
         port,
-        password: parts[3].to_string(),
+        password: password.to_string(),
     })
 }
 
+/// LCU 写请求（POST / PUT / DELETE），返回响应 JSON（无内容时为 Null）
+async fn lcu_send(
+    client: &reqwest::Client,
+    auth: &LcuAuth,
+    method: reqwest::Method,
+    path: &str,
+    body: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, String> {
+    let token = base64::engine::general_purpose::STANDARD
+        .encode(format!("riot:{}", auth.password));
+    let url = format!("https://127.0.0.1:{}{}", auth.port, path);
+    let mut req = client
+        .request(method, &url)
+        .header("Authorization", format!("Basic {token}"));
+    if let Some(b) = body {
+        req = req.json(b);
+    }
+    let resp = req.send().await.map_err(|e| e.to_string())?;
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    if !status.is_success() {
+        let brief: String = text.chars().take(200).collect();
+        return Err(format!("HTTP {status}: {brief}"));
+    }
+    Ok(serde_json::from_str(&text).unwrap_or(serde_json::Value::Null))
+}
+
+/// 按客户端 /lol-perks/v1/styles 校验符文页：主系 4 个、副系 2 个符文属于各自的符文系，属性碎片存在
+///
+/// 客户端返回的数据里读不到任何符文时不做校验（仍有 OP.GG 解析时的本地校验）。
+fn check_rune_page(styles: &serde_json::Value, page: &RunePage) -> Result<(), String> {
+    // perk id → 所属符文系（属性碎片在每个符文系里都有，记为 None）
+    let mut perk_style: HashMap<i64, Option<i64>> = HashMap::new();
+    for style in styles.as_array().into_iter().flatten() {
+        let Some(style_id) = style.get("id").and_then(|v| v.as_i64()) else { continue };
+        for slot in style.get("slots").and_then(|v| v.as_array()).into_iter().flatten() {
+            let stat_mod = slot.get("type").and_then(|v| v.as_str()) == Some("kStatMod");
+            for perk in slot.get("perks").and_then(|v| v.as_array()).into_iter().flatten().filter_map(|v| v.as_i64()) {
+                perk_style.entry(perk).or_insert((!stat_mod).then_some(style_id));
+            }
+        }
+    }
+    if perk_style.is_empty() {
+        return Ok(());
+    }
+    for (i, perk) in page.perks.iter().enumerate() {
+        let expected = match i {
+            0..4 => Some(page.primary_style),
+            4..6 => Some(page.sub_style),
+            _ => None,
+        };
+        match perk_style.get(perk) {
+            None => return Err(format!("客户端没有符文 {perk}，符文数据可能已过期")),
+            Some(style) if expected.is_some() && *style != expected => {
+                return Err(format!("符文 {perk} 与客户端的符文系不对应"));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// 工具专用符文页名称（每次覆盖这一页，不占用额外符文页）
+const RUNE_PAGE_NAME: &str = "lol-helper";
+
+/// 在客户端创建/替换 "lol-helper" 符文页并设为当前页（仅选人阶段）
+pub async fn apply_rune_page(auth: &LcuAuth, page: &RunePage, title: &str) -> Result<(), String> {
+    let client = lcu_client();
+    let phase = lcu_get(&client, auth, "/lol-gameflow/v1/gameflow-phase", None).await?;
+    if phase.as_str() != Some("ChampSelect") {
+        return Err("只能在选人阶段应用符文".into());
+    }
+
+    // 本地符文表没有逐条对照过官方数据，写入前按客户端自己的符文系数据再校验一遍
+    let styles = lcu_get(&client, auth, "/lol-perks/v1/styles", None).await?;
+    check_rune_page(&styles, page)?;
+
+    let pages = lcu_get(&client, auth, "/lol-perks/v1/pages", None).await?;
+    let pages = pages.as_array().cloned().unwrap_or_default();
+    let is_editable = |p: &serde_json::Value| p.get("isDeletable").and_then(|v| v.as_bool()).unwrap_or(false);
+    let page_name = |p: &serde_json::Value| p.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let ours = pages.iter().find(|p| is_editable(p) && page_name(p).starts_with(RUNE_PAGE_NAME));
+
+    let mut body = serde_json::json!({
+        "name": format!("{RUNE_PAGE_NAME} {title}"),
+        "primaryStyleId": page.primary_style,
+        "subStyleId": page.sub_style,
+        "selectedPerkIds": page.perks,
+        "current": true,
+    });
+    // 已有专用页时原地更新，写入失败也不会把旧页删掉
+    let id = if let Some(id) = ours.and_then(|p| p.get("id")).and_then(|v| v.as_i64()) {
+        body["id"] = serde_json::json!(id);
+        lcu_send(&client, auth, reqwest::Method::PUT, &format!("/lol-perks/v1/pages/{id}"), Some(&body)).await?;
+        Some(id)
+    } else {
+        // 没有专用页时检查上限，不删除用户自己的符文页
+        let inv = lcu_get(&client, auth, "/lol-perks/v1/inventory", None).await?;
+        let owned = inv.get("ownedPageCount").and_then(|v| v.as_i64()).unwrap_or(0);
+        let used = pages.iter().filter(|p| is_editable(p)).count() as i64;
+        if owned > 0 && used >= owned {
+            return Err(format!("符文页已满（{used}/{owned}），请删除一页或将一页改名为 {RUNE_PAGE_NAME}"));
+        }
+        let created = lcu_send(&client, auth, reqwest::Method::POST, "/lol-perks/v1/pages", Some(&body)).await?;
+        created.get("id").and_then(|v| v.as_i64())
+    };
+    if let Some(id) = id {
+        lcu_send(&client, auth, reqwest::Method::PUT, "/lol-perks/v1/currentpage", Some(&serde_json::json!(id))).await?;
+    }
+    Ok(())
+}
+
+/// 查询玩家的英雄熟练度（按点数降序）；puuid 为空时查询当前登录的玩家，返回 (puuid, 列表)
+pub async fn fetch_champion_mastery(auth: &LcuAuth, puuid: &str) -> Result<(String, Vec<ChampionMastery>), String> {
+    let client = lcu_client();
+    let puuid = if puuid.is_empty() {
+        let me = lcu_get(&client, auth, "/lol-summoner/v1/current-summoner", None).await?;
+        me.get("puuid")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| "获取当前玩家 puuid 失败".to_string())?
+            .to_string()
+    } else {
+        puuid.to_string()
+    };
+    let val = lcu_get(&client, auth, &format!("/lol-collections/v1/inventories/{puuid}/champion-mastery"), None).await?;
+    let mut list: Vec<ChampionMastery> = val
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|m| {
+                    Some(ChampionMastery {
+                        champion_id: m.get("championId")?.as_i64()?,
+                        points: m.get("championPoints").and_then(|v| v.as_i64()).unwrap_or(0),
+                        level: m.get("championLevel").and_then(|v| v.as_i64()).unwrap_or(0),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    list.sort_by_key(|m| std::cmp::Reverse(m.points));
+    Ok((puuid, list))
+}
+
+/// LCU 请求超时与轮询间隔
+struct LcuTiming {
+    timeout: Duration,
+    poll: Duration,
+}
+
+static TIMING: std::sync::OnceLock<LcuTiming> = std::sync::OnceLock::new();
+
+/// 超时下限（秒）：过短时图标等批量请求容易失败
+const MIN_TIMEOUT_SECS: u64 = 1;
+/// 轮询间隔下限（毫秒）：过短会频繁请求客户端
+const MIN_POLL_MS: u64 = 300;
+
+/// 启动时按配置设置 LCU 超时和轮询间隔（只生效一次，低于下限按下限处理）
+pub fn configure_timing(timeout_secs: u64, poll_ms: u64) {
+    let _ = TIMING.set(LcuTiming {
+        timeout: Duration::from_secs(timeout_secs.max(MIN_TIMEOUT_SECS)),
+        poll: Duration::from_millis(poll_ms.max(MIN_POLL_MS)),
+    });
+}
+
+static VARIANT: std::sync::OnceLock<ClientVariant> = std::sync::OnceLock::new();
+
+/// 启动时按配置设置客户端版本（只生效一次，未调用时为国际服）
+pub fn configure_client_variant(variant: ClientVariant) {
+    let _ = VARIANT.set(variant);
+}
+
+fn client_variant() -> ClientVariant {
+    VARIANT.get().copied().unwrap_or_default()
+}
+
+/// champion-summary 依次尝试的 locale（None 为客户端默认语言），取第一个返回中文名的
+///
+/// 腾讯客户端默认即为简体中文，部分版本不认 locale 参数，因此先用默认语言。
+fn champion_locales() -> &'static [Option<&'static str>] {
+    match client_variant() {
+        ClientVariant::Global => &[Some("zh_CN"), Some("zh_TW"), None],
+        ClientVariant::Tencent => &[None, Some("zh_CN")],
+    }
+}
+
+fn timing() -> &'static LcuTiming {
+    TIMING.get_or_init(|| LcuTiming { timeout: Duration::from_secs(3), poll: Duration::from_millis(900) })
+}
+
+/// 成功轮询后的等待时间
+fn poll_interval() -> Duration {
+    timing().poll
+}
+
+/// 连接失败 / 找不到客户端时的等待时间，至少 2 秒
+fn error_backoff() -> Duration {
+    (timing().poll * 2).max(Duration::from_secs(2))
+}
+
 /// 创建忽略证书验证的 HTTP 客户端（仅用于 127.0.0.1 LCU）
 pub fn lcu_client() -> reqwest::Client {
     reqwest::Client::builder()
         .danger_accept_invalid_certs(true)
         .no_proxy()
-        .timeout(std::time::Duration::from_secs(3))
+        .timeout(timing().timeout)
         .build()
         .unwrap()
 }
 
+/// TLS 握手失败的请求错误前缀（与 HTTP 状态错误的 "HTTP " 前缀对应）
+const TLS_ERROR_PREFIX: &str = "TLS ";
+
+/// 请求错误转为文本，TLS 握手失败时加上 TLS_ERROR_PREFIX
+fn request_error(e: reqwest::Error) -> String {
+    if is_tls_error(&e) {
+        format!("{TLS_ERROR_PREFIX}{e}")
+    } else {
+        e.to_string()
+    }
+}
+
+/// 错误链中是否有 rustls 错误（连接已建立但握手失败，而不是连接被拒或超时）
+fn is_tls_error(e: &reqwest::Error) -> bool {
+    let mut next = std::error::Error::source(e);
+    while let Some(err) = next {
+        if err.is::<rustls::Error>() {
+            return true;
+        }
+        // 握手错误被包在（可能多层）io::Error 里，io::Error::source 会跳过被包的错误本身
+        next = match err.downcast_ref::<std::io::Error>() {
+            Some(io) => io.get_ref().map(|inner| inner as &(dyn std::error::Error + 'static)),
+            None => err.source(),
+        };
+    }
+    false
+}
+
 /// LCU GET 请求
 async fn lcu_get(
     client: &reqwest::Client,
@@ -113,7 +679,7 @@ async fn lcu_get(
         .header("Authorization", format!("Basic {token}"))
         .send()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(request_error)?;
 
     if !resp.status().is_success() {
         return Err(format!("HTTP {}", resp.status()));
@@ -136,28 +702,251 @@ async fn lcu_get_bytes(
         .header("Authorization", format!("Basic {token}"))
         .send()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(request_error)?;
     if !resp.status().is_success() {
         return Err(format!("HTTP {}", resp.status()));
     }
     resp.bytes().await.map(|b| b.to_vec()).map_err(|e| e.to_string())
 }
 
+/// championId → OP.GG slug（未知英雄返回空）
+fn champ_slug(champ_cache: &HashMap<i64, ChampionSummary>, id: i64) -> String {
+    champ_cache.get(&id).map(|c| to_opgg_slug(&c.alias, &c.name)).unwrap_or_default()
+}
+
+/// 选人 session 解析结果
+struct ChampSelectInfo {
+    my_pos: String,
+    my_champion_id: i64,
+    enemies: Vec<EnemyInfo>,
+    lane_enemy_id: Option<i64>,
+    is_aram: bool,
+    bench: Vec<i64>,
+    intents: Vec<ChampIntent>,
+}
+
+/// 解析选人 session
+///
+/// 每次轮询都完整重建，换英雄/交换英雄后 championId 变化会直接反映到结果里。
+fn parse_champ_select(
+    sess: &serde_json::Value,
+    champ_cache: &HashMap<i64, ChampionSummary>,
+) -> ChampSelectInfo {
+    let empty = Vec::new();
+    let their_team = sess.get("theirTeam").and_then(|v| v.as_array()).unwrap_or(&empty);
+    let my_team = sess.get("myTeam").and_then(|v| v.as_array()).unwrap_or(&empty);
+    let local_cell = sess.get("localPlayerCellId").and_then(|v| v.as_i64());
+
+    // 找我的位置和英雄（未锁定时用预选英雄）
+    let mut my_pos = String::new();
+    let mut my_champion_id = 0;
+    if let Some(cell) = local_cell {
+        for p in my_team {
+            if p.get("cellId").and_then(|v| v.as_i64()) == Some(cell) {
+                my_pos = p.get("assignedPosition").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                my_champion_id = p.get("championId").and_then(|v| v.as_i64()).unwrap_or(0);
+                if my_champion_id <= 0 {
+                    my_champion_id = p.get("championPickIntent").and_then(|v| v.as_i64()).unwrap_or(0);
+                }
+                break;
+            }
+        }
+    }
+
+    // 构建敌方列表
+    let mut enemies = Vec::new();
+    for p in their_team {
+        let cid = p.get("championId").and_then(|v| v.as_i64()).unwrap_or(0);
+        let champ = champ_cache.get(&cid);
+        let name = champ.map(|c| c.name.as_str()).unwrap_or("未知英雄").to_string();
+        let slug = champ.map(|c| to_opgg_slug(&c.alias, &c.name)).unwrap_or_default();
+        let pos = p.get("assignedPosition").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        enemies.push(EnemyInfo {
+            champion_id: cid,
+            name,
+            slug,
+            pos,
+        });
+    }
+
+    // 找对线对手
+    let lane_enemy_id = if !my_pos.is_empty() {
+        enemies.iter().find(|e| e.pos == my_pos).map(|e| e.champion_id)
+    } else {
+        None
+    };
+
+    // 大乱斗：候选席英雄（新版为 benchChampions 对象数组，旧版为 benchChampionIds）
+    let is_aram = sess.get("benchEnabled").and_then(|v| v.as_bool()).unwrap_or(false);
+    let bench = match sess.get("benchChampions").and_then(|v| v.as_array()) {
+        Some(arr) => arr.iter().filter_map(|b| b.get("championId").and_then(|v| v.as_i64())).collect(),
+        None => sess
+            .get("benchChampionIds")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_i64()).collect())
+            .unwrap_or_default(),
+    };
+
+    let intents = parse_intents(sess, my_team, their_team, local_cell, champ_cache);
+
+    ChampSelectInfo { my_pos, my_champion_id, enemies, lane_enemy_id, is_aram, bench, intents }
+}
+
+/// 选人阶段倒计时
+#[derive(Debug, Clone)]
+pub struct ChampSelectTimer {
+    /// LCU 阶段名：PLANNING / BAN_PICK / FINALIZATION / GAME_STARTING
+    pub phase: String,
+    /// 收到时该阶段剩余的毫秒数
+    pub left_ms: i64,
+}
+
+/// gameflow session 中的队列 id，缺失时为 0
+fn parse_queue_id(gameflow: &serde_json::Value) -> i64 {
+    gameflow
+        .pointer("/gameData/queue/id")
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0)
+}
+
+/// 解析 session.timer；isInfinite（自定义房间）或缺少剩余时间时返回 None
+fn parse_timer(sess: &serde_json::Value) -> Option<ChampSelectTimer> {
+    let timer = sess.get("timer")?;
+    if timer.get("isInfinite").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return None;
+    }
+    let left_ms = timer.get("adjustedTimeLeftInPhase").and_then(|v| v.as_i64())?;
+    let phase = timer.get("phase").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    Some(ChampSelectTimer { phase, left_ms: left_ms.max(0) })
+}
+
+/// 解析其他玩家尚未完成的 ban / 选英雄意向
+///
+/// 来源：actions 中未完成但已悬停英雄的 ban / pick，以及队友的 championPickIntent（预选）。
+/// 不含自己；敌方的悬停在大多数队列中不可见，拿不到时结果为空。
+fn parse_intents(
+    sess: &serde_json::Value,
+    my_team: &[serde_json::Value],
+    their_team: &[serde_json::Value],
+    local_cell: Option<i64>,
+    champ_cache: &HashMap<i64, ChampionSummary>,
+) -> Vec<ChampIntent> {
+    let cell_info = |cell: i64| -> Option<(String, bool)> {
+        for (team, is_ally) in [(my_team, true), (their_team, false)] {
+            if let Some(p) = team.iter().find(|p| p.get("cellId").and_then(|v| v.as_i64()) == Some(cell)) {
+                let pos = p.get("assignedPosition").and_then(|v| v.as_str()).unwrap_or("").to_string();
+                return Some((pos, is_ally));
+            }
+        }
+        None
+    };
+    let mut intents: Vec<ChampIntent> = Vec::new();
+    let mut push = |champion_id: i64, position: String, is_ally: bool, is_ban: bool| {
+        if champion_id <= 0
+            || intents.iter().any(|i| i.champion_id == champion_id && i.is_ban == is_ban && i.is_ally == is_ally)
+        {
+            return;
+        }
+        let name = champ_cache.get(&champion_id).map(|c| c.name.clone()).unwrap_or_else(|| format!("#{champion_id}"));
+        intents.push(ChampIntent { champion_id, name, position, is_ally, is_ban });
+    };
+
+    // actions 为按阶段分组的二维数组
+    let actions = sess.get("actions").and_then(|v| v.as_array()).map(|a| a.as_slice()).unwrap_or(&[]);
+    for action in actions.iter().filter_map(|g| g.as_array()).flatten() {
+        if action.get("completed").and_then(|v| v.as_bool()).unwrap_or(true) {
+            continue;
+        }
+        let is_ban = match action.get("type").and_then(|v| v.as_str()) {
+            Some("ban") => true,
+            Some("pick") => false,
+            _ => continue,
+        };
+        let cell = action.get("actorCellId").and_then(|v| v.as_i64()).unwrap_or(-1);
+        if Some(cell) == local_cell {
+            continue;
+        }
+        let champion_id = action.get("championId").and_then(|v| v.as_i64()).unwrap_or(0);
+        let (position, in_my_team) = cell_info(cell).unwrap_or_default();
+        let is_ally = action.get("isAllyAction").and_then(|v| v.as_bool()).unwrap_or(in_my_team);
+        push(champion_id, position, is_ally, is_ban);
+    }
+
+    // 预选（declare intent）阶段还没有进行中的 pick action，以 championPickIntent 为准
+    for p in my_team {
+        let cell = p.get("cellId").and_then(|v| v.as_i64());
+        let locked = p.get("championId").and_then(|v| v.as_i64()).unwrap_or(0) > 0;
+        if cell == local_cell || locked {
+            continue;
+        }
+        let champion_id = p.get("championPickIntent").and_then(|v| v.as_i64()).unwrap_or(0);
+        let position = p.get("assignedPosition").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        push(champion_id, position, true, false);
+    }
+
+    intents
+}
+
+/// 读取 LCU 资源列表的 id → name（装备、召唤师技能等）
+async fn lcu_get_names(
+    client: &reqwest::Client,
+    auth: &LcuAuth,
+    path: &str,
+    params: Option<&[(&str, &str)]>,
+) -> HashMap<i64, String> {
+    let Ok(val) = lcu_get(client, auth, path, params).await else {
+        return HashMap::new();
+    };
+    val.as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| {
+                    let id = v.get("id")?.as_i64()?;
+                    let name = v.get("name")?.as_str()?;
+                    Some((id, name.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 连续多少次连接失败后重建 LCU 客户端（其中 TLS 握手失败时给出对应提示）
+const LCU_CLIENT_REBUILD_AFTER: u32 = 3;
+
+/// 英雄图标一个都没加载到时，隔多久再试
+const ICON_RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// 队友缓存条目：(name, tag_line, puuid, account_id, tier, division, lp)
+type TeammateRank = (String, String, String, i64, String, String, i32);
+
 /// 后台 LCU 轮询任务
+///
+/// `reload` 置为 true 时丢弃已加载的英雄名/图标数据并按当前客户端语言重新加载。
 pub fn spawn_lcu_poller(
     rt: Arc<tokio::runtime::Runtime>,
     lockfile_dir: String,
+    reload: Arc<AtomicBool>,
     tx: mpsc::UnboundedSender<LcuState>,
     ctx: egui::Context,
 ) {
     rt.spawn(async move {
-        let client = lcu_client();
+        let mut client = lcu_client();
+        // 连续的连接层失败次数（TLS 握手失败、连接被拒等，不含 HTTP 错误状态）
+        let mut conn_failures: u32 = 0;
         let mut champ_cache: HashMap<i64, ChampionSummary> = HashMap::new();
         let mut champion_lang = "unknown".to_string();
         let mut icon_data: Option<ChampionIconData> = None;
-        // 队友缓存: summoner_id → (name, tag_line, puuid, account_id, tier, division, lp)
-        let mut teammate_rank_cache: HashMap<i64, (String, String, String, i64, String, String, i32)> = HashMap::new();
+        let mut icons_loaded = false;
+        // 上次一个图标都没拿到时，下次重试的时间
+        let mut icons_retry_at: Option<std::time::Instant> = None;
+        // 上次连接的 LCU 端口：变化说明客户端重启过（可能切换了语言或账号）
+        let mut last_port: Option<u16> = None;
+        // 队友缓存: summoner_id → 名字与段位
+        let mut teammate_rank_cache: HashMap<i64, TeammateRank> = HashMap::new();
         let mut my_summoner_id: i64 = 0;
+        let mut game_version = String::new();
+        // 选人 session 的 (gameId, queueId)：同一局只查一次 gameflow
+        let mut session_queue: (i64, i64) = (0, 0);
 
         loop {
             let lockfile = find_lockfile(&lockfile_dir);
@@ -165,16 +954,28 @@ pub fn spawn_lcu_poller(
                 let _ = tx.send(LcuState {
                     connected: false,
                     error: "找不到 lockfile（可在 config.toml 设置 lockfile_dir）".into(),
+                    error_kind: Some(LcuErrorKind::LockfileNotFound),
+                    info: String::new(),
                     enemies: vec![],
                     teammates: vec![],
                     my_pos: String::new(),
                     lane_enemy_id: None,
+                    my_champ_slug: String::new(),
                     champion_lang: champion_lang.clone(),
+                    game_version: game_version.clone(),
+                    session_id: 0,
+                    is_aram: false,
+                    queue_id: 0,
+                    bench: vec![],
+                    intents: vec![],
+                    timer: None,
                     champion_data: None,
                     auth: None,
                 });
                 ctx.request_repaint();
-                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                // 客户端已退出，重启后可能已更新版本
+                game_version.clear();
+                tokio::time::sleep(error_backoff()).await;
                 continue;
             };
 
@@ -184,89 +985,122 @@ pub fn spawn_lcu_poller(
                     let _ = tx.send(LcuState {
                         connected: false,
                         error: e,
+                        error_kind: Some(LcuErrorKind::LockfileInvalid),
+                        info: String::new(),
                         enemies: vec![],
                         teammates: vec![],
                         my_pos: String::new(),
                         lane_enemy_id: None,
+                        my_champ_slug: String::new(),
                         champion_lang: champion_lang.clone(),
+                        game_version: game_version.clone(),
+                        session_id: 0,
+                        is_aram: false,
+                        queue_id: 0,
+                        bench: vec![],
+                        intents: vec![],
+                        timer: None,
                         champion_data: None,
                         auth: None,
                     });
                     ctx.request_repaint();
-                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    tokio::time::sleep(error_backoff()).await;
                     continue;
                 }
             };
 
+            let restarted = last_port.is_some_and(|p| p != auth.port);
+            if reload.swap(false, Ordering::Relaxed) || restarted {
+                champ_cache.clear();
+                champion_lang = "unknown".to_string();
+                icon_data = None;
+                icons_loaded = false;
+                icons_retry_at = None;
+                game_version.clear();
+                my_summoner_id = 0;
+            }
+            if restarted {
+                // 客户端重启后可能换了账号，段位和名字都要重新查
+                teammate_rank_cache.clear();
+            }
+            last_port = Some(auth.port);
+
             // 加载英雄列表
             if champ_cache.is_empty() {
-                // 尝试 zh_CN
-                if let Ok(val) = lcu_get(&client, &auth, "/lol-game-data/assets/v1/champion-summary.json", Some(&[("locale", "zh_CN")])).await {
-                    if let Some(arr) = val.as_array() {
-                        for item in arr {
-                            if let Ok(c) = serde_json::from_value::<ChampionSummary>(item.clone()) {
-                                champ_cache.insert(c.id, c);
-                            }
-                        }
-                        let sample_name = champ_cache.values().next().map(|c| c.name.as_str()).unwrap_or("");
-                        champion_lang = if looks_like_chinese(sample_name) { "zh_CN".into() } else { "non_zh".into() };
+                // 按客户端版本依次尝试 locale（国际服 zh_CN → zh_TW → 默认，国服默认 → zh_CN），
+                // 都不是中文时退回客户端默认语言的结果
+                let mut fallback = HashMap::new();
+                for &locale in champion_locales() {
+                    let query = locale.map(|l| [("locale", l)]);
+                    let Ok(val) = lcu_get(&client, &auth, "/lol-game-data/assets/v1/champion-summary.json", query.as_ref().map(|q| &q[..])).await else {
+                        continue;
+                    };
+                    let champs: HashMap<i64, ChampionSummary> = val
+                        .as_array()
+                        .map(|arr| {
+                            arr.iter()
+                                .filter_map(|item| serde_json::from_value::<ChampionSummary>(item.clone()).ok())
+                                .map(|c| (c.id, c))
+                                .collect()
+                        })
+                        .unwrap_or_default();
+                    let sample_name = champs.values().next().map(|c| c.name.as_str()).unwrap_or("");
+                    if looks_like_chinese(sample_name) {
+                        champ_cache = champs;
+                        champion_lang = locale.unwrap_or("client_default").into();
+                        break;
                     }
-                }
-                // zh_CN 结果非中文时尝试 zh_TW（适配繁体中文客户端）
-                if champion_lang == "non_zh" {
-                    champ_cache.clear();
-                    if let Ok(val) = lcu_get(&client, &auth, "/lol-game-data/assets/v1/champion-summary.json", Some(&[("locale", "zh_TW")])).await {
-                        if let Some(arr) = val.as_array() {
-                            for item in arr {
-                                if let Ok(c) = serde_json::from_value::<ChampionSummary>(item.clone()) {
-                                    champ_cache.insert(c.id, c);
-                                }
-                            }
-                            let sample_name = champ_cache.values().next().map(|c| c.name.as_str()).unwrap_or("");
-                            if looks_like_chinese(sample_name) {
-                                champion_lang = "zh_TW".into();
-                            }
-                        }
+                    if locale.is_none() || fallback.is_empty() {
+                        fallback = champs;
                     }
                 }
-                // fallback
-                if champ_cache.is_empty() {
-                    if let Ok(val) = lcu_get(&client, &auth, "/lol-game-data/assets/v1/champion-summary.json", None).await {
-                        if let Some(arr) = val.as_array() {
-                            for item in arr {
-                                if let Ok(c) = serde_json::from_value::<ChampionSummary>(item.clone()) {
-                                    champ_cache.insert(c.id, c);
-                                }
-                            }
-                            champion_lang = "client_default".into();
-                        }
-                    }
+                if champ_cache.is_empty() && !fallback.is_empty() {
+                    champ_cache = fallback;
+                    champion_lang = "client_default".into();
                 }
             }
 
-            // 加载英雄图标（一次性）
-            if icon_data.is_none() && !champ_cache.is_empty() {
+            // 加载英雄图标（每次加载英雄列表后一次，一个都没拿到时下次轮询重试）
+            if !icons_loaded && !champ_cache.is_empty() && icons_retry_at.is_none_or(|t| std::time::Instant::now() >= t) {
                 let mut slug_to_id = HashMap::new();
                 let mut name_to_id = HashMap::new();
+                let mut slug_to_alias = HashMap::new();
+                let mut slug_to_roles = HashMap::new();
                 for (&id, champ) in &champ_cache {
                     if id <= 0 { continue; }
-                    slug_to_id.insert(to_opgg_slug(&champ.alias, &champ.name), id);
+                    let slug = to_opgg_slug(&champ.alias, &champ.name);
+                    if !champ.alias.is_empty() {
+                        slug_to_alias.insert(slug.clone(), champ.alias.clone());
+                    }
+                    if !champ.roles.is_empty() {
+                        slug_to_roles.insert(slug.clone(), champ.roles.clone());
+                    }
+                    slug_to_id.insert(slug, id);
                     name_to_id.insert(champ.name.clone(), id);
                 }
 
+                // 优先读本地缓存的 PNG，缺失的再从 LCU 下载并写入缓存
+                let dir = icons_dir();
+                let _ = std::fs::create_dir_all(&dir);
                 let mut icons = HashMap::new();
                 let ids: Vec<i64> = champ_cache.keys().copied().filter(|&id| id > 0).collect();
                 let mut set = tokio::task::JoinSet::new();
                 for id in ids {
                     let c = client.clone();
                     let a = auth.clone();
+                    let file = dir.join(format!("{id}.png"));
                     set.spawn(async move {
-                        let path = format!("/lol-game-data/assets/v1/champion-icons/{id}.png");
-                        let bytes = lcu_get_bytes(&c, &a, &path).await.ok()?;
-                        let img = image::load_from_memory(&bytes).ok()?;
-                        let rgba = img.to_rgba8();
-                        let (w, h) = rgba.dimensions();
-                        Some((id, rgba.into_raw(), w, h))
+                        let bytes = match std::fs::read(&file) {
+                            Ok(b) => b,
+                            Err(_) => {
+                                let path = format!("/lol-game-data/assets/v1/champion-icons/{id}.png");
+                                let b = lcu_get_bytes(&c, &a, &path).await.ok()?;
+                                let _ = std::fs::write(&file, &b);
+                                b
+                            }
+                        };
+                        let (rgba, w, h) = decode_icon(&bytes)?;
+                        Some((id, rgba, w, h))
                     });
                 }
                 while let Some(result) = set.join_next().await {
@@ -274,15 +1108,44 @@ pub fn spawn_lcu_poller(
                         icons.insert(id, (rgba, w, h));
                     }
                 }
+                icons_loaded = !icons.is_empty();
+                icons_retry_at = (!icons_loaded).then(|| std::time::Instant::now() + ICON_RETRY_AFTER);
 
                 let mut id_to_name = HashMap::new();
+                let mut index = IconIndex::new();
                 for (&id, champ) in &champ_cache {
                     if id > 0 {
                         id_to_name.insert(id, champ.name.clone());
+                        index.insert(id, IconIndexEntry {
+                            slug: to_opgg_slug(&champ.alias, &champ.name),
+                            name: champ.name.clone(),
+                            alias: champ.alias.clone(),
+                            roles: champ.roles.clone(),
+                        });
                     }
                 }
+                if let Ok(json) = serde_json::to_string(&index) {
+                    let _ = std::fs::write(dir.join("index.json"), json);
+                }
 
-                icon_data = Some(ChampionIconData { icons, slug_to_id, name_to_id, id_to_name });
+                let locale: Option<&[(&str, &str)]> = match champion_lang.as_str() {
+                    "zh_CN" => Some(&[("locale", "zh_CN")]),
+                    "zh_TW" => Some(&[("locale", "zh_TW")]),
+                    _ => None,
+                };
+                let item_names = lcu_get_names(&client, &auth, "/lol-game-data/assets/v1/items.json", locale).await;
+                let spell_names = lcu_get_names(&client, &auth, "/lol-game-data/assets/v1/summoner-spells.json", locale).await;
+
+                icon_data = Some(ChampionIconData {
+                    icons, slug_to_id, name_to_id, id_to_name, slug_to_alias, slug_to_roles, item_names, spell_names,
+                });
+            }
+
+            // 客户端版本（一次性）
+            if game_version.is_empty() {
+                if let Ok(v) = lcu_get(&client, &auth, "/lol-patch/v1/game-version", None).await {
+                    game_version = v.as_str().map(crate::types::short_patch).unwrap_or_default();
+                }
             }
 
             // 缓存我的 summonerId（用于判断队伍归属）
@@ -294,37 +1157,58 @@ pub fn spawn_lcu_poller(
 
             // 获取选人 session
             match lcu_get(&client, &auth, "/lol-champ-select/v1/session", None).await {
+                // lcu_get 的 HTTP 状态错误以 "HTTP " 开头，其余都是请求没发出去
+                Err(e) if !e.starts_with("HTTP ") => {
+                    conn_failures += 1;
+                    if conn_failures.is_multiple_of(LCU_CLIENT_REBUILD_AFTER) {
+                        // 重新建立连接池再试
+                        client = lcu_client();
+                    }
+                    let error = match e.strip_prefix(TLS_ERROR_PREFIX) {
+                        Some(tls) if conn_failures >= LCU_CLIENT_REBUILD_AFTER => {
+                            format!("LCU TLS 握手失败，请以管理员身份运行或检查防火墙（{tls}）")
+                        }
+                        Some(tls) => format!("连接客户端失败：{tls}"),
+                        None => format!("连接客户端失败：{e}"),
+                    };
+                    let _ = tx.send(LcuState {
+                        connected: false,
+                        error,
+                        error_kind: None,
+                        info: String::new(),
+                        enemies: vec![],
+                        teammates: vec![],
+                        my_pos: String::new(),
+                        lane_enemy_id: None,
+                        my_champ_slug: String::new(),
+                        champion_lang: champion_lang.clone(),
+                        game_version: game_version.clone(),
+                        session_id: 0,
+                        is_aram: false,
+                        queue_id: 0,
+                        bench: vec![],
+                        intents: vec![],
+                        timer: None,
+                        champion_data: None,
+                        auth: None,
+                    });
+                    ctx.request_repaint();
+                    tokio::time::sleep(error_backoff()).await;
+                }
                 Ok(sess) => {
+                    conn_failures = 0;
                     let their_team = sess.get("theirTeam").and_then(|v| v.as_array()).cloned().unwrap_or_default();
                     let my_team = sess.get("myTeam").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-                    let local_cell = sess.get("localPlayerCellId").and_then(|v| v.as_i64());
-
-                    // 找我的位置
-                    let mut my_pos = String::new();
-                    if let Some(cell) = local_cell {
-                        for p in &my_team {
-                            if p.get("cellId").and_then(|v| v.as_i64()) == Some(cell) {
-                                my_pos = p.get("assignedPosition").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                                break;
-                            }
+                    let ChampSelectInfo { my_pos, my_champion_id, enemies, lane_enemy_id, is_aram, bench, intents } =
+                        parse_champ_select(&sess, &champ_cache);
+                    let session_id = sess.get("gameId").and_then(|v| v.as_i64()).unwrap_or(0);
+                    if session_queue.0 != session_id || session_queue.1 == 0 {
+                        if let Ok(gf) = lcu_get(&client, &auth, "/lol-gameflow/v1/session", None).await {
+                            session_queue = (session_id, parse_queue_id(&gf));
                         }
                     }
-
-                    // 构建敌方列表
-                    let mut enemies = Vec::new();
-                    for p in &their_team {
-                        let cid = p.get("championId").and_then(|v| v.as_i64()).unwrap_or(0);
-                        let champ = champ_cache.get(&cid);
-                        let name = champ.map(|c| c.name.as_str()).unwrap_or("未知英雄").to_string();
-                        let slug = champ.map(|c| to_opgg_slug(&c.alias, &c.name)).unwrap_or_default();
-                        let pos = p.get("assignedPosition").and_then(|v| v.as_str()).unwrap_or("").to_string();
-                        enemies.push(EnemyInfo {
-                            champion_id: cid,
-                            name,
-                            slug,
-                            pos,
-                        });
-                    }
+                    let queue_id = if session_queue.0 == session_id { session_queue.1 } else { 0 };
+                    let is_aram = is_aram || crate::types::queue_mode_slug(queue_id) == Some("aram");
 
                     // 构建全部玩家列表（并发获取未缓存的召唤师信息）
                     let mut teammates = Vec::new();
@@ -401,28 +1285,32 @@ pub fn spawn_lcu_poller(
                         }
                     }
 
-                    // 找对线对手
-                    let lane_enemy_id = if !my_pos.is_empty() {
-                        enemies.iter().find(|e| e.pos == my_pos).map(|e| e.champion_id)
-                    } else {
-                        None
-                    };
-
                     let _ = tx.send(LcuState {
                         connected: true,
                         error: String::new(),
+                        error_kind: None,
+                        info: String::new(),
                         enemies,
                         teammates,
                         my_pos,
                         lane_enemy_id,
+                        my_champ_slug: champ_slug(&champ_cache, my_champion_id),
                         champion_lang: champion_lang.clone(),
+                        game_version: game_version.clone(),
+                        session_id,
+                        is_aram,
+                        queue_id,
+                        bench,
+                        intents,
+                        timer: parse_timer(&sess),
                         champion_data: icon_data.take(),
                         auth: Some(auth.clone()),
                     });
                     ctx.request_repaint();
-                    tokio::time::sleep(std::time::Duration::from_millis(900)).await;
+                    tokio::time::sleep(poll_interval()).await;
                 }
                 Err(e) => {
+                    conn_failures = 0;
                     // 不在选人界面，检查是否已进入游戏
                     let mut handled = false;
                     if my_summoner_id > 0 {
@@ -430,6 +1318,11 @@ pub fn spawn_lcu_poller(
                             let phase = gf.get("phase").and_then(|v| v.as_str()).unwrap_or("");
                             if matches!(phase, "InProgress" | "GameStart" | "Reconnect" | "WaitingForStats") {
                                 let game_data = gf.get("gameData");
+                                let is_aram = game_data
+                                    .and_then(|g| g.get("queue"))
+                                    .and_then(|q| q.get("gameMode"))
+                                    .and_then(|v| v.as_str())
+                                    == Some("ARAM");
                                 let team_one = game_data.and_then(|g| g.get("teamOne")).and_then(|v| v.as_array()).cloned().unwrap_or_default();
                                 let team_two = game_data.and_then(|g| g.get("teamTwo")).and_then(|v| v.as_array()).cloned().unwrap_or_default();
 
@@ -450,6 +1343,7 @@ pub fn spawn_lcu_poller(
 
                                 // 构建全部玩家列表
                                 let mut my_pos = String::new();
+                                let mut my_champion_id = 0;
                                 let mut teammates = Vec::new();
                                 let mut to_fetch: Vec<(i64, i64, String, String, bool)> = Vec::new();
                                 for (team, is_ally) in [(my_team, true), (their_team, false)] {
@@ -459,7 +1353,10 @@ pub fn spawn_lcu_poller(
                                         let pos = p.get("selectedPosition").and_then(|v| v.as_str()).unwrap_or("").to_string();
                                         let sname = p.get("summonerName").and_then(|v| v.as_str()).unwrap_or("").to_string();
                                         if sid <= 0 { continue; }
-                                        if is_ally && sid == my_summoner_id { my_pos = pos.clone(); }
+                                        if is_ally && sid == my_summoner_id {
+                                            my_pos = pos.clone();
+                                            my_champion_id = cid;
+                                        }
                                         let champ_name = champ_cache.get(&cid).map(|c| c.name.clone()).unwrap_or_default();
                                         if let Some((cached_name, tag, puuid, account_id, tier, div, lp)) = teammate_rank_cache.get(&sid) {
                                             let display_name = if !sname.is_empty() { sname } else { cached_name.clone() };
@@ -541,16 +1438,26 @@ pub fn spawn_lcu_poller(
                                 let _ = tx.send(LcuState {
                                     connected: true,
                                     error: String::new(),
+                                    error_kind: None,
+                                    info: String::new(),
                                     enemies,
                                     teammates,
                                     my_pos,
                                     lane_enemy_id,
+                                    my_champ_slug: champ_slug(&champ_cache, my_champion_id),
                                     champion_lang: champion_lang.clone(),
+                                    game_version: game_version.clone(),
+                                    session_id: 0,
+                                    is_aram,
+                                    queue_id: parse_queue_id(&gf),
+                                    bench: vec![],
+                                    intents: vec![],
+                                    timer: None,
                                     champion_data: icon_data.take(),
                                     auth: Some(auth.clone()),
                                 });
                                 ctx.request_repaint();
-                                tokio::time::sleep(std::time::Duration::from_millis(900)).await;
+                                tokio::time::sleep(poll_interval()).await;
                                 handled = true;
                             }
                         }
@@ -558,17 +1465,28 @@ pub fn spawn_lcu_poller(
                     if !handled {
                         let _ = tx.send(LcuState {
                             connected: true,
-                            error: format!("不在选人界面：{}", &e[..e.len().min(140)]),
+                            error: String::new(),
+                            error_kind: None,
+                            info: format!("不在选人界面：{}", e.chars().take(140).collect::<String>()),
                             enemies: vec![],
                             teammates: vec![],
                             my_pos: String::new(),
                             lane_enemy_id: None,
+                            my_champ_slug: String::new(),
                             champion_lang: champion_lang.clone(),
+                            game_version: game_version.clone(),
+                            session_id: 0,
+                            is_aram: false,
+                            queue_id: 0,
+                            bench: vec![],
+                            intents: vec![],
+                            timer: None,
                             champion_data: icon_data.take(),
                             auth: Some(auth.clone()),
                         });
                         ctx.request_repaint();
-                        tokio::time::sleep(std::time::Duration::from_millis(1200)).await;
+                        // 不在选人 / 游戏中，状态变化不急，稍慢一些
+                        tokio::time::sleep(poll_interval().max(Duration::from_millis(1200))).await;
                     }
                 }
             }
@@ -576,3 +1494,203 @@ pub fn spawn_lcu_poller(
     });
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn champ(id: i64, alias: &str, name: &str) -> (i64, ChampionSummary) {
+        (id, ChampionSummary { id, name: name.into(), alias: alias.into(), roles: vec![] })
+    }
+
+    fn champ_cache() -> HashMap<i64, ChampionSummary> {
+        HashMap::from([
+            champ(103, "Ahri", "阿狸"),
+            champ(238, "Zed", "劫"),
+            champ(64, "LeeSin", "盲僧"),
+            champ(222, "Jinx", "金克丝"),
+        ])
+    }
+
+    /// 我在中路；敌方中路、打野各一人，their_team 按 cell 给出当前英雄
+    fn session(mid: i64, jungle: i64) -> serde_json::Value {
+        json!({
+            "localPlayerCellId": 0,
+            "myTeam": [
+                { "cellId": 0, "assignedPosition": "MIDDLE", "championId": 103 },
+            ],
+            "theirTeam": [
+                { "cellId": 5, "assignedPosition": "MIDDLE", "championId": mid },
+                { "cellId": 6, "assignedPosition": "JUNGLE", "championId": jungle },
+            ],
+            "actions": [],
+        })
+    }
+
+    #[test]
+    fn lockfile_valid() {
+        let auth = parse_lockfile("LeagueClient:12345:54321:s3cr3t:https\r\n").unwrap();
+        assert_eq!(auth.port, 54321);
+        assert_eq!(auth.password, "s3cr3t");
+    }
+
+    #[test]
+    fn lockfile_name_with_colon() {
+        let auth = parse_lockfile("League:Client:12345:54321:s3cr3t:https").unwrap();
+        assert_eq!(auth.port, 54321);
+        assert_eq!(auth.password, "s3cr3t");
+    }
+
+    #[test]
+    fn lockfile_too_few_fields() {
+        let err = parse_lockfile("LeagueClient:12345:54321").unwrap_err();
+        assert!(err.contains("格式不符"), "{err}");
+        assert!(parse_lockfile("").is_err());
+    }
+
+    #[test]
+    fn lockfile_bad_port() {
+        let err = parse_lockfile("LeagueClient:12345:port:s3cr3t:https").unwrap_err();
+        assert!(err.contains("端口"), "{err}");
+        assert!(!err.contains("s3cr3t"), "错误信息不应包含密码：{err}");
+        assert!(parse_lockfile("LeagueClient:12345:0:s3cr3t:https").is_err());
+        assert!(parse_lockfile("LeagueClient:12345:70000:s3cr3t:https").is_err());
+    }
+
+    #[test]
+    fn lane_enemy_follows_trade() {
+        let cache = champ_cache();
+
+        let before = parse_champ_select(&session(238, 64), &cache);
+        assert_eq!(before.my_pos, "MIDDLE");
+        assert_eq!(before.my_champion_id, 103);
+        let slugs: Vec<&str> = before.enemies.iter().map(|e| e.slug.as_str()).collect();
+        assert_eq!(slugs, ["zed", "leesin"]);
+        assert_eq!(before.lane_enemy_id, Some(238));
+        assert!(!before.is_aram);
+        assert!(before.bench.is_empty());
+
+        // 敌方中野交换英雄：位置不变，championId 互换
+        let after = parse_champ_select(&session(64, 238), &cache);
+        let enemies: Vec<(&str, &str)> = after.enemies.iter().map(|e| (e.pos.as_str(), e.name.as_str())).collect();
+        assert_eq!(enemies, [("MIDDLE", "盲僧"), ("JUNGLE", "劫")]);
+        assert_eq!(after.lane_enemy_id, Some(64));
+    }
+
+    #[test]
+    fn unpicked_enemy_is_placeholder() {
+        let info = parse_champ_select(&session(0, 64), &champ_cache());
+        assert_eq!(info.enemies[0].name, "未知英雄");
+        assert!(info.enemies[0].slug.is_empty());
+    }
+
+    #[test]
+    fn aram_bench_both_formats() {
+        let cache = champ_cache();
+        let current = json!({
+            "benchEnabled": true,
+            "benchChampions": [{ "championId": 222 }, { "championId": 64 }],
+            "myTeam": [], "theirTeam": [],
+        });
+        let info = parse_champ_select(&current, &cache);
+        assert!(info.is_aram);
+        assert_eq!(info.bench, [222, 64]);
+        assert_eq!(info.lane_enemy_id, None);
+
+        let legacy = json!({ "benchEnabled": true, "benchChampionIds": [238], "myTeam": [], "theirTeam": [] });
+        assert_eq!(parse_champ_select(&legacy, &cache).bench, [238]);
+    }
+
+    #[test]
+    fn icon_index_formats() {
+        // 旧版 [slug, 名称] 数组
+        let old: IconIndex = serde_json::from_str(r#"{"103":["ahri","阿狸"]}"#).unwrap();
+        assert_eq!(old[&103], IconIndexEntry { slug: "ahri".into(), name: "阿狸".into(), alias: String::new(), roles: vec![] });
+
+        let mut index = IconIndex::new();
+        index.insert(62, IconIndexEntry {
+            slug: "monkeyking".into(),
+            name: "齐天大圣".into(),
+            alias: "MonkeyKing".into(),
+            roles: vec!["fighter".into(), "tank".into()],
+        });
+        let json = serde_json::to_string(&index).unwrap();
+        assert_eq!(serde_json::from_str::<IconIndex>(&json).unwrap(), index);
+    }
+
+    /// 向本机端口发 HTTPS 请求，返回请求错误
+    async fn request_local(port: u16) -> reqwest::Error {
+        lcu_client().get(format!("https://127.0.0.1:{port}/")).send().await.unwrap_err()
+    }
+
+    #[test]
+    fn tls_error_detection() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            // 对端不说 TLS：握手失败
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                use tokio::io::AsyncWriteExt;
+                let (mut sock, _) = listener.accept().await.unwrap();
+                let _ = sock.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+            });
+            let e = request_local(port).await;
+            assert!(is_tls_error(&e), "{e:?}");
+            assert!(request_error(e).starts_with(TLS_ERROR_PREFIX));
+
+            // 没有进程监听：连接被拒，不是 TLS 问题
+            let port = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap().port();
+            let e = request_local(port).await;
+            assert!(!is_tls_error(&e), "{e:?}");
+        });
+    }
+
+    #[test]
+    fn install_dirs_dedup() {
+        let dirs = vec![
+            PathBuf::from(r"D:\Games\LoL"),
+            PathBuf::from(r"C:\Riot Games\League of Legends"),
+            PathBuf::from(r"d:\games\lol\"),
+            PathBuf::from("D:/Games/LoL"),
+        ];
+        assert_eq!(dedup_dirs(dirs), [PathBuf::from(r"D:\Games\LoL"), PathBuf::from(r"C:\Riot Games\League of Legends")]);
+    }
+
+    #[test]
+    fn league_dir_detection() {
+        let dir = std::env::temp_dir().join(format!("lol-helper-test-{}", std::process::id())).join("LoL");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(!is_league_dir(&dir));
+        std::fs::write(dir.join("LeagueClient.exe"), b"").unwrap();
+        assert!(is_league_dir(&dir));
+        std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn rune_page_checked_against_client_styles() {
+        let styles = json!([
+            { "id": 8100, "slots": [
+                { "type": "kKeyStone", "perks": [8112, 8128] },
+                { "type": "kMixedRegularSplashable", "perks": [8126, 8139] },
+                { "type": "kMixedRegularSplashable", "perks": [8136, 8120] },
+                { "type": "kMixedRegularSplashable", "perks": [8135, 8105] },
+                { "type": "kStatMod", "perks": [5008, 5005, 5002] },
+            ]},
+            { "id": 8200, "slots": [
+                { "type": "kKeyStone", "perks": [8214] },
+                { "type": "kMixedRegularSplashable", "perks": [8226, 8275] },
+                { "type": "kStatMod", "perks": [5008, 5005, 5002] },
+            ]},
+        ]);
+        let page = |perks: Vec<i64>| RunePage { primary_style: 8100, sub_style: 8200, perks };
+        assert_eq!(check_rune_page(&styles, &page(vec![8112, 8126, 8136, 8135, 8226, 8275, 5008, 5005, 5002])), Ok(()));
+        // 副系位置放了主系符文
+        assert!(check_rune_page(&styles, &page(vec![8112, 8126, 8136, 8135, 8139, 8275, 5008, 5005, 5002])).is_err());
+        // 客户端不认识的符文
+        assert!(check_rune_page(&styles, &page(vec![8112, 8126, 8136, 8135, 8226, 8275, 5008, 5005, 5099])).is_err());
+        // 读不到客户端数据时不拦截
+        assert_eq!(check_rune_page(&json!({}), &page(vec![1, 2, 3])), Ok(()));
+    }
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+const LIVE_URL: &str = "https://127.0.0.1:2999/liveclientdata/allgamedata";
+
+/// 对局中玩家实时数据
+#[derive(Debug, Clone)]
+pub struct LivePlayer {
+    pub name: String,
+    pub champion_name: String,
+    pub level: i64,
+    pub kills: i64,
+    pub deaths: i64,
+    pub assists: i64,
+    pub creep_score: i64,
+    /// 装备名
+    pub items: Vec<String>,
+    /// 按装备售价估算的经济
+    pub item_gold: i64,
+    pub is_enemy: bool,
+}
+
+/// Live Client Data 状态更新消息
+#[derive(Debug, Clone, Default)]
+pub struct LiveGameState {
+    pub in_game: bool,
+    pub game_time: f64,
+    pub players: Vec<LivePlayer>,
+}
+
+/// 定时刷新的地图资源（按固定时间表提醒，不跟踪实际击杀）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Objective {
+    Scuttle,
+    Dragon,
+    Grubs,
+    Herald,
+    Baron,
+}
+
+impl Objective {
+    pub const ALL: [Objective; 5] = [
+        Objective::Scuttle,
+        Objective::Dragon,
+        Objective::Grubs,
+        Objective::Herald,
+        Objective::Baron,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Objective::Scuttle => "河蟹",
+            Objective::Dragon => "小龙",
+            Objective::Grubs => "虚空巢虫",
+            Objective::Herald => "峡谷先锋",
+            Objective::Baron => "大龙",
+        }
+    }
+
+    /// (首次刷新时间, 之后的提醒间隔)，单位秒；间隔为 0 表示只提醒一次
+    fn schedule(self) -> (f64, f64) {
+        match self {
+            Objective::Scuttle => (195.0, 150.0),
+            Objective::Dragon => (300.0, 300.0),
+            Objective::Grubs => (360.0, 0.0),
+            Objective::Herald => (840.0, 0.0),
+            Objective::Baron => (1200.0, 360.0),
+        }
+    }
+
+    /// 当前时间之后（含刚过去 `after` 秒内）的下一次刷新时间
+    fn next_spawn(self, game_time: f64, after: f64) -> Option<f64> {
+        let (first, interval) = self.schedule();
+        let t = game_time - after;
+        if t <= first {
+            return Some(first);
+        }
+        if interval <= 0.0 {
+            return None;
+        }
+        Some(first + ((t - first) / interval).ceil() * interval)
+    }
+}
+
+/// 刷新后提醒横幅继续显示的秒数
+const REMINDER_LINGER_SECS: f64 = 10.0;
+
+/// 需要提醒的资源及距离刷新的秒数（负数表示刚刷新），按时间排序
+///
+/// 在刷新前 `lead_secs` 秒到刷新后 10 秒内返回。
+pub fn due_reminders(game_time: f64, enabled: &[Objective], lead_secs: f64) -> Vec<(Objective, f64)> {
+    let mut due: Vec<(Objective, f64)> = enabled
+        .iter()
+        .filter_map(|&o| {
+            let until = o.next_spawn(game_time, REMINDER_LINGER_SECS)? - game_time;
+            (until <= lead_secs).then_some((o, until))
+        })
+        .collect();
+    due.sort_by(|a, b| a.1.total_cmp(&b.1));
+    due
+}
+
+/// 解析 allgamedata
+fn parse_all_game_data(val: &Value) -> LiveGameState {
+    let active = val.get("activePlayer");
+    let active_name = active
+        .and_then(|a| a.get("riotId").or(a.get("summonerName")))
+        .and_then(|v| v.as_str())
+        .unwrap_or("");
+    let players_raw = val.get("allPlayers").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+
+    let player_name = |p: &Value| -> String {
+        p.get("riotId")
+            .or(p.get("summonerName"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    };
+    let my_team = players_raw
+        .iter()
+        .find(|p| player_name(p) == active_name)
+        .and_then(|p| p.get("team"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("ORDER")
+        .to_string();
+
+    let mut players = Vec::new();
+    for p in &players_raw {
+        let scores = p.get("scores");
+        let score = |k: &str| scores.and_then(|s| s.get(k)).and_then(|v| v.as_i64()).unwrap_or(0);
+        let items_raw = p.get("items").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+        let items = items_raw
+            .iter()
+            .filter_map(|i| i.get("displayName").and_then(|v| v.as_str()).map(String::from))
+            .collect();
+        let item_gold = items_raw
+            .iter()
+            .map(|i| {
+                let price = i.get("price").and_then(|v| v.as_i64()).unwrap_or(0);
+                let count = i.get("count").and_then(|v| v.as_i64()).unwrap_or(1);
+                price * count
+            })
+            .sum();
+        let team = p.get("team").and_then(|v| v.as_str()).unwrap_or("");
+        players.push(LivePlayer {
+            name: player_name(p),
+            champion_name: p.get("championName").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            level: p.get("level").and_then(|v| v.as_i64()).unwrap_or(0),
+            kills: score("kills"),
+            deaths: score("deaths"),
+            assists: score("assists"),
+            creep_score: score("creepScore"),
+            items,
+            item_gold,
+            is_enemy: team != my_team,
+        });
+    }
+
+    LiveGameState {
+        in_game: true,
+        game_time: val
+            .get("gameData")
+            .and_then(|g| g.get("gameTime"))
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0),
+        players,
+    }
+}
+
+/// 后台 Live Client Data 轮询任务（仅游戏进行中可用）
+pub fn spawn_live_poller(
+    rt: Arc<tokio::runtime::Runtime>,
+    tx: mpsc::UnboundedSender<LiveGameState>,
+    ctx: egui::Context,
+) {
+    rt.spawn(async move {
+        // 与 LCU 相同：本地自签名证书，不走代理
+        let client = crate::lcu::lcu_client();
+        let mut was_in_game = false;
+
+        loop {
+            let state = match client.get(LIVE_URL).send().await {
+                Ok(resp) if resp.status().is_success() => match resp.json::<Value>().await {
+                    Ok(val) => parse_all_game_data(&val),
+                    Err(_) => LiveGameState::default(),
+                },
+                _ => LiveGameState::default(),
+            };
+
+            let in_game = state.in_game;
+            // 不在游戏中时只在状态变化时通知一次
+            if in_game || was_in_game {
+                let _ = tx.send(state);
+                ctx.request_repaint();
+            }
+            was_in_game = in_game;
+
+            let interval = if in_game { 1000 } else { 3000 };
+            tokio::time::sleep(std::time::Duration::from_millis(interval)).await;
+        }
+    });
+}
@@ -3,15 +3,22 @@
 mod app;
 mod config;
 mod lcu;
+mod live;
 mod openai;
 mod opgg;
+mod tips;
 mod types;
 mod win32;
 
 use std::sync::Arc;
 
 fn main() {
+    let created_config = config::ensure_config_file();
     let config = config::load_config();
+    opgg::configure_site(&config.opgg_locale, &config.opgg_region, config.client_variant);
+    lcu::configure_timing(config.lcu_timeout_secs, config.lcu_poll_ms);
+    lcu::configure_client_variant(config.client_variant);
+    openai::configure_debug_header(config.show_ai_debug_header);
     let rt = Arc::new(
         tokio::runtime::Builder::new_multi_thread()
             .enable_all()
@@ -31,7 +38,7 @@ fn main() {
     eframe::run_native(
         &title,
         options,
-        Box::new(move |cc| Ok(Box::new(app::App::new(cc, config, rt_clone)))),
+        Box::new(move |cc| Ok(Box::new(app::App::new(cc, config, rt_clone, created_config)))),
     )
     .expect("Failed to start eframe");
 }
@@ -1,35 +1,116 @@
-use crate::config::AiEngine;
+use crate::config::{AiEngine, PromptTemplate};
 use crate::types::pos_cn;
 use tokio::sync::mpsc;
 
+/// 本次请求的 token 用量
+#[derive(Debug, Clone, Copy)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
 /// 流式 AI 分析消息
 pub enum AiStreamMsg {
     /// 追加文本片段
     Chunk(String),
-    /// 流结束，附带完整文本用于缓存
-    Done(String),
+    /// 流结束，附带完整文本用于缓存，以及接口返回的 token 用量
+    Done(String, Option<TokenUsage>),
     /// 错误
     Error(String),
+    /// 暂时性错误（429/5xx），可换备用模型重试
+    Retryable(String),
+}
+
+/// 调试头结尾分隔线（紧凑模式下据此隐藏调试头）
+pub const DEBUG_HEADER_END: &str = "────────────────\n\n";
+
+/// 是否为可重试的暂时性状态码（限流 / 服务过载）
+fn is_transient(status: reqwest::StatusCode) -> bool {
+    matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504)
+}
+
+/// 按引擎配置构建 POST/GET 请求：Authorization + extra_headers
+///
+/// 请求头名称或值不合法（如含中文）时返回错误，不发送请求。
+fn engine_request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    url: &str,
+    engine: &AiEngine,
+) -> Result<reqwest::RequestBuilder, String> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    for (name, value) in &engine.extra_headers {
+        let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("引擎 {} 的请求头名称无效：{name}", engine.name))?;
+        let value = reqwest::header::HeaderValue::from_str(value)
+            .map_err(|_| format!("引擎 {} 的请求头 {name} 值无效（只能包含 ASCII 字符）", engine.name))?;
+        headers.insert(name, value);
+    }
+    Ok(client
+        .request(method, url)
+        .header("Authorization", format!("Bearer {}", engine.api_key))
+        .headers(headers))
 }
 
-/// 构建提示词（返回 system_prompt, user_prompt）
-fn build_prompts(
-    my_champ: &str,
-    enemy_champ: &str,
-    position: &str,
-    win_rate: f64,
-) -> (String, String) {
-    let system = "你是一个英雄联盟高分段对线分析师。回答要求：针对具体对局给出实战建议，出装要具体到装备名称，优劣势要结合出装和玩法一起说。用简洁中文回答。".to_string();
+/// 内置 system prompt
+const DEFAULT_SYSTEM_PROMPT: &str = "你是一个英雄联盟高分段对线分析师。回答要求：针对具体对局给出实战建议，出装要具体到装备名称，优劣势要结合出装和玩法一起说。用简洁中文回答。";
 
+/// 打野的内置 system prompt：关注刷野、节奏和资源，而不是对线换血
+const JUNGLE_SYSTEM_PROMPT: &str = "你是一个英雄联盟高分段打野教练。回答要求：针对具体打野对位给出实战建议，刷野路线要具体到野怪顺序和时间点，结合双方清野速度、前期强度和抓人能力分析。用简洁中文回答。";
+
+/// 一次对位分析请求
+#[derive(Debug, Clone)]
+pub struct AiRequest {
+    pub my_champ: String,
+    pub enemy_champ: String,
+    /// OP.GG 位置名（可为空）
+    pub position: String,
+    /// 对位胜率（%），没有对位数据时为 None
+    pub win_rate: Option<f64>,
+    /// None 时使用内置模板
+    pub template: Option<PromptTemplate>,
+    /// 配置中的 system prompt，模板没有自己的 system prompt 时使用（为空使用内置）
+    pub system_prompt: String,
+}
+
+/// 构建提示词（返回 system_prompt, user_prompt），template 为 None 时使用内置模板（打野单独一套）
+///
+/// system prompt 依次取：模板自己的 → 配置的 system_prompt → 内置（打野单独一套）。
+fn build_prompts(req: &AiRequest) -> (String, String) {
+    let AiRequest { my_champ, enemy_champ, position, win_rate, .. } = req;
     let pos_text = pos_cn(position);
     let pos_text = if pos_text.is_empty() { "未知位置" } else { pos_text };
+    let system = |builtin: &str| {
+        [req.template.as_ref().map_or("", |t| t.system_prompt.as_str()), req.system_prompt.as_str()]
+            .into_iter()
+            .find(|s| !s.trim().is_empty())
+            .unwrap_or(builtin)
+            .to_string()
+    };
+
+    if let Some(t) = &req.template {
+        let wr_text = win_rate.map_or("未知".into(), |wr| format!("{wr:.1}%"));
+        let prompt = t
+            .prompt
+            .replace("{my_champ}", my_champ)
+            .replace("{enemy_champ}", enemy_champ)
+            .replace("{position}", pos_text)
+            .replace("{win_rate}", &wr_text);
+        return (system(DEFAULT_SYSTEM_PROMPT), prompt);
+    }
+
+    if position == "JUNGLE" {
+        return (system(JUNGLE_SYSTEM_PROMPT), jungle_prompt(my_champ, enemy_champ, *win_rate));
+    }
+
+    let system = system(DEFAULT_SYSTEM_PROMPT);
 
     let mut prompt = format!(
         "我在英雄联盟中使用【{my_champ}】在{pos_text}对线【{enemy_champ}】。"
     );
-    if win_rate > 0.0 {
+    if let Some(wr) = win_rate {
         prompt += &format!(
-            "\n根据数据，{my_champ} 对 {enemy_champ} 的胜率为 {win_rate:.1}%。"
+            "\n根据数据，{my_champ} 对 {enemy_champ} 的胜率为 {wr:.1}%。"
         );
     }
     prompt += "\n\n请用中文简洁分析：\n\
@@ -43,21 +124,41 @@ fn build_prompts(
     (system, prompt)
 }
 
+/// 打野对位的内置提示词：刷野路线、抓人时机、资源控制
+fn jungle_prompt(my_champ: &str, enemy_champ: &str, win_rate: Option<f64>) -> String {
+    let mut prompt = format!("我在英雄联盟中使用【{my_champ}】打野，对面打野是【{enemy_champ}】。");
+    if let Some(wr) = win_rate {
+        prompt += &format!("\n根据数据，{my_champ} 对 {enemy_champ} 的胜率为 {wr:.1}%。");
+    }
+    prompt += "\n\n请用中文简洁分析：\n\
+         1. 清野速度和前期强度对比（谁更快到 3/6 级，能否反野、入侵时机）\n\
+         2. 推荐开局刷野路线（起始野怪、第一次抓人或回家的时间点）\n\
+         3. 抓人窗口（优先帮哪条路、什么时候去、如何避开对方打野的反蹲）\n\
+         4. 资源控制（河蟹、小龙、巢虫/先锋的争夺和让换时机）\n\
+         5. 出装、符文和惩戒/召唤师技能\n\
+         请保持简洁实用。";
+    prompt
+}
+
 /// 流式调用 AI 分析（OpenAI 兼容接口）
 pub async fn call_ai_stream(
     engine: &AiEngine,
     model: &str,
-    my_champ: &str,
-    enemy_champ: &str,
-    position: &str,
-    win_rate: f64,
+    req: &AiRequest,
     chunk_tx: mpsc::UnboundedSender<AiStreamMsg>,
     ctx: egui::Context,
 ) {
-    let (system_prompt, user_prompt) = build_prompts(my_champ, enemy_champ, position, win_rate);
+    let (system_prompt, user_prompt) = build_prompts(req);
     call_ai_raw(engine, model, &system_prompt, &user_prompt, chunk_tx, ctx).await;
 }
 
+static SHOW_DEBUG_HEADER: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+
+/// 启动时按配置设置是否输出调试头（只生效一次，未调用时不输出）
+pub fn configure_debug_header(show: bool) {
+    let _ = SHOW_DEBUG_HEADER.set(show);
+}
+
 /// 通用流式调用（自定义 system/user prompt）
 pub async fn call_ai_raw(
     engine: &AiEngine,
@@ -67,31 +168,52 @@ pub async fn call_ai_raw(
     chunk_tx: mpsc::UnboundedSender<AiStreamMsg>,
     ctx: egui::Context,
 ) {
-    // 先发送调试头
-    let debug_header = format!(
-        "【引擎】{}\n【模型】{model}\n【Prompt】{user_prompt}\n\n────────────────\n\n",
-        engine.name
-    );
-    let _ = chunk_tx.send(AiStreamMsg::Chunk(debug_header.clone()));
-    ctx.request_repaint();
+    // 先发送调试头（关闭时不输出，缓存和保存的全文也不含）
+    let debug_header = if SHOW_DEBUG_HEADER.get().copied().unwrap_or(false) {
+        format!("【引擎】{}\n【模型】{model}\n【Prompt】{user_prompt}\n\n{DEBUG_HEADER_END}", engine.name)
+    } else {
+        String::new()
+    };
+    if !debug_header.is_empty() {
+        let _ = chunk_tx.send(AiStreamMsg::Chunk(debug_header.clone()));
+        ctx.request_repaint();
+    }
 
-    let payload = serde_json::json!({
+    let mut payload = serde_json::json!({
         "model": model,
         "messages": [
             { "role": "system", "content": system_prompt },
             { "role": "user", "content": user_prompt }
         ],
-        "max_completion_tokens": 4096,
         "stream": true,
     });
+    payload[engine.token_param_name.as_str()] = engine.max_tokens.into();
+    if let Some(t) = engine.temperature {
+        payload["temperature"] = t.into();
+    }
+    if engine.stream_usage {
+        payload["stream_options"] = serde_json::json!({ "include_usage": true });
+    }
 
-    let client = reqwest::Client::new();
-    let resp = client
-        .post(&engine.api_url)
+    let client = match crate::config::http_client(&engine.proxy_url, std::time::Duration::from_secs(60)) {
+        Ok(c) => c,
+        Err(e) => {
+            let _ = chunk_tx.send(AiStreamMsg::Error(e));
+            ctx.request_repaint();
+            return;
+        }
+    };
+    let request = match engine_request(&client, reqwest::Method::POST, &engine.api_url, engine) {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = chunk_tx.send(AiStreamMsg::Error(e));
+            ctx.request_repaint();
+            return;
+        }
+    };
+    let resp = request
         .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", engine.api_key))
         .json(&payload)
-        .timeout(std::time::Duration::from_secs(60))
         .send()
         .await;
 
@@ -100,14 +222,22 @@ pub async fn call_ai_raw(
             if !r.status().is_success() {
                 let status = r.status();
                 let body = r.text().await.unwrap_or_default();
-                let _ = chunk_tx.send(AiStreamMsg::Error(format!("请求失败 ({status})：{body}")));
+                let msg = format!("请求失败 ({status})：{body}");
+                let _ = chunk_tx.send(if is_transient(status) {
+                    AiStreamMsg::Retryable(msg)
+                } else {
+                    AiStreamMsg::Error(msg)
+                });
                 ctx.request_repaint();
                 return;
             }
             r
         }
         Err(e) => {
-            let _ = chunk_tx.send(AiStreamMsg::Error(format!("请求失败：{e}")));
+            let _ = chunk_tx.send(AiStreamMsg::Error(format!(
+                "请求失败：{}",
+                crate::config::net_error(&e, &engine.proxy_url)
+            )));
             ctx.request_repaint();
             return;
         }
@@ -116,11 +246,14 @@ pub async fn call_ai_raw(
     // 流式读取 SSE
     let mut full_text = debug_header;
     let mut buffer = String::new();
+    let mut usage: Option<TokenUsage> = None;
 
     loop {
         match resp.chunk().await {
             Ok(Some(bytes)) => {
                 buffer.push_str(&String::from_utf8_lossy(&bytes));
+                // 同一次读取到的多行 SSE 合并成一个片段发送（由转发方统一触发一次重绘）
+                let mut batch = String::new();
                 // 按行解析 SSE
                 while let Some(pos) = buffer.find('\n') {
                     let line = buffer[..pos].trim_end().to_string();
@@ -131,11 +264,21 @@ pub async fn call_ai_raw(
                     }
                     if let Some(data) = line.strip_prefix("data: ") {
                         if data.trim() == "[DONE]" {
-                            let _ = chunk_tx.send(AiStreamMsg::Done(full_text));
+                            if !batch.is_empty() {
+                                let _ = chunk_tx.send(AiStreamMsg::Chunk(batch));
+                            }
+                            let _ = chunk_tx.send(AiStreamMsg::Done(full_text, usage));
                             ctx.request_repaint();
                             return;
                         }
                         if let Ok(val) = serde_json::from_str::<serde_json::Value>(data) {
+                            // 开启 include_usage 时最后一个数据块带 usage
+                            if let Some(u) = val.get("usage").filter(|u| u.is_object()) {
+                                usage = Some(TokenUsage {
+                                    prompt_tokens: u.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+                                    completion_tokens: u.get("completion_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+                                });
+                            }
                             let content = val
                                 .get("choices")
                                 .and_then(|c| c.get(0))
@@ -143,18 +286,18 @@ pub async fn call_ai_raw(
                                 .and_then(|d| d.get("content"))
                                 .and_then(|c| c.as_str())
                                 .unwrap_or("");
-                            if !content.is_empty() {
-                                full_text.push_str(content);
-                                let _ = chunk_tx.send(AiStreamMsg::Chunk(content.to_string()));
-                                ctx.request_repaint();
-                            }
+                            full_text.push_str(content);
+                            batch.push_str(content);
                         }
                     }
                 }
+                if !batch.is_empty() {
+                    let _ = chunk_tx.send(AiStreamMsg::Chunk(batch));
+                }
             }
             Ok(None) => {
                 // 流结束
-                let _ = chunk_tx.send(AiStreamMsg::Done(full_text));
+                let _ = chunk_tx.send(AiStreamMsg::Done(full_text, usage));
                 ctx.request_repaint();
                 return;
             }
@@ -166,3 +309,131 @@ pub async fn call_ai_raw(
         }
     }
 }
+
+/// 连接测试 / 模型列表失败时按状态码给出说明
+fn describe_http_error(status: reqwest::StatusCode, body: &str) -> String {
+    let brief: String = body.chars().take(200).collect();
+    let hint = match status.as_u16() {
+        401 | 403 => "API Key 无效或没有权限",
+        404 => "接口地址或模型名不存在",
+        400 => "请求被拒绝（模型名或参数可能有误）",
+        429 => "请求过于频繁或额度不足",
+        500..=599 => "服务端错误，可稍后重试",
+        _ => "请求失败",
+    };
+    format!("{hint} ({status})：{brief}")
+}
+
+/// 测试引擎连接：发送一个极短的非流式请求，成功返回耗时
+pub async fn test_connection(engine: &AiEngine, model: &str) -> Result<std::time::Duration, String> {
+    let mut payload = serde_json::json!({
+        "model": model,
+        "messages": [{ "role": "user", "content": "ping" }],
+        "stream": false,
+    });
+    // 部分推理模型 token 上限过小会直接报错，留一点余量
+    payload[engine.token_param_name.as_str()] = 16.into();
+
+    let client = crate::config::http_client(&engine.proxy_url, std::time::Duration::from_secs(20))?;
+    let start = std::time::Instant::now();
+    let resp = engine_request(&client, reqwest::Method::POST, &engine.api_url, engine)?
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| format!("网络错误：{}", crate::config::net_error(&e, &engine.proxy_url)))?;
+    let elapsed = start.elapsed();
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(describe_http_error(status, &body));
+    }
+    Ok(elapsed)
+}
+
+/// 由 chat/completions 地址推出模型列表地址（…/v1/chat/completions → …/v1/models）
+fn models_url(api_url: &str) -> Option<String> {
+    let base = api_url.trim_end_matches('/').strip_suffix("/chat/completions")?;
+    Some(format!("{base}/models"))
+}
+
+/// 通过接口的 /models 获取可用模型列表（按名称排序），不支持时返回错误
+pub async fn list_models(engine: &AiEngine) -> Result<Vec<String>, String> {
+    let url = models_url(&engine.api_url).ok_or("接口地址不是 …/chat/completions 形式，无法推断模型列表地址")?;
+    let client = crate::config::http_client(&engine.proxy_url, std::time::Duration::from_secs(20))?;
+    let resp = engine_request(&client, reqwest::Method::GET, &url, engine)?
+        .send()
+        .await
+        .map_err(|e| format!("网络错误：{}", crate::config::net_error(&e, &engine.proxy_url)))?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        return Err(describe_http_error(status, &body));
+    }
+    let val: serde_json::Value = resp.json().await.map_err(|e| format!("模型列表解析失败：{e}"))?;
+    // OpenAI 格式为 data[].id，Ollama 等为 models[].name / id
+    let arr = val
+        .get("data")
+        .or_else(|| val.get("models"))
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let mut models: Vec<String> = arr
+        .iter()
+        .filter_map(|m| m.get("id").or_else(|| m.get("name")).and_then(|v| v.as_str()))
+        // Gemini 兼容接口返回 "models/gemini-…"
+        .map(|id| id.trim_start_matches("models/").to_string())
+        .collect();
+    if models.is_empty() {
+        return Err("接口未返回任何模型".into());
+    }
+    models.sort();
+    models.dedup();
+    Ok(models)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn req(position: &str, system_prompt: &str, template: Option<PromptTemplate>) -> AiRequest {
+        AiRequest {
+            my_champ: "阿狸".into(),
+            enemy_champ: "劫".into(),
+            position: position.into(),
+            win_rate: Some(47.25),
+            template,
+            system_prompt: system_prompt.into(),
+        }
+    }
+
+    fn template(system_prompt: &str) -> PromptTemplate {
+        PromptTemplate {
+            name: "t".into(),
+            prompt: "{my_champ} vs {enemy_champ} {position} {win_rate}".into(),
+            system_prompt: system_prompt.into(),
+        }
+    }
+
+    #[test]
+    fn system_prompt_precedence() {
+        assert_eq!(build_prompts(&req("MID", "", None)).0, DEFAULT_SYSTEM_PROMPT);
+        assert_eq!(build_prompts(&req("JUNGLE", "", None)).0, JUNGLE_SYSTEM_PROMPT);
+        // 配置的 system_prompt 覆盖内置（包括打野）
+        assert_eq!(build_prompts(&req("MID", "配置", None)).0, "配置");
+        assert_eq!(build_prompts(&req("JUNGLE", "配置", None)).0, "配置");
+        assert_eq!(build_prompts(&req("MID", "配置", Some(template("")))).0, "配置");
+        // 模板自己的 system prompt 优先
+        assert_eq!(build_prompts(&req("MID", "配置", Some(template("模板")))).0, "模板");
+        assert_eq!(build_prompts(&req("MID", " ", Some(template("")))).0, DEFAULT_SYSTEM_PROMPT);
+    }
+
+    #[test]
+    fn win_rate_placeholder() {
+        assert_eq!(build_prompts(&req("MID", "", Some(template("")))).1, "阿狸 vs 劫 中路 47.2%");
+        let mut unknown = req("MID", "", Some(template("")));
+        unknown.win_rate = None;
+        assert_eq!(build_prompts(&unknown).1, "阿狸 vs 劫 中路 未知");
+        unknown.template = None;
+        assert!(!build_prompts(&unknown).1.contains("胜率"));
+    }
+}
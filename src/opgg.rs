@@ -1,4 +1,4 @@
-use crate::types::{CounterDisplay, CounterEntry, MatchEntry, OpggCache};
+use crate::types::{ClientVariant, CounterDisplay, CounterEntry, CounterOverride, MatchDetail, MatchEntry, OpggCache, PoolThreat, PositionBreakdown, RunePage, SoloRank};
 use regex::Regex;
 use serde_json::Value;
 use std::path::PathBuf;
@@ -15,6 +15,29 @@ fn data_path() -> PathBuf {
         .join("opgg_data.json")
 }
 
+/// 手动覆盖的对位数据："slug:POS:对手slug" → 胜率/场次
+pub type CounterOverrides = std::collections::HashMap<String, CounterOverride>;
+
+fn overrides_path() -> PathBuf {
+    data_path().with_file_name("overrides.json")
+}
+
+/// 加载 overrides.json（不存在时为空）
+///
+/// 读取或解析失败时返回错误，调用方不应再保存，否则会用空数据覆盖用户的文件。
+pub fn load_overrides() -> Result<CounterOverrides, String> {
+    match std::fs::read_to_string(overrides_path()) {
+        Ok(content) => serde_json::from_str(&content).map_err(|e| format!("overrides.json 格式错误：{e}")),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(CounterOverrides::new()),
+        Err(e) => Err(format!("读取 overrides.json 失败：{e}")),
+    }
+}
+
+pub fn save_overrides(overrides: &CounterOverrides) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(overrides).map_err(|e| format!("序列化失败：{e}"))?;
+    std::fs::write(overrides_path(), json).map_err(|e| format!("写入 overrides.json 失败：{e}"))
+}
+
 /// 加载本地缓存
 pub fn load_local_data() -> OpggCache {
     let path = data_path();
@@ -24,6 +47,64 @@ pub fn load_local_data() -> OpggCache {
     }
 }
 
+/// 导入别人分享的 opgg_data.json：merge 为 true 时按条目合并（同一条目保留采集时间较新的），否则整体替换
+///
+/// 文件必须能解析为 OpggCache 且含克制数据，成功后写入本地缓存并返回新缓存。
+pub fn import_local_data(path: &std::path::Path, current: &OpggCache, merge: bool) -> Result<OpggCache, String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("读取文件失败：{e}"))?;
+    let imported: OpggCache =
+        serde_json::from_str(&content).map_err(|e| format!("不是有效的 OP.GG 数据文件（{e}）"))?;
+    if imported.counters.is_empty() || imported.counters.values().all(|c| c.is_empty()) {
+        return Err("文件中没有克制数据".into());
+    }
+    if !merge {
+        save_local_data(&imported);
+        return Ok(imported);
+    }
+
+    let mut cache = current.clone();
+    let entry_time = |c: &OpggCache, k: &str| c.counter_updated_at.get(k).copied().unwrap_or(c.updated_at);
+    for (key, counters) in &imported.counters {
+        if counters.is_empty() {
+            continue;
+        }
+        let newer = !cache.counters.contains_key(key) || entry_time(&imported, key) > entry_time(&cache, key);
+        if newer {
+            cache.counters.insert(key.clone(), counters.clone());
+            cache.counter_updated_at.insert(key.clone(), entry_time(&imported, key));
+        }
+    }
+    for (key, name) in &imported.champions {
+        cache.champions.entry(key.clone()).or_insert_with(|| name.clone());
+    }
+    let imported_newer = imported.updated_at > cache.updated_at;
+    for (key, wr) in &imported.baseline_win_rates {
+        if imported_newer || !cache.baseline_win_rates.contains_key(key) {
+            cache.baseline_win_rates.insert(key.clone(), *wr);
+        }
+    }
+    // 对方已确认为空页面的条目也带过来，免得合并后又算作失败项
+    for (key, t) in &imported.counter_updated_at {
+        if !imported.counters.contains_key(key) && !cache.counters.contains_key(key) {
+            cache.counter_updated_at.entry(key.clone()).or_insert(*t);
+        }
+    }
+    for key in &imported.entry_keys {
+        if !cache.entry_keys.contains(key) {
+            cache.entry_keys.push(key.clone());
+        }
+    }
+    if imported_newer {
+        cache.updated_at = imported.updated_at;
+        if !imported.patch.is_empty() {
+            cache.patch = imported.patch.clone();
+        }
+    }
+    cache.total_entries = cache.total_entries.max(imported.total_entries).max(cache.counters.len());
+    save_local_data(&cache);
+    Ok(cache)
+}
+
 /// 保存本地缓存
 pub fn save_local_data(cache: &OpggCache) {
     let path = data_path();
@@ -32,6 +113,64 @@ pub fn save_local_data(cache: &OpggCache) {
     }
 }
 
+/// OP.GG 站点设置：页面语言路径和英雄统计数据的地区
+struct OpggSite {
+    locale: String,
+    region: String,
+}
+
+static SITE: std::sync::OnceLock<OpggSite> = std::sync::OnceLock::new();
+
+/// 启动时按配置设置 OP.GG 语言/地区（只生效一次，未调用时为 zh-cn / global）
+///
+/// 腾讯客户端的英雄名固定为简体中文，页面语言始终用 zh-cn，保证按名字匹配英雄时与客户端一致。
+pub fn configure_site(locale: &str, region: &str, variant: ClientVariant) {
+    let locale = match variant {
+        ClientVariant::Tencent => "zh-cn",
+        ClientVariant::Global => locale.trim_matches('/'),
+    };
+    let _ = SITE.set(OpggSite {
+        locale: locale.to_string(),
+        region: region.to_string(),
+    });
+}
+
+fn site() -> &'static OpggSite {
+    SITE.get_or_init(|| OpggSite { locale: "zh-cn".into(), region: "global".into() })
+}
+
+/// OP.GG 网页 URL，path 以 "/" 开头；locale 为空时使用站点默认语言（英文）
+fn page_url(path: &str) -> String {
+    locale_page_url(&site().locale, path)
+}
+
+fn locale_page_url(locale: &str, path: &str) -> String {
+    match locale {
+        "" => format!("https://www.op.gg/lol{path}"),
+        locale => format!("https://www.op.gg/{locale}/lol{path}"),
+    }
+}
+
+/// 英雄统计页 URL：/champions/{slug}/{page}[/{位置}]，带统计地区和段位参数
+fn champion_page_url(slug: &str, page: &str, pos_slug: &str) -> String {
+    locale_champion_page_url(&site().locale, &site().region, slug, page, pos_slug)
+}
+
+fn locale_champion_page_url(locale: &str, region: &str, slug: &str, page: &str, pos_slug: &str) -> String {
+    let pos = if pos_slug.is_empty() { String::new() } else { format!("/{pos_slug}") };
+    locale_page_url(locale, &format!("/champions/{slug}/{page}{pos}?region={region}&tier=emerald_plus"))
+}
+
+/// 英雄列表（梯队）页 URL，全部位置
+fn champion_list_url(locale: &str, region: &str) -> String {
+    locale_page_url(locale, &format!("/champions?position=all&region={region}"))
+}
+
+/// 特殊模式英雄页 URL：/modes/{mode}/{slug}/{page}（大乱斗 / 无限火力 / 斗魂竞技场不分位置和段位）
+fn champion_mode_page_url(mode: &str, slug: &str, page: &str) -> String {
+    page_url(&format!("/modes/{mode}/{slug}/{page}?region={}", site().region))
+}
+
 /// 从 RSC push 数据中解析满足条件的 data 数组
 fn parse_rsc_push_data(html: &str, predicate: &dyn Fn(&Value) -> bool) -> Option<Vec<Value>> {
     let re = Regex::new(r"self\.__next_f\.push\(\[").unwrap();
@@ -105,6 +244,14 @@ fn find_data_array(
     }
 }
 
+/// 从 OP.GG 页面中提取当前数据版本（如 "14.23"），找不到返回空
+fn parse_patch(html: &str) -> String {
+    let re = Regex::new(r#"\\?"(?:version|patch)\\?"\s*:\s*\\?"(\d{1,2}\.\d{1,2})"#).unwrap();
+    re.captures(html)
+        .map(|c| c[1].to_string())
+        .unwrap_or_default()
+}
+
 /// 英雄+位置条目
 #[derive(Debug, Clone)]
 pub struct ChampPosEntry {
@@ -114,24 +261,40 @@ pub struct ChampPosEntry {
 }
 
 /// 从 OP.GG 获取英雄+位置列表
+///
+/// 返回 (英雄+位置列表, key → 中文名, 数据版本)
 pub async fn fetch_champion_position_list(
     client: &reqwest::Client,
-) -> Result<(Vec<ChampPosEntry>, std::collections::HashMap<String, String>), String> {
-    let url = "https://www.op.gg/zh-cn/lol/champions?position=all&region=global";
+    proxy_url: &str,
+) -> Result<ChampionList, String> {
+    let url = &champion_list_url(&site().locale, &site().region);
     let resp = client
         .get(url)
         .header("User-Agent", OPGG_UA)
         .send()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| crate::config::net_error(&e, proxy_url))?;
     let html = resp.text().await.map_err(|e| e.to_string())?;
 
-    let arr = parse_rsc_push_data(&html, &|v| {
+    let parsed = parse_champion_list(&html);
+    if parsed.0.is_empty() && has_rsc_payload(&html) {
+        return Err(format!("英雄列表{NO_DATA_HINT}"));
+    }
+    Ok(parsed)
+}
+
+/// 英雄列表类型：(英雄+位置条目, key → 中文名, 数据版本)
+type ChampionList = (Vec<ChampPosEntry>, std::collections::HashMap<String, String>, String);
+
+/// 解析英雄列表页 HTML（与网络请求分开，便于用保存下来的页面排查解析问题）
+pub fn parse_champion_list(html: &str) -> ChampionList {
+    let arr = parse_rsc_push_data(html, &|v| {
         v.get("key").is_some() && v.get("name").is_some() && v.get("positionName").is_some()
     });
 
+    let patch = parse_patch(html);
     let Some(arr) = arr else {
-        return Ok((vec![], std::collections::HashMap::new()));
+        return (vec![], std::collections::HashMap::new(), patch);
     };
 
     let mut entries = Vec::new();
@@ -154,23 +317,27 @@ pub async fn fetch_champion_position_list(
         }
     }
 
-    Ok((entries, name_map))
+    (entries, name_map, patch)
+}
+
+/// 页面正常返回（带 RSC 数据）却一条都解析不出来时的提示
+const NO_DATA_HINT: &str = "页面解析不到数据，OP.GG 页面结构可能已变化";
+
+/// 页面是否带有 RSC push 数据（没有时多半是被拦截或返回了错误页）
+fn has_rsc_payload(html: &str) -> bool {
+    html.contains("self.__next_f.push")
 }
 
 /// 从 OP.GG 获取指定英雄的克制数据
+///
+/// 页面带 RSC 数据但解析为空时返回空列表（由调用方计数提示），页面不含数据时返回错误。
 async fn fetch_counters_from_opgg(
     client: &reqwest::Client,
     slug: &str,
     position: &str,
 ) -> Result<Vec<CounterEntry>, String> {
     let pos_slug = crate::types::opgg_pos_slug(position);
-    let url = if !pos_slug.is_empty() {
-        format!(
-            "https://www.op.gg/champions/{slug}/counters/{pos_slug}?region=global&tier=emerald_plus"
-        )
-    } else {
-        format!("https://www.op.gg/champions/{slug}/counters?region=global&tier=emerald_plus")
-    };
+    let url = champion_page_url(slug, "counters", pos_slug);
 
     let mut html = String::new();
     for attempt in 0..2 {
@@ -196,12 +363,21 @@ async fn fetch_counters_from_opgg(
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
     }
 
-    let arr = parse_rsc_push_data(&html, &|v| {
+    let result = parse_counters(&html);
+    if result.is_empty() && !has_rsc_payload(&html) {
+        return Err("页面不含克制数据".into());
+    }
+    Ok(result)
+}
+
+/// 解析克制页 HTML
+pub fn parse_counters(html: &str) -> Vec<CounterEntry> {
+    let arr = parse_rsc_push_data(html, &|v| {
         v.get("win_rate").is_some() && v.get("champion").is_some()
     });
 
     let Some(arr) = arr else {
-        return Ok(vec![]);
+        return vec![];
     };
 
     let mut result = Vec::new();
@@ -224,7 +400,101 @@ async fn fetch_counters_from_opgg(
         });
     }
 
-    Ok(result)
+    result
+}
+
+/// 从 OP.GG 获取下路组合胜率（ADC 页面的辅助搭配），无记录返回 None
+pub async fn fetch_duo_synergy(
+    client: &reqwest::Client,
+    adc_slug: &str,
+    support_slug: &str,
+) -> Result<Option<(f64, i64)>, String> {
+    let url = champion_page_url(adc_slug, "synergies", "adc");
+    let resp = client
+        .get(&url)
+        .header("User-Agent", OPGG_UA)
+        .timeout(std::time::Duration::from_secs(8))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let html = resp.text().await.map_err(|e| e.to_string())?;
+
+    let arr = parse_rsc_push_data(&html, &|v| {
+        v.get("win_rate").is_some() && v.get("champion").is_some()
+    });
+    let Some(arr) = arr else {
+        return Ok(None);
+    };
+
+    Ok(arr.iter().find_map(|r| {
+        let key = r.get("champion").and_then(|c| c.get("key")).and_then(|v| v.as_str())?;
+        if key != support_slug {
+            return None;
+        }
+        let win_rate = r.get("win_rate").and_then(|v| v.as_f64())?;
+        let games = r.get("play").and_then(|v| v.as_i64()).unwrap_or(0);
+        Some((win_rate, games))
+    }))
+}
+
+/// 从 OP.GG 符文页获取推荐符文（取第一套，即最常用），无数据返回 None；
+/// mode 为特殊模式 slug 时改用该模式出装页上的符文
+pub async fn fetch_recommended_runes(
+    client: &reqwest::Client,
+    slug: &str,
+    lcu_position: &str,
+    mode: Option<&str>,
+) -> Result<Option<RunePage>, String> {
+    let url = match mode {
+        Some(mode) => champion_mode_page_url(mode, slug, "build"),
+        None => {
+            let pos_slug = crate::types::opgg_pos_slug(crate::types::lcu_pos_to_opgg(resolve_pos(slug, lcu_position)));
+            champion_page_url(slug, "runes", pos_slug)
+        }
+    };
+    let resp = client
+        .get(&url)
+        .header("User-Agent", OPGG_UA)
+        .timeout(std::time::Duration::from_secs(8))
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let html = resp.text().await.map_err(|e| e.to_string())?;
+
+    let arr = parse_rsc_push_data(&html, &|v| {
+        v.get("primary_page_id").is_some() && v.get("primary_rune_ids").is_some()
+    });
+    let Some(first) = arr.as_ref().and_then(|a| a.first()) else {
+        return Ok(None);
+    };
+
+    let ids = |k: &str| -> Vec<i64> {
+        first
+            .get(k)
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().filter_map(|x| x.as_i64()).collect())
+            .unwrap_or_default()
+    };
+    let style = |k: &str| first.get(k).and_then(|v| v.as_i64()).and_then(crate::types::opgg_style_to_lcu);
+    let (Some(primary_style), Some(sub_style)) = (style("primary_page_id"), style("secondary_page_id")) else {
+        return Ok(None);
+    };
+    let perks: Vec<i64> = [ids("primary_rune_ids"), ids("secondary_rune_ids"), ids("stat_mod_ids")]
+        .concat()
+        .into_iter()
+        .map(|id| crate::types::opgg_rune_to_perk(id).ok_or(format!("未知符文 id: {id}")))
+        .collect::<Result<_, _>>()?;
+    if perks.len() != 9 {
+        return Err(format!("符文数量异常（{}）", perks.len()));
+    }
+    // 主系 4 个、副系 2 个必须属于各自的符文系，否则客户端会拒绝这个符文页
+    let style_ok = |range: std::ops::Range<usize>, style: i64| {
+        perks[range].iter().all(|&p| crate::types::rune_style(p) == Some(style))
+    };
+    if !style_ok(0..4, primary_style) || !style_ok(4..6, sub_style) {
+        return Err("符文与符文系不对应".into());
+    }
+    Ok(Some(RunePage { primary_style, sub_style, perks }))
 }
 
 fn counter_key(slug: &str, position: &str) -> String {
@@ -235,23 +505,140 @@ fn counter_key(slug: &str, position: &str) -> String {
     }
 }
 
+/// 采集进度回调：(已完成, 总数, 当前条目)
+pub type ProgressFn = Arc<dyn Fn(usize, usize, &str) + Send + Sync>;
+
 /// 全量采集所有英雄克制数据
 pub async fn fetch_all_counters(
-    progress: Option<Arc<dyn Fn(usize, usize, &str) + Send + Sync>>,
+    proxy_url: &str,
+    progress: Option<ProgressFn>,
 ) -> Result<OpggCache, String> {
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| e.to_string())?;
+    let client = crate::config::http_client(proxy_url, std::time::Duration::from_secs(10))?;
 
-    let (entries, name_map) = fetch_champion_position_list(&client).await?;
+    let (entries, name_map, patch) = fetch_champion_position_list(&client, proxy_url).await?;
     if entries.is_empty() {
         return Err("无法获取英雄列表".into());
     }
 
+    let total = entries.len();
+    let entry_keys = entries.iter().map(|e| counter_key(&e.key, &e.position)).collect();
+    let (counters, empty_keys) = scrape_counters(&client, entries, progress.clone()).await;
+    if counters.is_empty() && !empty_keys.is_empty() {
+        return Err(format!("克制{NO_DATA_HINT}"));
+    }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+
+    let mut cache = OpggCache {
+        champions: name_map,
+        counters: Default::default(),
+        updated_at: now,
+        counter_updated_at: Default::default(),
+        total_entries: total,
+        patch,
+        // 英雄列表页的整体胜率字段还没有对照真实页面确认过，暂不采集；导入的数据中有则沿用
+        baseline_win_rates: Default::default(),
+        entry_keys,
+        duo_synergies: Default::default(),
+    };
+    apply_scrape(&mut cache, counters, empty_keys, now);
+
+    save_local_data(&cache);
+
+    if let Some(ref p) = progress {
+        p(total, total, "完成");
+    }
+
+    Ok(cache)
+}
+
+/// 上次全量更新中采集失败的 "slug:POS"（页面有返回但没有数据的条目不算，见 apply_scrape）
+pub fn missing_counter_keys(cache: &OpggCache) -> Vec<&str> {
+    cache
+        .entry_keys
+        .iter()
+        .filter(|k| !cache.counters.contains_key(*k) && !cache.counter_updated_at.contains_key(*k))
+        .map(|k| k.as_str())
+        .collect()
+}
+
+/// 页面正常返回却没有克制数据的条目数（样本太少，或数量很多时说明页面结构可能已变化）
+pub fn empty_counter_count(cache: &OpggCache) -> usize {
+    cache
+        .entry_keys
+        .iter()
+        .filter(|k| !cache.counters.contains_key(*k) && cache.counter_updated_at.contains_key(*k))
+        .count()
+}
+
+/// 把一次采集结果写入缓存：有数据的条目更新克制列表和采集时间，
+/// 页面为空的条目只记录采集时间，之后不再当作失败项反复补采
+fn apply_scrape(
+    cache: &mut OpggCache,
+    fetched: std::collections::HashMap<String, Vec<CounterEntry>>,
+    empty_keys: Vec<String>,
+    now: f64,
+) {
+    for k in empty_keys {
+        cache.counter_updated_at.insert(k, now);
+    }
+    for (k, data) in fetched {
+        cache.counter_updated_at.insert(k.clone(), now);
+        cache.counters.insert(k, data);
+    }
+}
+
+/// 只重新采集上次更新失败的条目，合并进已有缓存
+pub async fn fetch_missing_counters(
+    proxy_url: &str,
+    mut cache: OpggCache,
+    progress: Option<ProgressFn>,
+) -> Result<OpggCache, String> {
+    let targets: Vec<ChampPosEntry> = missing_counter_keys(&cache)
+        .into_iter()
+        .map(|k| {
+            let (key, position) = k.split_once(':').unwrap_or((k, ""));
+            ChampPosEntry {
+                key: key.to_string(),
+                name: cache.champions.get(key).cloned().unwrap_or_else(|| key.to_string()),
+                position: position.to_string(),
+            }
+        })
+        .collect();
+    if targets.is_empty() {
+        return Ok(cache);
+    }
+    let client = crate::config::http_client(proxy_url, std::time::Duration::from_secs(10))?;
+    let total = targets.len();
+    let (fetched, empty_keys) = scrape_counters(&client, targets, progress.clone()).await;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+    apply_scrape(&mut cache, fetched, empty_keys, now);
+    save_local_data(&cache);
+
+    if let Some(ref p) = progress {
+        p(total, total, "完成");
+    }
+
+    Ok(cache)
+}
+
+/// 并发采集一批英雄+位置的克制数据，失败或为空的条目不出现在结果中
+///
+/// 返回 (克制数据, 页面正常返回却解析不到数据的 "slug:POS")
+async fn scrape_counters(
+    client: &reqwest::Client,
+    entries: Vec<ChampPosEntry>,
+    progress: Option<ProgressFn>,
+) -> (std::collections::HashMap<String, Vec<CounterEntry>>, Vec<String>) {
     let total = entries.len();
     let counters = Arc::new(Mutex::new(std::collections::HashMap::new()));
     let done = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let empty = Arc::new(Mutex::new(Vec::new()));
     let sem = Arc::new(Semaphore::new(10));
 
     let mut handles = Vec::new();
@@ -260,6 +647,7 @@ pub async fn fetch_all_counters(
         let sem = sem.clone();
         let counters = counters.clone();
         let done = done.clone();
+        let empty = empty.clone();
         let progress = progress.clone();
 
         let handle = tokio::spawn(async move {
@@ -269,7 +657,10 @@ pub async fn fetch_all_counters(
                 Ok(data) if !data.is_empty() => {
                     counters.lock().unwrap().insert(ckey, data);
                 }
-                _ => {}
+                Ok(_) => {
+                    empty.lock().unwrap().push(ckey);
+                }
+                Err(_) => {}
             }
             let d = done.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
             if let Some(ref p) = progress {
@@ -283,70 +674,341 @@ pub async fn fetch_all_counters(
         let _ = h.await;
     }
 
-    let counters = Arc::try_unwrap(counters).unwrap().into_inner().unwrap();
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs_f64();
+    (
+        Arc::try_unwrap(counters).unwrap().into_inner().unwrap(),
+        Arc::try_unwrap(empty).unwrap().into_inner().unwrap(),
+    )
+}
 
-    let cache = OpggCache {
-        champions: name_map,
-        counters,
-        updated_at: now,
-        total_entries: total,
-    };
+/// 指定位置无数据时依次尝试的 OP.GG 位置
+const FALLBACK_POSITIONS: [&str; 5] = ["TOP", "JUNGLE", "MID", "ADC", "SUPPORT"];
 
-    save_local_data(&cache);
+/// 取某英雄在指定位置的克制列表，返回 (缓存 key, 列表)
+///
+/// 查找顺序固定：指定位置 → 英雄默认位置 → FALLBACK_POSITIONS → 不带位置的旧数据，
+/// 保证同一份缓存每次回退到同一个位置。
+fn counters_for<'a>(
+    cache: &'a OpggCache,
+    slug: &str,
+    opgg_pos: &str,
+) -> Option<(&'a String, &'a Vec<CounterEntry>)> {
+    let default_pos = crate::types::lcu_pos_to_opgg(crate::types::default_role(slug));
+    // 指定位置为空时先查不带位置的 key（与 counter_key 一致）
+    std::iter::once(opgg_pos)
+        .chain(Some(default_pos).filter(|p| !p.is_empty()))
+        .chain(FALLBACK_POSITIONS)
+        .chain(std::iter::once(""))
+        .find_map(|p| cache.counters.get_key_value(&counter_key(slug, p)))
+}
 
-    if let Some(ref p) = progress {
-        p(total, total, "完成");
+/// 克制数据回退到了其他位置时返回实际使用的 OP.GG 位置（用于界面提示），否则 None
+///
+/// 「全部位置」本来就合并各位置数据，不算回退。
+pub fn counter_fallback_position<'a>(cache: &'a OpggCache, slug: &str, lcu_position: &str) -> Option<&'a str> {
+    if lcu_position == crate::types::ALL_POSITIONS {
+        return None;
     }
+    let opgg_pos = crate::types::lcu_pos_to_opgg(resolve_pos(slug, lcu_position));
+    let (key, _) = counters_for(cache, slug, opgg_pos)?;
+    let used = key.split_once(':').map_or("", |(_, p)| p);
+    (!used.is_empty() && used != opgg_pos).then_some(used)
+}
 
-    Ok(cache)
+/// 英雄在各位置的克制数据概况：只列出缓存中有 `{slug}:POS` 数据的位置，按上中下顺序
+///
+/// 最强克制只在场次不少于 min_games 的条目中挑选。
+pub fn position_breakdown(cache: &OpggCache, slug: &str, min_games: i64) -> Vec<PositionBreakdown> {
+    FALLBACK_POSITIONS
+        .iter()
+        .filter_map(|&pos| {
+            let list = cache.counters.get(&counter_key(slug, pos))?;
+            let top_counter = list
+                .iter()
+                .filter(|c| c.games >= min_games)
+                .max_by(|a, b| a.win_rate.total_cmp(&b.win_rate))
+                .map(|c| {
+                    let name = cache.champions.get(&c.key).cloned().unwrap_or_else(|| c.key.clone());
+                    (name, c.win_rate, c.games)
+                });
+            Some(PositionBreakdown {
+                pos,
+                entries: list.len(),
+                baseline: cache.baseline_win_rates.get(&counter_key(slug, pos)).copied(),
+                top_counter,
+            })
+        })
+        .collect()
+}
+
+/// 未指定位置时按英雄默认位置推测（见 types::default_role）
+fn resolve_pos<'a>(slug: &str, lcu_position: &'a str) -> &'a str {
+    if lcu_position.is_empty() {
+        crate::types::default_role(slug)
+    } else {
+        lcu_position
+    }
+}
+
+/// 指定英雄克制数据的采集时间（无单条记录时用整体更新时间）
+pub fn counter_updated_at(cache: &OpggCache, slug: &str, lcu_position: &str) -> Option<f64> {
+    let opgg_pos = crate::types::lcu_pos_to_opgg(resolve_pos(slug, lcu_position));
+    let (key, _) = counters_for(cache, slug, opgg_pos)?;
+    cache
+        .counter_updated_at
+        .get(key)
+        .copied()
+        .or(Some(cache.updated_at))
+        .filter(|t| *t > 0.0)
+}
+
+/// overrides 中 `{table_key}:{counter_slug}` 的手动数据，换成克制条目
+fn override_entry(overrides: &CounterOverrides, table_key: &str, counter_slug: &str) -> Option<CounterEntry> {
+    overrides.get(&format!("{table_key}:{counter_slug}")).map(|o| CounterEntry {
+        key: counter_slug.to_string(),
+        win_rate: o.win_rate,
+        games: o.games,
+    })
+}
+
+/// 某英雄单一位置的克制表（位置回退同 counters_for），已套用 overrides 中的手动数据
+///
+/// 返回 (表的 key, [(条目, 是否手动数据)])；OP.GG 没有的对位也按手动数据补上。
+/// 按单一位置读取克制数据的地方都经过这里，手动数据才能在克制表、对位胜率、热力图中保持一致。
+fn effective_counters(
+    cache: &OpggCache,
+    overrides: &CounterOverrides,
+    slug: &str,
+    opgg_pos: &str,
+) -> (String, Vec<(CounterEntry, bool)>) {
+    let (key, counters) = match counters_for(cache, slug, opgg_pos) {
+        Some((k, list)) => (k.clone(), list.as_slice()),
+        None => (counter_key(slug, opgg_pos), &[][..]),
+    };
+    let mut rows: Vec<(CounterEntry, bool)> = counters
+        .iter()
+        .map(|c| match override_entry(overrides, &key, &c.key) {
+            Some(o) => (o, true),
+            None => (c.clone(), false),
+        })
+        .collect();
+    let prefix = format!("{key}:");
+    let mut extra: Vec<CounterEntry> = overrides
+        .iter()
+        .filter_map(|(k, o)| Some((k.strip_prefix(&prefix)?, o)))
+        .filter(|(enemy, _)| !enemy.contains(':') && !counters.iter().any(|c| c.key == *enemy))
+        .map(|(enemy, o)| CounterEntry { key: enemy.to_string(), win_rate: o.win_rate, games: o.games })
+        .collect();
+    extra.sort_by(|a, b| a.key.cmp(&b.key));
+    rows.extend(extra.into_iter().map(|c| (c, true)));
+    (key, rows)
+}
+
+/// 查询对位胜率：我方英雄对敌方英雄的胜率和场次（含手动数据），无数据返回 None
+///
+/// `counters[X]` 里每条的 win_rate 是该条英雄打 X 的胜率，所以先查敌方英雄表中的我方条目；
+/// 没有时再用我方英雄表中的敌方条目，取 100 - 胜率。
+pub fn matchup_win_rate(
+    cache: &OpggCache,
+    overrides: &CounterOverrides,
+    my_slug: &str,
+    enemy_slug: &str,
+    lcu_position: &str,
+) -> Option<(f64, i64)> {
+    if my_slug.is_empty() || enemy_slug.is_empty() {
+        return None;
+    }
+    let find = |host: &str, key: &str| {
+        let opgg_pos = crate::types::lcu_pos_to_opgg(resolve_pos(host, lcu_position));
+        effective_counters(cache, overrides, host, opgg_pos)
+            .1
+            .into_iter()
+            .find(|(c, _)| c.key == key)
+            .map(|(c, _)| (c.win_rate, c.games))
+    };
+    find(enemy_slug, my_slug).or_else(|| find(my_slug, enemy_slug).map(|(wr, games)| (100.0 - wr, games)))
 }
 
-/// 从缓存中获取指定英雄的克制数据
+/// 英雄在指定 OP.GG 位置的整体胜率；位置为空或无该位置数据时取各位置平均
+pub fn baseline_win_rate(cache: &OpggCache, slug: &str, opgg_pos: &str) -> Option<f64> {
+    if let Some(wr) = cache.baseline_win_rates.get(&counter_key(slug, opgg_pos)) {
+        return Some(*wr);
+    }
+    let prefix = format!("{slug}:");
+    let all: Vec<f64> = cache
+        .baseline_win_rates
+        .iter()
+        .filter(|(k, _)| k.starts_with(&prefix))
+        .map(|(_, v)| *v)
+        .collect();
+    (!all.is_empty()).then(|| all.iter().sum::<f64>() / all.len() as f64)
+}
+
+/// 从缓存中获取指定英雄的克制数据，overrides 中的手动数据优先于 OP.GG 数据
+///
+/// 覆盖只作用于单一位置视图；合并全部位置时仍按 OP.GG 数据加权。
 pub fn get_counters_for_champion(
     cache: &OpggCache,
+    overrides: &CounterOverrides,
     slug: &str,
     lcu_position: &str,
 ) -> Vec<CounterDisplay> {
     if slug.is_empty() {
         return vec![];
     }
-    let opgg_pos = crate::types::lcu_pos_to_opgg(lcu_position);
+    if lcu_position == crate::types::ALL_POSITIONS {
+        return merged_counters(cache, slug);
+    }
+    let opgg_pos = crate::types::lcu_pos_to_opgg(resolve_pos(slug, lcu_position));
+    effective_counters(cache, overrides, slug, opgg_pos)
+        .1
+        .into_iter()
+        .map(|(c, overridden)| CounterDisplay {
+            name: cache.champions.get(&c.key).cloned().unwrap_or_else(|| c.key.clone()),
+            delta: baseline_win_rate(cache, &c.key, opgg_pos).map(|b| c.win_rate - b),
+            key: c.key,
+            win_rate: c.win_rate,
+            games: c.games,
+            positions: vec![],
+            overridden,
+        })
+        .collect()
+}
 
-    // 优先匹配 slug:position
-    let key = counter_key(slug, opgg_pos);
-    let mut counters = cache.counters.get(&key).cloned();
+/// 手动覆盖数据的 key 前缀（即该英雄克制数据在缓存中的 "slug:POS"），合并全部位置时无法覆盖返回 None
+pub fn override_base_key(cache: &OpggCache, slug: &str, lcu_position: &str) -> Option<String> {
+    if slug.is_empty() || lcu_position == crate::types::ALL_POSITIONS {
+        return None;
+    }
+    let opgg_pos = crate::types::lcu_pos_to_opgg(resolve_pos(slug, lcu_position));
+    Some(match counters_for(cache, slug, opgg_pos) {
+        Some((k, _)) => k.clone(),
+        None => counter_key(slug, opgg_pos),
+    })
+}
 
-    // fallback: 尝试该英雄任意位置
-    if counters.is_none() {
-        for (k, v) in &cache.counters {
-            if k.starts_with(&format!("{slug}:")) || k == slug {
-                counters = Some(v.clone());
-                break;
+/// 合并某英雄所有位置的克制数据，胜率按场次加权
+fn merged_counters(cache: &OpggCache, slug: &str) -> Vec<CounterDisplay> {
+    let prefix = format!("{slug}:");
+    // counter slug → (加权胜率和, 场次, 来源位置)
+    let mut merged: std::collections::HashMap<&str, (f64, i64, Vec<String>)> =
+        std::collections::HashMap::new();
+    let mut keys: Vec<&String> = cache.counters.keys().filter(|k| k.starts_with(&prefix)).collect();
+    keys.sort();
+    for k in keys {
+        let pos = &k[prefix.len()..];
+        for c in &cache.counters[k] {
+            let e = merged.entry(c.key.as_str()).or_default();
+            e.0 += c.win_rate * c.games as f64;
+            e.1 += c.games;
+            e.2.push(pos.to_string());
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(key, (wr_sum, games, positions))| {
+            let win_rate = if games > 0 { wr_sum / games as f64 } else { 0.0 };
+            CounterDisplay {
+                name: cache.champions.get(key).cloned().unwrap_or_else(|| key.to_string()),
+                key: key.to_string(),
+                win_rate,
+                games,
+                positions,
+                delta: baseline_win_rate(cache, key, "").map(|b| win_rate - b),
+                overridden: false,
+            }
+        })
+        .collect()
+}
+
+/// 英雄池威胁：对每个收藏英雄（合并全部位置）找出它胜率低于 threshold 的对手，按被克制的英雄数汇总
+///
+/// 收藏英雄克制表中的胜率是对手打它的胜率，这里换成收藏英雄的视角（100 - 胜率）再比较。
+/// 场次少于 min_games 的对局不计入。结果按克制英雄数降序、收藏英雄的平均胜率升序排列。
+pub fn pool_threats(cache: &OpggCache, pool: &[String], min_games: i64, threshold: f64) -> Vec<PoolThreat> {
+    // 敌方 slug → (受害者, 收藏英雄加权胜率和, 场次)
+    let mut agg: std::collections::HashMap<String, (Vec<String>, f64, i64)> = std::collections::HashMap::new();
+    for slug in pool {
+        let victim = cache.champions.get(slug).cloned().unwrap_or_else(|| slug.clone());
+        for c in merged_counters(cache, slug) {
+            let my_win_rate = 100.0 - c.win_rate;
+            if c.games < min_games || my_win_rate >= threshold {
+                continue;
             }
+            let e = agg.entry(c.key).or_default();
+            e.0.push(victim.clone());
+            e.1 += my_win_rate * c.games as f64;
+            e.2 += c.games;
         }
     }
+    let mut out: Vec<PoolThreat> = agg
+        .into_iter()
+        .map(|(key, (mut victims, wr_sum, games))| {
+            victims.sort();
+            PoolThreat {
+                name: cache.champions.get(&key).cloned().unwrap_or_else(|| key.clone()),
+                key,
+                victims,
+                avg_win_rate: if games > 0 { wr_sum / games as f64 } else { 0.0 },
+                games,
+            }
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.victims.len().cmp(&a.victims.len()).then_with(|| {
+            a.avg_win_rate.partial_cmp(&b.avg_win_rate).unwrap_or(std::cmp::Ordering::Equal)
+        })
+    });
+    out
+}
 
-    let Some(counters) = counters else {
+/// 反查：哪些英雄打指定敌方英雄胜率高（扫描所有英雄克制表中 key == enemy_slug 的条目）
+///
+/// 返回的 win_rate 为该英雄对敌方英雄的胜率。位置为空时不限位置，同一英雄取场次最多的一条。
+pub fn get_good_picks_against(
+    cache: &OpggCache,
+    overrides: &CounterOverrides,
+    enemy_slug: &str,
+    lcu_position: &str,
+) -> Vec<CounterDisplay> {
+    if enemy_slug.is_empty() {
         return vec![];
-    };
+    }
+    let opgg_pos = crate::types::lcu_pos_to_opgg(resolve_pos(enemy_slug, lcu_position));
 
-    counters
-        .iter()
-        .map(|c| {
-            let cn_name = cache
-                .champions
-                .get(&c.key)
-                .cloned()
-                .unwrap_or_else(|| c.key.clone());
+    // 英雄 slug → (位置, 条目, 是否手动数据)
+    let mut best: std::collections::HashMap<&str, (&str, CounterEntry, bool)> = std::collections::HashMap::new();
+    for (k, list) in &cache.counters {
+        let (slug, pos) = k.split_once(':').unwrap_or((k.as_str(), ""));
+        if slug == enemy_slug || (!opgg_pos.is_empty() && pos != opgg_pos) {
+            continue;
+        }
+        let entry = match override_entry(overrides, k, enemy_slug) {
+            Some(o) => (o, true),
+            None => match list.iter().find(|c| c.key == enemy_slug) {
+                Some(c) => (c.clone(), false),
+                None => continue,
+            },
+        };
+        match best.get(slug) {
+            Some((_, old, _)) if old.games >= entry.0.games => {}
+            _ => {
+                best.insert(slug, (pos, entry.0, entry.1));
+            }
+        }
+    }
+
+    // 条目中的 win_rate 是敌方英雄打该英雄的胜率，换成该英雄的视角
+    best.into_iter()
+        .map(|(slug, (pos, e, overridden))| {
+            let win_rate = 100.0 - e.win_rate;
             CounterDisplay {
-                name: cn_name,
-                key: c.key.clone(),
-                win_rate: c.win_rate,
-                games: c.games,
+                name: cache.champions.get(slug).cloned().unwrap_or_else(|| slug.to_string()),
+                key: slug.to_string(),
+                win_rate,
+                games: e.games,
+                positions: vec![],
+                delta: baseline_win_rate(cache, slug, pos).map(|b| win_rate - b),
+                overridden,
             }
         })
         .collect()
@@ -368,61 +1030,131 @@ fn percent_encode_path(s: &str) -> String {
 /// 构造 OP.GG 页面 URL（供调试显示）
 pub fn match_history_url(region: &str, game_name: &str, tag_line: &str) -> String {
     let encoded = percent_encode_path(&format!("{game_name}-{tag_line}"));
-    format!("https://www.op.gg/zh-cn/lol/summoners/{region}/{encoded}")
+    page_url(&format!("/summoners/{region}/{encoded}"))
+}
+
+/// 构造 OP.GG 英雄出装页 URL（opgg_position 为空时不指定位置；mode 为特殊模式 slug 时打开该模式的出装页）
+pub fn champion_build_url(slug: &str, opgg_position: &str, mode: Option<&str>) -> String {
+    match mode {
+        Some(mode) => champion_mode_page_url(mode, slug, "build"),
+        None => champion_page_url(slug, "build", crate::types::opgg_pos_slug(opgg_position)),
+    }
+}
+
+/// 构造 OP.GG 多人查询 URL，riot_ids 为 `name#tag` 列表
+pub fn multisearch_url(region: &str, riot_ids: &[String]) -> String {
+    let encoded: Vec<String> = riot_ids.iter().map(|id| percent_encode_path(id)).collect();
+    page_url(&format!("/multisearch/{region}?summoners={}", encoded.join("%2C")))
 }
 
 const OPGG_API: &str = "https://lol-api-summoner.op.gg";
 
-/// 从 OP.GG API 查询 summoner_id
+/// OP.GG API 临时失败后重试前的等待
+const API_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(800);
+
+/// 请求 OP.GG API 并解析 JSON，HTTP 404 返回 Ok(None)
+///
+/// 连接失败、超时和 5xx/429 视为临时故障，等待后重试一次；其他错误不重试。
+async fn api_get_json(
+    client: &reqwest::Client,
+    url: &str,
+    timeout_secs: u64,
+    what: &str,
+) -> Result<Option<Value>, String> {
+    let mut retried = false;
+    loop {
+        let resp = client
+            .get(url)
+            .header("User-Agent", OPGG_UA)
+            .timeout(std::time::Duration::from_secs(timeout_secs))
+            .send()
+            .await;
+        let transient = match resp {
+            Ok(r) if r.status() == reqwest::StatusCode::NOT_FOUND => return Ok(None),
+            Ok(r) if r.status().is_server_error() || r.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                format!("{what}失败：OP.GG 暂时不可用（HTTP {}）", r.status().as_u16())
+            }
+            Ok(r) if !r.status().is_success() => {
+                return Err(format!("{what}失败：OP.GG 返回 HTTP {}", r.status().as_u16()));
+            }
+            Ok(r) => return r.json().await.map(Some).map_err(|e| format!("解析{what}响应失败: {e}")),
+            Err(e) if e.is_timeout() => format!("{what}超时：OP.GG 响应过慢（{timeout_secs} 秒）"),
+            Err(e) if e.is_connect() => format!("{what}失败：无法连接 OP.GG，请检查网络（{e}）"),
+            Err(e) => return Err(format!("{what}失败: {e}")),
+        };
+        if retried {
+            return Err(format!("{transient}，已重试 1 次"));
+        }
+        retried = true;
+        tokio::time::sleep(API_RETRY_DELAY).await;
+    }
+}
+
+/// 从 OP.GG API 查询 summoner_id（以及单双排段位，如有）
 async fn opgg_lookup_summoner(
     client: &reqwest::Client,
     region: &str,
     game_name: &str,
     tag_line: &str,
-) -> Result<String, String> {
+) -> Result<(String, Option<SoloRank>), String> {
     let riot_id = percent_encode_path(&format!("{game_name}#{tag_line}"));
     let url = format!("{OPGG_API}/api/v3/{region}/summoners?riot_id={riot_id}&hl=zh_CN");
-    let resp = client
-        .get(&url)
-        .header("User-Agent", OPGG_UA)
-        .timeout(std::time::Duration::from_secs(8))
-        .send()
-        .await
-        .map_err(|e| format!("查询召唤师失败: {e}"))?;
-    let json: Value = resp.json().await.map_err(|e| format!("解析响应失败: {e}"))?;
-    let sid = json.get("data")
+    let json = api_get_json(client, &url, 8, "查询召唤师").await?;
+    let summoner = json.as_ref()
+        .and_then(|j| j.get("data"))
         .and_then(|d| d.as_array())
-        .and_then(|arr| arr.first())
+        .and_then(|arr| arr.first());
+    // 玩家不存在不是网络问题，不重试
+    let sid = summoner
         .and_then(|s| s.get("summoner_id"))
         .and_then(|v| v.as_str())
-        .ok_or_else(|| "未找到召唤师".to_string())?;
-    Ok(sid.to_string())
+        .ok_or_else(|| format!("未找到召唤师 {game_name}#{tag_line}（请检查名字、标签和地区 {region}）"))?;
+    let rank = summoner
+        .and_then(|s| s.get("solo_tier_info"))
+        .filter(|t| t.is_object())
+        .map(|t| {
+            // division 可能是数字（4）或字符串（"IV"）
+            let division = match t.get("division") {
+                Some(Value::Number(n)) => match n.as_i64() {
+                    Some(1) => "I".to_string(),
+                    Some(2) => "II".to_string(),
+                    Some(3) => "III".to_string(),
+                    Some(4) => "IV".to_string(),
+                    _ => String::new(),
+                },
+                Some(Value::String(d)) => d.clone(),
+                _ => String::new(),
+            };
+            SoloRank {
+                tier: t.get("tier").and_then(|v| v.as_str()).unwrap_or("").to_uppercase(),
+                division,
+                lp: t.get("lp").and_then(|v| v.as_i64()).unwrap_or(0) as i32,
+            }
+        });
+    Ok((sid.to_string(), rank))
 }
 
-/// 从 OP.GG API 获取玩家最近对局记录
+/// 从 OP.GG API 获取玩家最近对局记录（附带 OP.GG 上的单双排段位）
 pub async fn fetch_match_history(
     client: &reqwest::Client,
     region: &str,
     game_name: &str,
     tag_line: &str,
-) -> Result<Vec<MatchEntry>, String> {
+) -> Result<(Vec<MatchEntry>, Option<SoloRank>), String> {
     // 1. 查询 summoner_id
-    let summoner_id = opgg_lookup_summoner(client, region, game_name, tag_line).await?;
+    let (summoner_id, rank) = opgg_lookup_summoner(client, region, game_name, tag_line).await?;
 
     // 2. 获取对局列表
     let url = format!(
         "{OPGG_API}/api/{region}/summoners/{summoner_id}/games?limit=20&game_type=total&hl=zh_CN&ended_at="
     );
-    let resp = client
-        .get(&url)
-        .header("User-Agent", OPGG_UA)
-        .timeout(std::time::Duration::from_secs(10))
-        .send()
-        .await
-        .map_err(|e| format!("获取对局失败: {e}"))?;
-    let json: Value = resp.json().await.map_err(|e| format!("解析对局失败: {e}"))?;
+    let json = api_get_json(client, &url, 10, "获取对局").await?;
 
-    let games = json.get("data").and_then(|d| d.as_array()).cloned().unwrap_or_default();
+    let games = json.as_ref()
+        .and_then(|j| j.get("data"))
+        .and_then(|d| d.as_array())
+        .cloned()
+        .unwrap_or_default();
 
     // 查找目标玩家的名字（用于匹配 participants）
     let target_name = game_name.to_lowercase();
@@ -431,6 +1163,7 @@ pub async fn fetch_match_history(
     for game in &games {
         let duration = game.get("game_length_second").and_then(|v| v.as_i64()).unwrap_or(0);
         let game_type = game.get("game_type").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        let game_id = game.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string();
         let queue_id = game.get("queue_id").and_then(|v| v.as_i64()).unwrap_or(0);
 
         // 解析时间戳（ISO 8601 → ms）
@@ -459,6 +1192,7 @@ pub async fn fetch_match_history(
         let kills = stats.and_then(|s| s.get("kill")).and_then(|v| v.as_i64()).unwrap_or(0);
         let deaths = stats.and_then(|s| s.get("death")).and_then(|v| v.as_i64()).unwrap_or(0);
         let assists = stats.and_then(|s| s.get("assist")).and_then(|v| v.as_i64()).unwrap_or(0);
+        let position = participant_position(me);
 
         entries.push(MatchEntry {
             champion_id,
@@ -472,8 +1206,334 @@ pub async fn fetch_match_history(
             timestamp_ms,
             queue_id,
             game_type,
+            game_id,
+            created_at: created_at.to_string(),
+            position,
         });
     }
 
-    Ok(entries)
+    Ok((entries, rank))
+}
+
+/// 从 OP.GG API 获取单局详情，返回目标玩家的最终出装和召唤师技能
+pub async fn fetch_match_detail(
+    client: &reqwest::Client,
+    region: &str,
+    game_id: &str,
+    created_at: &str,
+    game_name: &str,
+) -> Result<MatchDetail, String> {
+    let url = format!(
+        "{OPGG_API}/api/{region}/games/{}?created_at={}&hl=zh_CN",
+        percent_encode_path(game_id),
+        percent_encode_path(created_at),
+    );
+    let resp = client
+        .get(&url)
+        .header("User-Agent", OPGG_UA)
+        .timeout(std::time::Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| format!("获取对局详情失败: {e}"))?;
+    let json: Value = resp.json().await.map_err(|e| format!("解析对局详情失败: {e}"))?;
+
+    let target_name = game_name.to_lowercase();
+    let participants = json
+        .get("data")
+        .and_then(|d| d.get("participants"))
+        .and_then(|p| p.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let me = participants
+        .iter()
+        .find(|p| {
+            p.get("summoner")
+                .and_then(|s| s.get("game_name"))
+                .and_then(|v| v.as_str())
+                .map(|n| n.to_lowercase() == target_name)
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| "对局详情中未找到该玩家".to_string())?;
+
+    let ids = |key: &str| -> Vec<i64> {
+        me.get(key)
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_i64()).filter(|&id| id > 0).collect())
+            .unwrap_or_default()
+    };
+    Ok(MatchDetail {
+        items: ids("items"),
+        spells: ids("spells"),
+        position: participant_position(me),
+    })
+}
+
+/// 参与者的位置（OP.GG 字段为 position，统一为大写 OP.GG 位置名）
+fn participant_position(p: &Value) -> String {
+    let pos = p.get("position").and_then(|v| v.as_str()).unwrap_or("").to_uppercase();
+    match pos.as_str() {
+        "TOP" | "JUNGLE" | "MID" | "ADC" | "SUPPORT" => pos,
+        "MIDDLE" => "MID".into(),
+        "BOTTOM" => "ADC".into(),
+        "UTILITY" => "SUPPORT".into(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tests/fixtures 下的页面目前是按 OP.GG 的 RSC 结构手工构造的样本，没有对照过真实页面，
+    // 只能保证解析逻辑自洽；能联网时运行 refresh_fixtures 换成真实页面并按需调整断言。
+    const CHAMPIONS_ZH: &str = include_str!("../tests/fixtures/champions_zh-cn.html");
+    const COUNTERS_AHRI_MID: &str = include_str!("../tests/fixtures/counters_ahri_mid.html");
+    const CHAMPIONS_EN: &str = include_str!("../tests/fixtures/champions_en.html");
+
+    #[test]
+    fn champion_list_fixture() {
+        let (entries, names, patch) = parse_champion_list(CHAMPIONS_ZH);
+        assert_eq!(patch, "14.23");
+        assert_eq!(entries.len(), 7);
+        assert_eq!(names.len(), 6);
+        assert_eq!(names["ahri"], "阿狸");
+
+        let ahri = entries.iter().find(|e| e.key == "ahri").unwrap();
+        assert_eq!(ahri.position, "MID");
+
+        // 同一英雄多个位置各自一条
+        let sylas: Vec<_> = entries.iter().filter(|e| e.key == "sylas").collect();
+        assert_eq!(sylas.len(), 2);
+        assert_eq!(sylas[0].position, "TOP");
+        assert_eq!(sylas[1].position, "MID");
+    }
+
+    #[test]
+    fn champion_list_english_page() {
+        // 英文页（locale 为空）与中文页结构相同，只有英雄名不同
+        let (entries, names, patch) = parse_champion_list(CHAMPIONS_EN);
+        let (zh_entries, _, _) = parse_champion_list(CHAMPIONS_ZH);
+        assert_eq!(patch, "14.23");
+        assert_eq!(names["leesin"], "Lee Sin");
+        let keys = |e: &[ChampPosEntry]| e.iter().map(|e| (e.key.clone(), e.position.clone())).collect::<Vec<_>>();
+        assert_eq!(keys(&entries), keys(&zh_entries));
+    }
+
+    #[test]
+    fn page_url_locale() {
+        assert_eq!(locale_page_url("", "/champions"), "https://www.op.gg/lol/champions");
+        assert_eq!(locale_page_url("zh-cn", "/champions"), "https://www.op.gg/zh-cn/lol/champions");
+        assert_eq!(champion_list_url("", "kr"), "https://www.op.gg/lol/champions?position=all&region=kr");
+        assert_eq!(
+            locale_champion_page_url("en", "global", "ahri", "counters", "mid"),
+            "https://www.op.gg/en/lol/champions/ahri/counters/mid?region=global&tier=emerald_plus"
+        );
+    }
+
+    fn counter(key: &str) -> CounterEntry {
+        CounterEntry { key: key.into(), win_rate: 50.0, games: 100 }
+    }
+
+    /// 按给定顺序插入克制数据的缓存
+    fn cache_with(keys: &[&str]) -> OpggCache {
+        let mut cache = OpggCache::default();
+        for k in keys {
+            cache.counters.insert(k.to_string(), vec![counter("zed")]);
+        }
+        cache
+    }
+
+    #[test]
+    fn fallback_position_is_stable() {
+        // 无默认位置的英雄按 FALLBACK_POSITIONS 顺序回退，与插入顺序无关
+        for keys in [["newchamp:SUPPORT", "newchamp:MID", "newchamp:TOP"], ["newchamp:TOP", "newchamp:MID", "newchamp:SUPPORT"]] {
+            let cache = cache_with(&keys);
+            for _ in 0..20 {
+                assert_eq!(counter_fallback_position(&cache, "newchamp", "JUNGLE"), Some("TOP"));
+            }
+        }
+        // 有默认位置时先回退到默认位置
+        let cache = cache_with(&["ahri:TOP", "ahri:MID"]);
+        assert_eq!(counter_fallback_position(&cache, "ahri", "JUNGLE"), Some("MID"));
+        // 指定位置有数据时不算回退
+        assert_eq!(counter_fallback_position(&cache, "ahri", "TOP"), None);
+    }
+
+    /// counters 中的单条数据：key 打 host 的胜率
+    fn entry(key: &str, win_rate: f64, games: i64) -> CounterEntry {
+        CounterEntry { key: key.into(), win_rate, games }
+    }
+
+    #[test]
+    fn matchup_win_rate_direction() {
+        let mut cache = OpggCache::default();
+        // 阿狸表里劫打阿狸 54%，安妮表里阿狸打安妮 45%
+        cache.counters.insert("ahri:MID".into(), vec![entry("zed", 54.0, 1000)]);
+        cache.counters.insert("annie:MID".into(), vec![entry("ahri", 45.0, 300)]);
+
+        assert_eq!(matchup_win_rate(&cache, &CounterOverrides::new(), "zed", "ahri", "MIDDLE"), Some((54.0, 1000)));
+        // 没有劫的克制表时由阿狸表反推
+        assert_eq!(matchup_win_rate(&cache, &CounterOverrides::new(), "ahri", "zed", "MIDDLE"), Some((46.0, 1000)));
+        assert_eq!(matchup_win_rate(&cache, &CounterOverrides::new(), "ahri", "annie", "MIDDLE"), Some((45.0, 300)));
+        assert_eq!(matchup_win_rate(&cache, &CounterOverrides::new(), "annie", "ahri", "MIDDLE"), Some((55.0, 300)));
+        assert_eq!(matchup_win_rate(&cache, &CounterOverrides::new(), "annie", "zed", "MIDDLE"), None);
+    }
+
+    #[test]
+    fn good_picks_against_order() {
+        let mut cache = OpggCache::default();
+        // 劫打安妮 44%（安妮优势），劫打阿狸 53%（阿狸劣势）
+        cache.counters.insert("annie:MID".into(), vec![entry("zed", 44.0, 500)]);
+        cache.counters.insert("ahri:MID".into(), vec![entry("zed", 53.0, 800)]);
+        cache.baseline_win_rates.insert("annie:MID".into(), 51.0);
+
+        let mut picks = get_good_picks_against(&cache, &CounterOverrides::new(), "zed", "MIDDLE");
+        picks.sort_by(|a, b| b.win_rate.total_cmp(&a.win_rate));
+        let order: Vec<(&str, f64)> = picks.iter().map(|p| (p.key.as_str(), p.win_rate)).collect();
+        assert_eq!(order, [("annie", 56.0), ("ahri", 47.0)]);
+        assert_eq!(picks[0].delta, Some(5.0));
+        assert_eq!(picks[1].delta, None);
+    }
+
+    #[test]
+    fn pool_threats_keep_counters() {
+        let mut cache = OpggCache::default();
+        // 劫打阿狸 55%、打安妮 54%；阿狸打安妮只有 40%（不是威胁）；维克托打阿狸 51%（不到阈值）
+        cache.counters.insert("ahri:MID".into(), vec![entry("zed", 55.0, 900), entry("viktor", 51.0, 400)]);
+        cache.counters.insert("annie:MID".into(), vec![entry("zed", 54.0, 100), entry("ahri", 40.0, 300)]);
+
+        let threats = pool_threats(&cache, &["ahri".into(), "annie".into()], 50, 48.0);
+        assert_eq!(threats.len(), 1);
+        assert_eq!(threats[0].key, "zed");
+        assert_eq!(threats[0].victims, ["ahri", "annie"]);
+        // 收藏英雄视角的加权胜率：(45 * 900 + 46 * 100) / 1000
+        assert!((threats[0].avg_win_rate - 45.1).abs() < 1e-9);
+
+        // 场次不足的对局不计入
+        let threats = pool_threats(&cache, &["ahri".into(), "annie".into()], 200, 48.0);
+        assert_eq!(threats[0].victims, ["ahri"]);
+    }
+
+    #[test]
+    fn overrides_apply_everywhere() {
+        let mut cache = OpggCache::default();
+        cache.counters.insert("ahri:MID".into(), vec![entry("zed", 55.0, 900)]);
+        let overrides: CounterOverrides = [
+            ("ahri:MID:zed".to_string(), CounterOverride { win_rate: 40.0, games: 20 }),
+            ("ahri:MID:annie".to_string(), CounterOverride { win_rate: 52.0, games: 10 }),
+        ]
+        .into();
+
+        let rows = get_counters_for_champion(&cache, &overrides, "ahri", "MIDDLE");
+        let rows: Vec<(&str, f64, bool)> = rows.iter().map(|r| (r.key.as_str(), r.win_rate, r.overridden)).collect();
+        assert_eq!(rows, [("zed", 40.0, true), ("annie", 52.0, true)]);
+
+        // 对位胜率（AI 提示词、热力图、威胁评分）与克制表用同一份手动数据
+        assert_eq!(matchup_win_rate(&cache, &overrides, "zed", "ahri", "MIDDLE"), Some((40.0, 20)));
+        assert_eq!(matchup_win_rate(&cache, &overrides, "ahri", "annie", "MIDDLE"), Some((48.0, 10)));
+        let picks = get_good_picks_against(&cache, &overrides, "zed", "MIDDLE");
+        assert_eq!((picks[0].win_rate, picks[0].overridden), (60.0, true));
+    }
+
+    #[test]
+    fn fallback_position_all_positions() {
+        let cache = cache_with(&["ahri:TOP", "ahri:MID"]);
+        assert_eq!(counter_fallback_position(&cache, "ahri", crate::types::ALL_POSITIONS), None);
+    }
+
+    #[test]
+    fn empty_pages_are_not_missing() {
+        let mut cache = OpggCache {
+            entry_keys: vec!["ahri:MID".into(), "ahri:TOP".into(), "zed:MID".into()],
+            ..Default::default()
+        };
+        let fetched = [("ahri:MID".to_string(), vec![counter("zed")])].into_iter().collect();
+        apply_scrape(&mut cache, fetched, vec!["ahri:TOP".into()], 100.0);
+        // ahri:TOP 页面为空，记录过采集时间后不再算失败项；zed:MID 请求失败，仍需补采
+        assert_eq!(missing_counter_keys(&cache), ["zed:MID"]);
+        assert_eq!(empty_counter_count(&cache), 1);
+        assert!(!cache.counters.contains_key("ahri:TOP"));
+
+        // 补采后有了数据，不再算空页面
+        let fetched = [("ahri:TOP".to_string(), vec![counter("zed")])].into_iter().collect();
+        apply_scrape(&mut cache, fetched, vec![], 200.0);
+        assert_eq!(empty_counter_count(&cache), 0);
+        assert_eq!(cache.counter_updated_at["ahri:TOP"], 200.0);
+    }
+
+    #[test]
+    fn empty_pages_accumulate() {
+        // 多次补采中各自遇到的空页面都要计入，不能被后一次覆盖
+        let mut cache = OpggCache {
+            entry_keys: vec!["ahri:MID".into(), "ahri:TOP".into(), "zed:MID".into()],
+            ..Default::default()
+        };
+        apply_scrape(&mut cache, Default::default(), vec!["ahri:TOP".into()], 100.0);
+        apply_scrape(&mut cache, Default::default(), vec!["zed:MID".into()], 200.0);
+        assert_eq!(empty_counter_count(&cache), 2);
+        assert_eq!(missing_counter_keys(&cache), ["ahri:MID"]);
+    }
+
+    #[test]
+    fn counters_fixture() {
+        // 页面里先出现的出装 data 没有 champion 字段，不能被当成克制列表
+        let counters = parse_counters(COUNTERS_AHRI_MID);
+        assert_eq!(counters.len(), 4);
+        assert_eq!(counters[0].key, "zed");
+        assert_eq!(counters[0].win_rate, 46.09);
+        assert_eq!(counters[0].games, 1523);
+        assert_eq!(counters[2].key, "annie");
+        assert_eq!(counters[2].win_rate, 55.0);
+        assert_eq!(parse_patch(COUNTERS_AHRI_MID), "14.23");
+    }
+
+    #[test]
+    fn no_rsc_payload() {
+        let html = "<!DOCTYPE html><html><body><h1>Access denied</h1></body></html>";
+        assert!(!has_rsc_payload(html));
+        assert!(parse_counters(html).is_empty());
+        let (entries, names, patch) = parse_champion_list(html);
+        assert!(entries.is_empty() && names.is_empty() && patch.is_empty());
+    }
+
+    #[test]
+    fn rsc_payload_without_data() {
+        // 只有框架自身的 push（短块 / 非 JSON），页面结构变化时就是这种情况
+        let html = CHAMPIONS_ZH.split("<script>self.__next_f.push([1,\"4:").next().unwrap();
+        assert!(has_rsc_payload(html));
+        assert!(parse_champion_list(html).0.is_empty());
+        assert!(parse_counters(CHAMPIONS_ZH).is_empty());
+    }
+
+    /// 重新下载 tests/fixtures 下的页面：cargo test refresh_fixtures -- --ignored
+    ///
+    /// 下载后立即用解析函数检查真实页面能解析出数据，解析不到时测试失败（页面仍会写入，方便排查）。
+    /// 页面结构变化导致解析失败时先刷新样本，再按新结构修改解析和上面的断言。
+    #[test]
+    #[ignore]
+    fn refresh_fixtures() {
+        // 与程序实际请求的 URL 一致（含英文页的无语言前缀路径）
+        let pages = [
+            ("champions_zh-cn.html", champion_list_url("zh-cn", "global")),
+            ("champions_en.html", champion_list_url("", "global")),
+            ("counters_ahri_mid.html", locale_champion_page_url("zh-cn", "global", "ahri", "counters", "mid")),
+        ];
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let client = crate::config::http_client("", std::time::Duration::from_secs(15)).unwrap();
+        for (file, url) in pages {
+            let html = rt
+                .block_on(async { client.get(&url).header("User-Agent", OPGG_UA).send().await?.text().await })
+                .unwrap_or_else(|e| panic!("{url}: {e}"));
+            assert!(has_rsc_payload(&html), "{url} 不含 RSC 数据");
+            std::fs::write(dir.join(file), &html).unwrap();
+            let parsed = if file.starts_with("counters_") {
+                parse_counters(&html).len()
+            } else {
+                parse_champion_list(&html).0.len()
+            };
+            assert!(parsed > 0, "{url}：{NO_DATA_HINT}");
+            assert!(!parse_patch(&html).is_empty(), "{url} 解析不到版本号");
+        }
+    }
 }
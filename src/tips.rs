@@ -0,0 +1,60 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// 内置对线小贴士（未配置 AI 时也能给出一句通用提示），按 LCU champion-summary 的 roles 匹配
+#[derive(Debug, Deserialize)]
+struct TipTable {
+    /// "我方定位>敌方定位" → 提示
+    #[serde(default)]
+    pairs: HashMap<String, String>,
+    /// 敌方定位 → 提示（没有匹配的组合时使用）
+    #[serde(default)]
+    enemy: HashMap<String, String>,
+}
+
+fn table() -> &'static TipTable {
+    static TABLE: OnceLock<TipTable> = OnceLock::new();
+    TABLE.get_or_init(|| serde_json::from_str(include_str!("matchup_tips.json")).expect("matchup_tips.json"))
+}
+
+/// 按双方定位找一条提示：敌方主定位优先，先找组合提示再退回只看敌方定位；没有定位数据时为 None
+pub fn matchup_tip(my_roles: &[String], enemy_roles: &[String]) -> Option<&'static str> {
+    let t = table();
+    enemy_roles
+        .iter()
+        .find_map(|e| my_roles.iter().find_map(|m| t.pairs.get(&format!("{m}>{e}"))))
+        .or_else(|| enemy_roles.iter().find_map(|e| t.enemy.get(e)))
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// LCU champion-summary 中的定位标签
+    const ROLES: [&str; 6] = ["assassin", "fighter", "mage", "marksman", "support", "tank"];
+
+    #[test]
+    fn tip_table_keys_are_roles() {
+        let t = table();
+        assert!(!t.pairs.is_empty() && !t.enemy.is_empty());
+        for key in t.pairs.keys() {
+            let (my, enemy) = key.split_once('>').unwrap_or_else(|| panic!("{key} 应为 我方>敌方"));
+            assert!(ROLES.contains(&my) && ROLES.contains(&enemy), "{key}");
+        }
+        for key in t.enemy.keys() {
+            assert!(ROLES.contains(&key.as_str()), "{key}");
+        }
+        // 每个定位都有兜底提示
+        assert_eq!(t.enemy.len(), ROLES.len());
+    }
+
+    #[test]
+    fn tip_prefers_pair() {
+        let roles = |r: &[&str]| r.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(matchup_tip(&roles(&["mage"]), &roles(&["assassin"])), table().pairs.get("mage>assassin").map(String::as_str));
+        assert_eq!(matchup_tip(&roles(&["support"]), &roles(&["mage"])), table().enemy.get("mage").map(String::as_str));
+        assert_eq!(matchup_tip(&roles(&["mage"]), &[]), None);
+    }
+}
@@ -1,15 +1,28 @@
 #[cfg(windows)]
 mod imp {
-    use windows::Win32::Foundation::{BOOL, HWND, LPARAM, RECT, CloseHandle};
+    use windows::Win32::Foundation::{BOOL, HLOCAL, HWND, LPARAM, RECT, CloseHandle, LocalFree};
+    use windows::Win32::Security::Cryptography::{
+        CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN, CRYPT_INTEGER_BLOB,
+    };
     use windows::Win32::System::Threading::{
-        OpenProcess, QueryFullProcessImageNameW, PROCESS_QUERY_LIMITED_INFORMATION,
-        PROCESS_NAME_FORMAT,
+        GetCurrentProcessId, OpenProcess, QueryFullProcessImageNameW,
+        PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_NAME_FORMAT,
+    };
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        RegisterHotKey, HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+    };
+    use windows::Win32::UI::Controls::Dialogs::{
+        GetOpenFileNameW, OFN_FILEMUSTEXIST, OFN_NOCHANGEDIR, OFN_PATHMUSTEXIST, OPENFILENAMEW,
     };
+    use windows::Win32::UI::Shell::ShellExecuteW;
     use windows::Win32::UI::WindowsAndMessaging::{
-        EnumWindows, GetSystemMetrics, GetWindow, GetWindowRect, GetWindowThreadProcessId,
-        IsIconic, IsWindowVisible, GW_OWNER, SM_CXVIRTUALSCREEN, SM_CYVIRTUALSCREEN,
-        SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+        EnumWindows, GetMessageW, GetSystemMetrics, GetWindow, GetWindowRect,
+        GetWindowThreadProcessId, IsIconic, IsWindowVisible, SetForegroundWindow, SetWindowDisplayAffinity,
+        ShowWindow, GW_OWNER, MSG, SM_CXSCREEN, SM_CXVIRTUALSCREEN, SM_CYSCREEN, SM_CYVIRTUALSCREEN,
+        SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, SW_HIDE, SW_SHOW, SW_SHOWNORMAL, WDA_EXCLUDEFROMCAPTURE, WDA_NONE,
+        WM_HOTKEY,
     };
+    use windows::core::{w, PCWSTR};
 
     /// LoL 客户端窗口信息
     #[derive(Debug, Clone)]
@@ -145,11 +158,208 @@ mod imp {
             (x, y, w, h)
         }
     }
+
+    /// 获取主显示器尺寸 (w, h)，主显示器左上角固定为 (0, 0)
+    pub fn primary_screen_size() -> (i32, i32) {
+        unsafe { (GetSystemMetrics(SM_CXSCREEN), GetSystemMetrics(SM_CYSCREEN)) }
+    }
+
+    /// 设置本进程顶层窗口是否从截图/录屏中排除（需 Win10 2004+）
+    pub fn set_capture_excluded(exclude: bool) -> Result<(), String> {
+        let mut own: Vec<isize> = Vec::new();
+        unsafe {
+            let _ = EnumWindows(Some(own_window_callback), LPARAM(&mut own as *mut _ as isize));
+        }
+        if own.is_empty() {
+            return Err("未找到本程序窗口".into());
+        }
+        let affinity = if exclude { WDA_EXCLUDEFROMCAPTURE } else { WDA_NONE };
+        for hwnd in own {
+            unsafe {
+                SetWindowDisplayAffinity(HWND(hwnd as *mut _), affinity)
+                    .map_err(|e| format!("设置窗口捕获属性失败: {e}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// toggle_own_windows 隐藏的窗口（再次切换时只恢复这些，不会显示本来就隐藏的辅助窗口）
+    static HIDDEN_WINDOWS: std::sync::Mutex<Vec<isize>> = std::sync::Mutex::new(Vec::new());
+
+    /// 切换本进程可见顶层窗口的显示，返回切换后是否为隐藏
+    ///
+    /// 直接调用 ShowWindow 而不是通过 eframe：窗口隐藏后 eframe 可能不再调用 update()，
+    /// 界面线程就收不到「重新显示」的消息了。可在快捷键线程中调用。
+    pub fn toggle_own_windows() -> Result<bool, String> {
+        let mut hidden = HIDDEN_WINDOWS.lock().map_err(|_| "窗口状态锁已损坏".to_string())?;
+        if !hidden.is_empty() {
+            for hwnd in hidden.drain(..) {
+                let hwnd = HWND(hwnd as *mut _);
+                unsafe {
+                    let _ = ShowWindow(hwnd, SW_SHOW);
+                    let _ = SetForegroundWindow(hwnd);
+                }
+            }
+            return Ok(false);
+        }
+        let mut own: Vec<isize> = Vec::new();
+        unsafe {
+            let _ = EnumWindows(Some(own_window_callback), LPARAM(&mut own as *mut _ as isize));
+        }
+        own.retain(|&hwnd| unsafe { IsWindowVisible(HWND(hwnd as *mut _)).as_bool() });
+        if own.is_empty() {
+            return Err("未找到本程序窗口".into());
+        }
+        for &hwnd in &own {
+            unsafe {
+                let _ = ShowWindow(HWND(hwnd as *mut _), SW_HIDE);
+            }
+        }
+        *hidden = own;
+        Ok(true)
+    }
+
+    unsafe extern "system" fn own_window_callback(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let own = &mut *(lparam.0 as *mut Vec<isize>);
+        let mut pid: u32 = 0;
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        if pid != GetCurrentProcessId() {
+            return BOOL(1);
+        }
+        if let Ok(h) = GetWindow(hwnd, GW_OWNER) {
+            if !h.is_invalid() {
+                return BOOL(1);
+            }
+        }
+        own.push(hwnd.0 as isize);
+        BOOL(1)
+    }
+
+    /// 用默认浏览器打开 URL
+    pub fn open_url(url: &str) -> Result<(), String> {
+        let wide: Vec<u16> = url.encode_utf16().chain(std::iter::once(0)).collect();
+        let ret = unsafe {
+            ShellExecuteW(
+                HWND::default(),
+                w!("open"),
+                PCWSTR(wide.as_ptr()),
+                PCWSTR::null(),
+                PCWSTR::null(),
+                SW_SHOWNORMAL,
+            )
+        };
+        // 返回值大于 32 表示成功
+        if ret.0 as isize > 32 {
+            Ok(())
+        } else {
+            Err(format!("打开浏览器失败（错误码 {}）", ret.0 as isize))
+        }
+    }
+
+    /// 系统「打开文件」对话框，只列出 .json 文件；取消时返回 None（阻塞直到对话框关闭）
+    pub fn pick_json_file(title: &str) -> Option<std::path::PathBuf> {
+        let title: Vec<u16> = title.encode_utf16().chain(std::iter::once(0)).collect();
+        // 过滤器为「描述\0模式\0」成对排列，以两个 \0 结束
+        let filter: Vec<u16> = "JSON 文件 (*.json)\0*.json\0所有文件\0*.*\0\0".encode_utf16().collect();
+        let mut file = vec![0u16; 1024];
+        let mut ofn = OPENFILENAMEW {
+            lStructSize: std::mem::size_of::<OPENFILENAMEW>() as u32,
+            lpstrFilter: PCWSTR(filter.as_ptr()),
+            lpstrFile: windows::core::PWSTR(file.as_mut_ptr()),
+            nMaxFile: file.len() as u32,
+            lpstrTitle: PCWSTR(title.as_ptr()),
+            Flags: OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST | OFN_NOCHANGEDIR,
+            ..Default::default()
+        };
+        if !unsafe { GetOpenFileNameW(&mut ofn) }.as_bool() {
+            return None;
+        }
+        let len = file.iter().position(|&c| c == 0).unwrap_or(file.len());
+        Some(std::path::PathBuf::from(String::from_utf16_lossy(&file[..len])))
+    }
+
+    /// 用 DPAPI 加密数据（绑定当前 Windows 用户，换机器/账户无法解密）
+    pub fn protect_data(data: &[u8]) -> Result<Vec<u8>, String> {
+        let input = CRYPT_INTEGER_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+        let mut output = CRYPT_INTEGER_BLOB::default();
+        unsafe {
+            CryptProtectData(&input, PCWSTR::null(), None, None, None, CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+                .map_err(|e| format!("加密失败: {e}"))?;
+            Ok(take_blob(output))
+        }
+    }
+
+    /// 解密 protect_data 的结果
+    pub fn unprotect_data(data: &[u8]) -> Result<Vec<u8>, String> {
+        let input = CRYPT_INTEGER_BLOB { cbData: data.len() as u32, pbData: data.as_ptr() as *mut u8 };
+        let mut output = CRYPT_INTEGER_BLOB::default();
+        unsafe {
+            CryptUnprotectData(&input, None, None, None, None, CRYPTPROTECT_UI_FORBIDDEN, &mut output)
+                .map_err(|e| format!("解密失败（可能不是本机/本用户加密的）: {e}"))?;
+            Ok(take_blob(output))
+        }
+    }
+
+    /// 复制 DPAPI 输出并释放系统分配的内存
+    unsafe fn take_blob(blob: CRYPT_INTEGER_BLOB) -> Vec<u8> {
+        let out = std::slice::from_raw_parts(blob.pbData, blob.cbData as usize).to_vec();
+        let _ = LocalFree(HLOCAL(blob.pbData as *mut _));
+        out
+    }
+
+    /// 解析 "Ctrl+Alt+L" 形式的快捷键 → (修饰键, 虚拟键码)
+    fn parse_hotkey(spec: &str) -> Result<(HOT_KEY_MODIFIERS, u32), String> {
+        let mut mods = MOD_NOREPEAT;
+        let mut vk = None;
+        for part in spec.split('+').map(|p| p.trim().to_uppercase()) {
+            match part.as_str() {
+                "CTRL" | "CONTROL" => mods |= MOD_CONTROL,
+                "ALT" => mods |= MOD_ALT,
+                "SHIFT" => mods |= MOD_SHIFT,
+                "WIN" => mods |= MOD_WIN,
+                k if k.len() == 1 && k.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                    vk = Some(k.as_bytes()[0] as u32);
+                }
+                k if k.starts_with('F') => match k[1..].parse::<u32>() {
+                    Ok(n @ 1..=12) => vk = Some(0x70 + n - 1), // VK_F1 = 0x70
+                    _ => return Err(format!("快捷键无效：{spec}")),
+                },
+                _ => return Err(format!("快捷键无效：{spec}")),
+            }
+        }
+        vk.map(|vk| (mods, vk)).ok_or_else(|| format!("快捷键缺少按键：{spec}"))
+    }
+
+    /// 在独立线程注册全局快捷键，按下时调用 `on_press`
+    pub fn spawn_hotkey_listener(
+        spec: &str,
+        on_press: impl Fn() + Send + 'static,
+    ) -> Result<(), String> {
+        let (mods, vk) = parse_hotkey(spec)?;
+        let spec = spec.to_string();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || unsafe {
+            // 热键消息投递到注册线程的消息队列
+            if RegisterHotKey(HWND::default(), 1, mods, vk).is_err() {
+                let _ = ready_tx.send(Err(format!("快捷键 {spec} 注册失败（可能已被占用）")));
+                return;
+            }
+            let _ = ready_tx.send(Ok(()));
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {
+                if msg.message == WM_HOTKEY {
+                    on_press();
+                }
+            }
+        });
+        ready_rx.recv().unwrap_or_else(|_| Err("快捷键线程启动失败".into()))
+    }
 }
 
 #[cfg(not(windows))]
 mod imp {
     #[derive(Debug, Clone)]
+    #[allow(dead_code)]
     pub struct LolWindow {
         pub hwnd: isize,
         pub left: i32,
@@ -166,6 +376,46 @@ mod imp {
     pub fn virtual_screen_rect() -> (i32, i32, i32, i32) {
         (0, 0, 1920, 1080)
     }
+
+    pub fn primary_screen_size() -> (i32, i32) {
+        (1920, 1080)
+    }
+
+    pub fn set_capture_excluded(_exclude: bool) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn spawn_hotkey_listener(
+        _spec: &str,
+        _on_press: impl Fn() + Send + 'static,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    pub fn toggle_own_windows() -> Result<bool, String> {
+        Err("显示/隐藏窗口仅支持 Windows".into())
+    }
+
+    pub fn pick_json_file(_title: &str) -> Option<std::path::PathBuf> {
+        None
+    }
+
+    pub fn protect_data(_data: &[u8]) -> Result<Vec<u8>, String> {
+        Err("配置加密仅支持 Windows".into())
+    }
+
+    pub fn unprotect_data(_data: &[u8]) -> Result<Vec<u8>, String> {
+        Err("配置加密仅支持 Windows".into())
+    }
+
+    pub fn open_url(url: &str) -> Result<(), String> {
+        let opener = if cfg!(target_os = "macos") { "open" } else { "xdg-open" };
+        std::process::Command::new(opener)
+            .arg(url)
+            .spawn()
+            .map(|_| ())
+            .map_err(|e| format!("打开浏览器失败: {e}"))
+    }
 }
 
 pub use imp::*;